@@ -0,0 +1,60 @@
+// This example shows how to draw without a window. The result is written to a PNG file. This works
+// without a display, so it's suitable for CI or generating thumbnails
+
+fn main() {
+    // Setup logging
+    env_logger::Builder::from_default_env()
+        .filter_level(log::LevelFilter::Debug)
+        .init();
+
+    // Create the renderer, which will draw to an offscreen surface
+    let renderer = skulpin::RendererBuilder::new()
+        .use_vulkan_debug_layer(true)
+        .build_headless(900, 600);
+
+    // Check if there were error setting up vulkan
+    if let Err(e) = renderer {
+        println!("Error during renderer construction: {}", e);
+        return;
+    }
+
+    let mut renderer = renderer.unwrap();
+
+    // Draw a single frame and read back the pixels
+    let frame = match renderer.draw(|canvas| draw(canvas)) {
+        Ok(frame) => frame,
+        Err(e) => {
+            println!("Error during draw: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = frame.write_png("headless.png") {
+        println!("Error writing png: {:?}", e);
+        return;
+    }
+
+    println!("Wrote {}x{} image to headless.png", frame.width, frame.height);
+}
+
+fn draw(canvas: &mut skia_safe::Canvas) {
+    canvas.clear(skia_safe::Color::from_argb(255, 0, 0, 0));
+
+    // Make a color to draw with
+    let mut paint = skia_safe::Paint::new(skia_safe::Color4f::new(0.0, 0.5, 1.0, 1.0), None);
+    paint.set_anti_alias(true);
+    paint.set_style(skia_safe::paint::Style::Stroke);
+    paint.set_stroke_width(2.0);
+
+    // Draw a circle
+    canvas.draw_circle(
+        skia_safe::Point::new(450.0, 420.0),
+        50.0,
+        &paint
+    );
+
+    let mut font = skia_safe::Font::default();
+    font.set_size(100.0);
+
+    canvas.draw_str("Hello Skulpin", (65, 200), &font, &paint);
+}
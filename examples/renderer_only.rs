@@ -25,7 +25,7 @@ fn main() {
 
     // Check if there were error setting up vulkan
     if let Err(e) = renderer {
-        println!("Error during renderer construction: {:?}", e);
+        println!("Error during renderer construction: {}", e);
         return;
     }
 
@@ -87,7 +87,7 @@ fn main() {
                     draw(canvas, frame_count);
                     frame_count += 1;
                 }) {
-                    println!("Error during draw: {:?}", e);
+                    println!("Error during draw: {}", e);
                     *control_flow = winit::event_loop::ControlFlow::Exit
                 }
             },
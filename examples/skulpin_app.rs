@@ -4,6 +4,7 @@
 
 use skulpin::AppHandler;
 use skulpin::AppControl;
+use skulpin::AppWindowId;
 use skulpin::InputState;
 use skulpin::TimeState;
 use skulpin::VirtualKeyCode;
@@ -53,6 +54,7 @@ impl AppHandler for ExampleApp {
     fn draw(
         &mut self,
         _app_control: &AppControl,
+        _window_id: AppWindowId,
         _input_state: &InputState,
         time_state: &TimeState,
         canvas: &mut skia_safe::Canvas
@@ -0,0 +1,15 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform sampler2D texSampler;
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 outColor;
+
+// Skia draws premultiplied colors, a swapchain with post-multiplied composite alpha expects them
+// without alpha applied
+void main() {
+    vec4 color = texture(texSampler, fragTexCoord);
+    outColor = vec4(color.rgb / max(color.a, 0.00001), color.a);
+}
@@ -4,12 +4,101 @@ use super::input_state::InputState;
 use super::time_state::TimeState;
 use super::time_state::TimeContext;
 use super::util::PeriodicEvent;
+use super::perf_overlay::PerfOverlay;
+use super::input_state::VirtualKeyCode;
+use super::input_recording::InputRecorder;
+use super::input_recording::ScriptedInputEvent;
+use super::gamepad::GamepadBackend;
+use super::gamepad::default_gamepad_backend;
+use super::app_window::AppWindow;
+use super::app_window::AppWindowId;
+use super::app_window::AppWindowOptions;
+use super::app_window::FullscreenMode;
+use super::app_window::Icon;
+use super::app_window::apply_window_request;
+use crate::profiler::Profiler;
+use crate::profiler::ScopeTimer;
 use std::ffi::CString;
+use std::path::Path;
+use std::path::PathBuf;
+use std::rc::Rc;
 
+use crate::Renderer;
 use crate::RendererBuilder;
+use crate::RendererBackend;
+use crate::DeviceSelectionPolicy;
+use crate::PresentMode;
+use crate::CompositeAlpha;
 use winit::dpi::LogicalSize;
+use winit::dpi::LogicalPosition;
+
+/// Determines when the app updates and draws
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RedrawMode {
+    /// Update and draw every frame, as fast as vsync or the target frame rate allows
+    Continuous,
+
+    /// Only update and draw when input arrives or `AppControl::request_redraw` is called. The
+    /// event loop sleeps in between, so an idle app uses no CPU. Note that the time between
+    /// updates can be large.
+    Reactive
+}
+
+/// Settings for calling `AppHandler::update` at a fixed rate, independent of the frame rate. This
+/// makes simulations deterministic. `TimeState::interpolation_alpha` can be used to smooth
+/// drawing between updates.
+#[derive(Copy, Clone, Debug)]
+pub struct FixedTimestep {
+    /// Time that passes in each call to update
+    pub step: std::time::Duration,
+
+    /// If a frame takes so long that more updates than this are needed to catch up, the extra time
+    /// is dropped. This keeps slow updates from snowballing.
+    pub max_updates_per_frame: u32
+}
+
+impl FixedTimestep {
+    const DEFAULT_UPDATES_PER_SECOND: f32 = 60.0;
+
+    /// A rate that isn't a positive number is replaced by 60 updates per second, with a warning
+    pub fn new(updates_per_second: f32) -> Self {
+        let updates_per_second = if is_valid_rate(updates_per_second) {
+            updates_per_second
+        } else {
+            warn!(
+                "Invalid fixed timestep rate {}, using {} updates per second",
+                updates_per_second,
+                Self::DEFAULT_UPDATES_PER_SECOND
+            );
+            Self::DEFAULT_UPDATES_PER_SECOND
+        };
+
+        FixedTimestep {
+            step: std::time::Duration::from_secs_f32(1.0 / updates_per_second),
+            max_updates_per_frame: 5
+        }
+    }
+
+    /// Must be at least 1, otherwise update would never be called. 0 is ignored with a warning.
+    pub fn max_updates_per_frame(mut self, max_updates_per_frame: u32) -> Self {
+        if max_updates_per_frame == 0 {
+            warn!("max_updates_per_frame must be at least 1, keeping {}", self.max_updates_per_frame);
+            return self;
+        }
+
+        self.max_updates_per_frame = max_updates_per_frame;
+        self
+    }
+}
+
+// True if the rate is positive and small enough that its period can be represented as a Duration
+// (Duration::from_secs_f32 panics on NaN, negative and infinite values)
+fn is_valid_rate(rate: f32) -> bool {
+    rate > 0.0 && (1.0 / rate).is_finite()
+}
 
 pub trait AppHandler {
+    /// Called once per update with the main window's input
     fn update(
         &mut self,
         app_control: &mut AppControl,
@@ -17,51 +106,331 @@ pub trait AppHandler {
         time_state: &TimeState
     );
 
+    /// Called after `update` for each window opened with `AppControl::open_window`, with that
+    /// window's input
+    fn update_window(
+        &mut self,
+        _app_control: &mut AppControl,
+        _window_id: AppWindowId,
+        _input_state: &InputState,
+        _time_state: &TimeState
+    ) {
+
+    }
+
+    /// Called once per frame for every open window. The main window is `AppWindowId::MAIN`
     fn draw(
         &mut self,
         app_control: &AppControl,
+        window_id: AppWindowId,
         input_state: &InputState,
         time_state: &TimeState,
         canvas: &mut skia_safe::Canvas
     );
+
+    /// Called when the user tries to close a window (i.e. clicks the close button). Return false to
+    /// keep it open, for example to ask whether to save changes first. The window can be closed
+    /// later with `AppControl::close_window`. Closing the main window terminates the app
+    fn close_requested(
+        &mut self,
+        _app_control: &mut AppControl,
+        _window_id: AppWindowId
+    ) -> bool {
+        true
+    }
+
+    /// Called when a window gains or loses keyboard focus
+    fn focus_changed(
+        &mut self,
+        _app_control: &mut AppControl,
+        _window_id: AppWindowId,
+        _focused: bool
+    ) {
+
+    }
+
+    /// Called when a window is minimized or restored. winit has no minimize event, so this is
+    /// detected by the window being resized to zero size. Windows does this, but on X11, Wayland and
+    /// macOS a minimized window usually keeps its size, so this may never be called there. Windows
+    /// hidden behind other windows (occluded) are not detected on any platform.
+    ///
+    /// Updating and drawing pause while every window is minimized or has a surface the renderer
+    /// reports as zero-sized. The latter catches some cases where no zero-size resize is reported
+    fn minimized_changed(
+        &mut self,
+        _app_control: &mut AppControl,
+        _window_id: AppWindowId,
+        _minimized: bool
+    ) {
+
+    }
+
+    /// Called when a window is resized, but not when it is minimized
+    fn resized(
+        &mut self,
+        _app_control: &mut AppControl,
+        _window_id: AppWindowId,
+        _logical_size: LogicalSize
+    ) {
+
+    }
+
+    /// Called when a window's DPI factor changes, usually because it moved to another monitor
+    fn dpi_factor_changed(
+        &mut self,
+        _app_control: &mut AppControl,
+        _window_id: AppWindowId,
+        _dpi_factor: f64
+    ) {
+
+    }
+
+    /// Called once when the app is exiting, before the windows and renderer are destroyed. A good
+    /// place to save state
+    fn shutdown(
+        &mut self,
+        _app_control: &mut AppControl
+    ) {
+
+    }
 }
 
 pub struct AppBuilder {
-    app_name: CString,
-    use_vulkan_debug_layer: bool,
-    logical_size: LogicalSize
+    window_options: AppWindowOptions,
+
+    // Set by title(). If None, the window title follows app_name
+    title: Option<String>,
+
+    renderer_builder: RendererBuilder,
+    redraw_mode: RedrawMode,
+    target_frame_rate: Option<f32>,
+    fixed_timestep: Option<FixedTimestep>,
+    perf_overlay_enabled: bool,
+    perf_overlay_toggle_key: Option<VirtualKeyCode>,
+    input_recording_path: Option<PathBuf>,
+    gamepad_backend_factory: Option<Rc<dyn Fn() -> Box<dyn GamepadBackend>>>
 }
 
 impl AppBuilder {
     pub fn new() -> Self {
         AppBuilder {
-            app_name: CString::new("Skulpin").unwrap(),
-            use_vulkan_debug_layer: false,
-            logical_size: LogicalSize::new(900.0, 600.0)
+            window_options: AppWindowOptions::new().logical_size(LogicalSize::new(900.0, 600.0)),
+            title: None,
+            renderer_builder: RendererBuilder::new(),
+            redraw_mode: RedrawMode::Continuous,
+            target_frame_rate: None,
+            fixed_timestep: None,
+            perf_overlay_enabled: false,
+            perf_overlay_toggle_key: Some(VirtualKeyCode::F3),
+            input_recording_path: None,
+            gamepad_backend_factory: None
         }
     }
 
+    /// The name passed to vulkan. Also used as the window title unless `title` is called
     pub fn app_name(mut self, app_name: CString) -> Self {
-        self.app_name = app_name;
+        if self.title.is_none() {
+            self.window_options.title = app_name.to_string_lossy().into_owned();
+        }
+
+        self.renderer_builder = self.renderer_builder.app_name(app_name);
         self
     }
 
     pub fn use_vulkan_debug_layer(mut self, use_vulkan_debug_layer: bool) -> Self {
-        self.use_vulkan_debug_layer = use_vulkan_debug_layer;
+        self.renderer_builder = self.renderer_builder.use_vulkan_debug_layer(use_vulkan_debug_layer);
+        self
+    }
+
+    /// Replace all settings for the main window. The individual window settings below change one
+    /// field of these options. A title set with `title` is kept
+    pub fn window_options(mut self, window_options: AppWindowOptions) -> Self {
+        self.window_options = window_options;
+        if let Some(title) = &self.title {
+            self.window_options.title = title.clone();
+        }
+
+        self
+    }
+
+    /// The window title. Takes priority over `app_name` and the title in `window_options`,
+    /// whichever order they are called in
+    pub fn title(mut self, title: &str) -> Self {
+        self.window_options.title = title.to_string();
+        self.title = Some(title.to_string());
         self
     }
 
     pub fn logical_size(mut self, logical_size: LogicalSize) -> Self {
-        self.logical_size = logical_size;
+        self.window_options.logical_size = logical_size;
+        self
+    }
+
+    pub fn min_logical_size(mut self, min_logical_size: Option<LogicalSize>) -> Self {
+        self.window_options.min_logical_size = min_logical_size;
+        self
+    }
+
+    pub fn max_logical_size(mut self, max_logical_size: Option<LogicalSize>) -> Self {
+        self.window_options.max_logical_size = max_logical_size;
+        self
+    }
+
+    pub fn resizable(mut self, resizable: bool) -> Self {
+        self.window_options.resizable = resizable;
+        self
+    }
+
+    /// Show the title bar and border. On by default
+    pub fn decorations(mut self, decorations: bool) -> Self {
+        self.window_options.decorations = decorations;
+        self
+    }
+
+    pub fn always_on_top(mut self, always_on_top: bool) -> Self {
+        self.window_options.always_on_top = always_on_top;
+        self
+    }
+
+    /// Start fullscreen. This can be changed while running with `AppControl::set_fullscreen`
+    pub fn fullscreen(mut self, fullscreen: Option<FullscreenMode>) -> Self {
+        self.window_options.fullscreen = fullscreen;
+        self
+    }
+
+    /// Where to put the window's top-left corner on the desktop. None (the default) lets the OS
+    /// choose
+    pub fn position(mut self, position: Option<LogicalPosition>) -> Self {
+        self.window_options.position = position;
+        self
+    }
+
+    pub fn icon(mut self, icon: Option<Icon>) -> Self {
+        self.window_options.icon = icon;
+        self
+    }
+
+    /// Let the desktop show through where the app draws with alpha less than 1.0. This also sets
+    /// the composite alpha to `CompositeAlpha::PreMultiplied` (or back to `Opaque`), call
+    /// `composite_alpha` afterwards to use a different one
+    pub fn transparent(mut self, transparent: bool) -> Self {
+        self.window_options.transparent = transparent;
+        self.renderer_builder = self.renderer_builder.composite_alpha(if transparent {
+            CompositeAlpha::PreMultiplied
+        } else {
+            CompositeAlpha::Opaque
+        });
+        self
+    }
+
+    /// How the window's alpha channel is composited with the desktop. Only has an effect on a
+    /// `transparent` window
+    pub fn composite_alpha(mut self, composite_alpha: CompositeAlpha) -> Self {
+        self.renderer_builder = self.renderer_builder.composite_alpha(composite_alpha);
+        self
+    }
+
+    /// Selects vulkan or software rendering. Use `RendererBackend::VulkanWithSoftwareFallback` to
+    /// keep the app running on machines without a GPU
+    pub fn renderer_backend(mut self, backend: RendererBackend) -> Self {
+        self.renderer_builder = self.renderer_builder.backend(backend);
+        self
+    }
+
+    /// Determines which GPU is used when there is more than one (i.e. laptops with both integrated
+    /// and discrete GPUs)
+    pub fn device_selection_policy(mut self, device_selection_policy: DeviceSelectionPolicy) -> Self {
+        self.renderer_builder = self.renderer_builder.device_selection_policy(device_selection_policy);
+        self
+    }
+
+    /// The present modes to try, in order of preference. Use `[PresentMode::Fifo]` for vsync. This
+    /// can be changed while running with `AppControl::set_present_mode_priority`
+    pub fn present_mode_priority(mut self, present_mode_priority: Vec<PresentMode>) -> Self {
+        self.renderer_builder = self.renderer_builder.present_mode_priority(present_mode_priority);
+        self
+    }
+
+    /// Use `RedrawMode::Reactive` to only update and draw when something changes
+    pub fn redraw_mode(mut self, redraw_mode: RedrawMode) -> Self {
+        self.redraw_mode = redraw_mode;
+        self
+    }
+
+    /// Limit how many frames per second are updated and drawn. None (the default) means no limit
+    /// other than vsync. A rate that isn't a positive number is ignored with a warning.
+    pub fn target_frame_rate(mut self, target_frame_rate: Option<f32>) -> Self {
+        self.target_frame_rate = match target_frame_rate {
+            Some(fps) if !is_valid_rate(fps) => {
+                warn!("Ignoring invalid target frame rate {}, the frame rate will not be limited", fps);
+                None
+            },
+            target_frame_rate => target_frame_rate
+        };
+        self
+    }
+
+    /// Call update zero or more times per frame at a fixed rate instead of once per frame with a
+    /// variable timestep. None (the default) uses a variable timestep.
+    pub fn fixed_timestep(mut self, fixed_timestep: Option<FixedTimestep>) -> Self {
+        self.fixed_timestep = fixed_timestep;
+        self
+    }
+
+    /// Draw a performance HUD (fps, frame time graph, update/draw/render times) on top of the app
+    pub fn perf_overlay(mut self, perf_overlay_enabled: bool) -> Self {
+        self.perf_overlay_enabled = perf_overlay_enabled;
+        self
+    }
+
+    /// The key that shows/hides the performance overlay, F3 by default. None means the overlay is
+    /// always shown when enabled.
+    pub fn perf_overlay_toggle_key(mut self, perf_overlay_toggle_key: Option<VirtualKeyCode>) -> Self {
+        self.perf_overlay_toggle_key = perf_overlay_toggle_key;
+        self
+    }
+
+    /// Record the main window's input, gamepad input and frame times to the given file. Each frame
+    /// is written as it ends, so the recording is complete even if the app crashes. The recording
+    /// can be played back with `InputReplay` or `AppTestHarness::input_recording`. Input to windows
+    /// opened with `AppControl::open_window` is not recorded, since replay has no secondary windows.
+    pub fn record_input<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.input_recording_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Read gamepads with `default_gamepad_backend` (the linux joystick API). Off by default
+    pub fn gamepads(mut self, enabled: bool) -> Self {
+        self.gamepad_backend_factory = if enabled {
+            Some(Rc::new(default_gamepad_backend) as Rc<dyn Fn() -> Box<dyn GamepadBackend>>)
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Read gamepads from a custom backend (i.e. `MockGamepadBackend`). The closure is called
+    /// when the app starts
+    pub fn gamepad_backend<F : Fn() -> Box<dyn GamepadBackend> + 'static>(mut self, create_backend: F) -> Self {
+        self.gamepad_backend_factory = Some(Rc::new(create_backend));
         self
     }
 
     pub fn run<T : 'static + AppHandler>(&self, app_handler: T) -> Result<(), Box<dyn std::error::Error>> {
         App::run(
             app_handler,
-            &self.app_name,
-            self.use_vulkan_debug_layer,
-            self.logical_size
+            &self.window_options,
+            &self.renderer_builder,
+            self.redraw_mode,
+            self.target_frame_rate,
+            self.fixed_timestep,
+            if self.perf_overlay_enabled {
+                Some(PerfOverlay::new(self.perf_overlay_toggle_key))
+            } else {
+                None
+            },
+            self.input_recording_path.clone(),
+            self.gamepad_backend_factory.as_ref().map(|create_backend| create_backend())
         )
     }
 }
@@ -75,84 +444,474 @@ impl App {
     // of returning
     pub fn run<T : 'static + AppHandler>(
         mut app_handler: T,
-        app_name: &CString,
-        use_vulkan_debug_layer: bool,
-        logical_size: LogicalSize
+        window_options: &AppWindowOptions,
+        renderer_builder: &RendererBuilder,
+        redraw_mode: RedrawMode,
+        target_frame_rate: Option<f32>,
+        fixed_timestep: Option<FixedTimestep>,
+        mut perf_overlay: Option<PerfOverlay>,
+        input_recording_path: Option<PathBuf>,
+        mut gamepad_backend: Option<Box<dyn GamepadBackend>>
     )
         -> Result<(), Box<dyn std::error::Error>>
     {
         // Create the event loop
         let event_loop = winit::event_loop::EventLoop::<()>::with_user_event();
 
-        // Create a single window
-        let window = winit::window::WindowBuilder::new()
-            .with_title("Skulpin")
-            .with_inner_size(logical_size)
-            .build(&event_loop)?;
+        // Gamepad input arrives on other threads. Let the backend wake the event loop so that a
+        // reactive app doesn't have to poll for it
+        if let Some(gamepad_backend) = &mut gamepad_backend {
+            let event_loop_proxy = event_loop.create_proxy();
+            gamepad_backend.set_waker(Box::new(move || {
+                // Only fails once the event loop has exited
+                let _ = event_loop_proxy.send_event(());
+            }));
+        }
+
+        // Create the main window
+        let window = window_options.build_window(&event_loop)?;
 
         let mut app_control = AppControl::default();
         let mut time_state = TimeState::default();
         let mut input_state = InputState::new(&window);
 
-        let mut renderer = RendererBuilder::new()
-            .use_vulkan_debug_layer(use_vulkan_debug_layer)
-            .app_name(app_name.clone())
-            .build(&window)?;
+        let mut renderer = renderer_builder.build(&window)?;
+
+        // Each frame is written to the file as it ends
+        let mut input_recorder = match input_recording_path {
+            Some(path) => {
+                info!("Recording input to {:?}", path);
+                Some(InputRecorder::create_file(&path, input_state.window_size(), input_state.dpi_factor())?)
+            },
+            None => None
+        };
 
         // To print fps once per second
         let mut print_fps_event = PeriodicEvent::default();
 
+        // Frames are not started before this instant when there is a target frame rate
+        let frame_interval = target_frame_rate
+            .filter(|fps| is_valid_rate(*fps))
+            .map(|fps| std::time::Duration::from_secs_f32(1.0 / fps));
+        let mut next_frame_instant = std::time::Instant::now();
+
+        // In reactive mode, set when input arrives or a redraw is requested. Always draw the first
+        // frame.
+        let mut needs_update = true;
+
+        // Time that has passed but has not been simulated yet when using a fixed timestep
+        let mut fixed_timestep_accumulator = std::time::Duration::from_secs(0);
+        let mut previous_frame_instant = std::time::Instant::now();
+
+        // When the main window last started drawing, for the frame time history. None after the
+        // loop has been idle, so that idle time isn't counted as a frame
+        let mut previous_draw_instant : Option<std::time::Instant> = None;
+
+        // Reused each frame to collect events from the gamepad backend
+        let mut gamepad_events = vec![];
+
+        // CPU time spent in AppHandler::update since the last draw, shown in the perf overlay
+        let mut update_time = std::time::Duration::from_secs(0);
+
+        // Windows opened with AppControl::open_window
+        let mut secondary_windows : Vec<AppWindow> = vec![];
+
+        // Set when the main window is resized to zero size
+        let mut main_window_minimized = false;
+
+        // Set while the main window is minimized or its surface has zero size
+        let mut main_window_paused = false;
+
+        // When the touch gesture clocks of the input states were last advanced
+        let mut input_clock_instant = std::time::Instant::now();
+
         // Pass control of this thread to winit until the app terminates. If this app wants to quit,
         // the update loop should send the appropriate event via the channel
         event_loop.run(move |event, window_target, control_flow| {
-            input_state.handle_winit_event(
-                &mut app_control,
-                &event,
-                window_target
-            );
+            // Advance the clocks used to time taps before handling the events that just arrived. In
+            // reactive mode the last update may have been long ago, so advancing them only when
+            // updating would time a tap from the wrong moment
+            if let winit::event::Event::NewEvents(_) = event {
+                let now_instant = std::time::Instant::now();
+                let elapsed = now_instant - input_clock_instant;
+                input_clock_instant = now_instant;
+
+                input_state.advance_time(elapsed);
+                for app_window in &mut secondary_windows {
+                    app_window.input_state.advance_time(elapsed);
+                }
+            }
+
+            let secondary_window_id = match &event {
+                winit::event::Event::WindowEvent { window_id, .. } if *window_id != window.id() => Some(*window_id),
+                _ => None
+            };
+
+            if let Some(secondary_window_id) = secondary_window_id {
+                // Events for windows that were just closed are dropped
+                if let Some(app_window) = secondary_windows.iter_mut().find(|x| x.window.id() == secondary_window_id) {
+                    match event {
+                        winit::event::Event::WindowEvent {
+                            event: winit::event::WindowEvent::RedrawRequested,
+                            ..
+                        } if app_window.is_paused => {
+                            // Nothing to draw to while minimized
+                        },
+                        winit::event::Event::WindowEvent {
+                            event: winit::event::WindowEvent::RedrawRequested,
+                            ..
+                        } => {
+                            let window_id = app_window.id;
+                            let window_input_state = &app_window.input_state;
+                            let window_renderer = &mut app_window.renderer;
 
-            match event {
-                winit::event::Event::EventsCleared => {
+                            if let Err(e) = window_renderer.draw(&app_window.window, |canvas| {
+                                let _scope_timer = ScopeTimer::new("AppHandler::draw");
+                                app_handler.draw(
+                                    &app_control,
+                                    window_id,
+                                    window_input_state,
+                                    &time_state,
+                                    canvas
+                                );
+                            }) {
+                                warn!("Error during draw of window {:?}: {}", window_id, e);
+                                app_control.close_window(window_id);
+                            }
+                        },
+                        winit::event::Event::WindowEvent { event: ref window_event, .. } => {
+                            // Closing a secondary window doesn't terminate the app
+                            let forward_to_input_state = dispatch_window_lifecycle_event(
+                                &mut app_handler,
+                                &mut app_control,
+                                app_window.id,
+                                window_event,
+                                &mut app_window.is_minimized
+                            );
 
-                    time_state.update(TimeContext::System);
+                            if forward_to_input_state {
+                                app_window.input_state.handle_winit_event(
+                                    &mut app_control,
+                                    &event,
+                                    window_target
+                                );
+                            }
 
-                    if print_fps_event.try_take_event(
-                        time_state.system().frame_start_instant,
-                        std::time::Duration::from_secs_f32(1.0)
-                    ) {
-                        debug!("fps: {}", time_state.system().fps);
+                            needs_update = true;
+                        },
+                        _ => {}
                     }
+                }
+            } else {
+                if let Some(input_recorder) = &mut input_recorder {
+                    input_recorder.record_winit_event(&event);
+                }
 
-                    app_handler.update(
+                let forward_to_input_state = match &event {
+                    winit::event::Event::WindowEvent { event: window_event, .. } => dispatch_window_lifecycle_event(
+                        &mut app_handler,
                         &mut app_control,
-                        &input_state,
-                        &time_state
+                        AppWindowId::MAIN,
+                        window_event,
+                        &mut main_window_minimized
+                    ),
+                    _ => true
+                };
+
+                if forward_to_input_state {
+                    input_state.handle_winit_event(
+                        &mut app_control,
+                        &event,
+                        window_target
                     );
+                }
 
-                    // Call this to mark the start of the next frame (i.e. "key just down" will return false)
-                    input_state.end_frame();
-
-                    // Queue a RedrawRequested event.
-                    window.request_redraw();
-                },
-                winit::event::Event::WindowEvent {
-                    event: winit::event::WindowEvent::RedrawRequested,
-                    ..
-                } => {
-                    if let Err(e) = renderer.draw(&window, |canvas| {
-                        app_handler.draw(
-                            &app_control,
-                            &input_state,
-                            &time_state,
-                            canvas
-                        );
-                    }) {
-                        //TODO: Handle Error
-                        warn!("{:?}", e);
-                        app_control.enqueue_terminate_process();
-                    }
-                },
-                _ => {}
+                match event {
+                    winit::event::Event::EventsCleared => {
+                        if let Some(gamepad_backend) = &mut gamepad_backend {
+                            gamepad_backend.poll_events(&mut gamepad_events);
+                            for gamepad_event in gamepad_events.drain(..) {
+                                if let Some(input_recorder) = &mut input_recorder {
+                                    input_recorder.record(ScriptedInputEvent::Gamepad(gamepad_event));
+                                }
+
+                                input_state.handle_gamepad_event(gamepad_event);
+                                needs_update = true;
+                            }
+                        }
+
+                        // A window is paused while minimized. The renderer can also notice a
+                        // zero-size surface that winit never reported as a zero-size resize
+                        main_window_paused = main_window_minimized ||
+                            !poll_surface_drawable(&mut renderer, &window, &mut app_control, AppWindowId::MAIN);
+
+                        for app_window in &mut secondary_windows {
+                            app_window.is_paused = app_window.is_minimized ||
+                                !poll_surface_drawable(&mut app_window.renderer, &app_window.window, &mut app_control, app_window.id);
+                        }
+
+                        // Nothing is visible while every window is paused, so don't update or
+                        // draw until one is restored
+                        let all_windows_paused = main_window_paused &&
+                            secondary_windows.iter().all(|x| x.is_paused);
+
+                        let now_instant = std::time::Instant::now();
+                        let wants_update = !all_windows_paused && match redraw_mode {
+                            RedrawMode::Continuous => true,
+                            RedrawMode::Reactive => needs_update
+                        };
+
+                        if all_windows_paused {
+                            // Don't try to catch up on time spent minimized once a window is restored
+                            previous_frame_instant = now_instant;
+                            time_state.discard_elapsed_time();
+                        }
+
+                        if wants_update && now_instant >= next_frame_instant {
+                            needs_update = false;
+
+                            let update_count = match fixed_timestep {
+                                Some(fixed_timestep) => {
+                                    fixed_timestep_accumulator += now_instant - previous_frame_instant;
+
+                                    let mut update_count = 0;
+                                    while fixed_timestep_accumulator >= fixed_timestep.step {
+                                        if update_count >= fixed_timestep.max_updates_per_frame {
+                                            // Too far behind to catch up, drop the remaining time
+                                            fixed_timestep_accumulator = std::time::Duration::from_secs(0);
+                                            break;
+                                        }
+
+                                        fixed_timestep_accumulator -= fixed_timestep.step;
+                                        update_count += 1;
+                                    }
+
+                                    time_state.set_interpolation_alpha(
+                                        fixed_timestep_accumulator.as_secs_f32() / fixed_timestep.step.as_secs_f32()
+                                    );
+
+                                    update_count
+                                },
+                                None => 1
+                            };
+                            previous_frame_instant = now_instant;
+
+                            // With a fixed timestep, System time follows the wall clock once per
+                            // frame, and Simulation advances by the step in each update (possibly
+                            // zero times this frame)
+                            if fixed_timestep.is_some() {
+                                time_state.update(TimeContext::System);
+                            }
+
+                            for _ in 0..update_count {
+                                let elapsed = match fixed_timestep {
+                                    Some(fixed_timestep) => {
+                                        time_state.update_context_with_elapsed(TimeContext::Simulation, fixed_timestep.step);
+                                        fixed_timestep.step
+                                    },
+                                    None => {
+                                        time_state.update(TimeContext::Simulation);
+                                        time_state.system().previous_frame_time
+                                    }
+                                };
+
+                                if let Some(input_recorder) = &mut input_recorder {
+                                    input_recorder.end_frame(elapsed);
+                                }
+
+                                if print_fps_event.try_take_event(
+                                    time_state.system().frame_start_instant,
+                                    std::time::Duration::from_secs_f32(1.0)
+                                ) {
+                                    debug!("fps: {}", time_state.system().fps);
+                                }
+
+                                let update_start_instant = std::time::Instant::now();
+                                {
+                                    let _scope_timer = ScopeTimer::new("AppHandler::update");
+                                    app_handler.update(
+                                        &mut app_control,
+                                        &input_state,
+                                        &time_state
+                                    );
+                                }
+                                update_time += update_start_instant.elapsed();
+
+                                if let Some(perf_overlay) = &mut perf_overlay {
+                                    perf_overlay.handle_input(&input_state);
+                                }
+
+                                for (time_context, request) in app_control.take_time_context_requests() {
+                                    time_state.apply_time_context_request(time_context, request);
+                                }
+
+                                // Call this to mark the start of the next frame (i.e. "key just down" will return false).
+                                // If there were no updates this frame, input is kept for the next one.
+                                input_state.end_frame();
+
+                                for app_window in &mut secondary_windows {
+                                    app_handler.update_window(
+                                        &mut app_control,
+                                        app_window.id,
+                                        &app_window.input_state,
+                                        &time_state
+                                    );
+                                    app_window.input_state.end_frame();
+                                }
+                            }
+
+                            if let Some(present_mode_priority) = app_control.take_pending_present_mode_priority() {
+                                for app_window in &mut secondary_windows {
+                                    if let Err(e) = app_window.renderer.set_present_mode_priority(&app_window.window, present_mode_priority.clone()) {
+                                        warn!("Error changing present mode of window {:?}: {}", app_window.id, e);
+                                        app_control.close_window(app_window.id);
+                                    }
+                                }
+
+                                if let Err(e) = renderer.set_present_mode_priority(&window, present_mode_priority) {
+                                    warn!("Error changing present mode: {}", e);
+                                    app_control.enqueue_terminate_process();
+                                }
+                            }
+
+                            // Close and open windows requested during update. Renderers for new
+                            // windows share the main renderer's device
+                            let window_closes = app_control.take_pending_window_closes();
+                            secondary_windows.retain(|x| !window_closes.contains(&x.id));
+
+                            for (window_id, options) in app_control.take_pending_window_opens() {
+                                let new_window = match options.build_window(window_target) {
+                                    Ok(new_window) => new_window,
+                                    Err(e) => {
+                                        warn!("Error creating window {:?}: {}", window_id, e);
+                                        continue;
+                                    }
+                                };
+
+                                match renderer.create_renderer_for_window(&new_window, options.composite_alpha()) {
+                                    Ok(window_renderer) => secondary_windows.push(AppWindow {
+                                        id: window_id,
+                                        renderer: window_renderer,
+                                        input_state: InputState::new(&new_window),
+                                        window: new_window,
+                                        is_minimized: false,
+                                        is_paused: false
+                                    }),
+                                    Err(e) => warn!("Error creating renderer for window {:?}: {}", window_id, e)
+                                }
+                            }
+
+                            app_control.set_open_windows(secondary_windows.iter().map(|x| x.id).collect());
+
+                            // Applied after opening windows so that windows opened this update can be
+                            // changed too
+                            for (window_id, request) in app_control.take_window_requests() {
+                                if window_id == AppWindowId::MAIN {
+                                    apply_window_request(&window, request);
+                                } else if let Some(app_window) = secondary_windows.iter().find(|x| x.id == window_id) {
+                                    apply_window_request(&app_window.window, request);
+                                }
+                            }
+
+                            // Queue a RedrawRequested event for every window that isn't paused.
+                            if !main_window_paused {
+                                window.request_redraw();
+                            }
+
+                            for app_window in secondary_windows.iter().filter(|x| !x.is_paused) {
+                                app_window.window.request_redraw();
+                            }
+
+                            if let Some(frame_interval) = frame_interval {
+                                // If we fell behind, don't try to catch up with a burst of frames
+                                next_frame_instant = std::cmp::max(next_frame_instant + frame_interval, now_instant);
+                            }
+                        }
+
+                        // A redraw requested during update means the app wants another frame
+                        if app_control.take_redraw_request() {
+                            needs_update = true;
+                        }
+
+                        let wants_another_update = redraw_mode == RedrawMode::Continuous || needs_update;
+                        *control_flow = if all_windows_paused {
+                            // Block until an event (i.e. a window being restored) arrives
+                            winit::event_loop::ControlFlow::Wait
+                        } else {
+                            match (wants_another_update, frame_interval) {
+                                (true, Some(_)) => winit::event_loop::ControlFlow::WaitUntil(next_frame_instant),
+                                (true, None) => winit::event_loop::ControlFlow::Poll,
+                                // Gamepad backends wake the event loop when input arrives
+                                (false, _) => winit::event_loop::ControlFlow::Wait
+                            }
+                        };
+
+                        if let winit::event_loop::ControlFlow::Wait = *control_flow {
+                            previous_draw_instant = None;
+                        }
+                    },
+                    winit::event::Event::WindowEvent {
+                        event: winit::event::WindowEvent::RedrawRequested,
+                        ..
+                    } if main_window_paused => {
+                        // Nothing to draw to while minimized
+                    },
+                    winit::event::Event::WindowEvent {
+                        event: winit::event::WindowEvent::RedrawRequested,
+                        ..
+                    } => {
+                        let render_start_instant = std::time::Instant::now();
+                        if let Some(previous_draw_instant) = previous_draw_instant {
+                            time_state.record_frame_time(render_start_instant - previous_draw_instant);
+                        }
+                        previous_draw_instant = Some(render_start_instant);
+
+                        let mut draw_time = std::time::Duration::from_secs(0);
+
+                        let render_scope_timer = ScopeTimer::new("Renderer::draw");
+                        if let Err(e) = renderer.draw(&window, |canvas| {
+                            let draw_start_instant = std::time::Instant::now();
+                            {
+                                let _scope_timer = ScopeTimer::new("AppHandler::draw");
+                                app_handler.draw(
+                                    &app_control,
+                                    AppWindowId::MAIN,
+                                    &input_state,
+                                    &time_state,
+                                    canvas
+                                );
+                            }
+                            draw_time = draw_start_instant.elapsed();
+
+                            if let Some(perf_overlay) = &perf_overlay {
+                                perf_overlay.draw(canvas, input_state.dpi_factor(), &time_state);
+                            }
+                        }) {
+                            warn!("Error during draw: {}", e);
+                            app_control.enqueue_terminate_process();
+                        }
+                        std::mem::drop(render_scope_timer);
+
+                        if let Some(perf_overlay) = &mut perf_overlay {
+                            let render_time = render_start_instant.elapsed().checked_sub(draw_time).unwrap_or_default();
+                            perf_overlay.record_frame(update_time, draw_time, render_time, renderer.gpu_timings());
+                        }
+                        update_time = std::time::Duration::from_secs(0);
+
+                        Profiler::end_frame();
+                    },
+                    winit::event::Event::WindowEvent { .. } => {
+                        // Any input or window change (i.e. resize) should be reflected on screen
+                        needs_update = true;
+                    },
+                    winit::event::Event::LoopDestroyed => {
+                        app_handler.shutdown(&mut app_control);
+
+                        // Destroy secondary windows and their renderers before the main window
+                        secondary_windows.clear();
+                    },
+                    _ => {}
+                }
             }
 
             if app_control.should_terminate_process() {
@@ -161,3 +920,81 @@ impl App {
         });
     }
 }
+
+// False while the window's surface has zero size. An error is handled like an error while drawing:
+// the window is closed, which terminates the app if it is the main window
+fn poll_surface_drawable(
+    renderer: &mut Renderer,
+    window: &winit::window::Window,
+    app_control: &mut AppControl,
+    window_id: AppWindowId
+) -> bool {
+    match renderer.poll_surface_drawable(window) {
+        Ok(drawable) => drawable,
+        Err(e) => {
+            warn!("Error checking the surface of window {:?}: {}", window_id, e);
+            app_control.close_window(window_id);
+            false
+        }
+    }
+}
+
+// Calls the AppHandler lifecycle methods for a window event. Returns false for a close request,
+// which is handled here rather than by the window's InputState (which would terminate the app)
+fn dispatch_window_lifecycle_event<T : AppHandler>(
+    app_handler: &mut T,
+    app_control: &mut AppControl,
+    window_id: AppWindowId,
+    event: &winit::event::WindowEvent,
+    is_minimized: &mut bool
+) -> bool {
+    use winit::event::WindowEvent;
+
+    match event {
+        WindowEvent::CloseRequested => {
+            trace!("close requested for window {:?}", window_id);
+            if app_handler.close_requested(app_control, window_id) {
+                app_control.close_window(window_id);
+            }
+
+            return false;
+        },
+        WindowEvent::Focused(focused) => {
+            app_handler.focus_changed(app_control, window_id, *focused);
+        },
+        WindowEvent::Resized(logical_size) => {
+            let minimized = logical_size.width <= 0.0 || logical_size.height <= 0.0;
+            if minimized != *is_minimized {
+                *is_minimized = minimized;
+                app_handler.minimized_changed(app_control, window_id, minimized);
+            }
+
+            if !minimized {
+                app_handler.resized(app_control, window_id, *logical_size);
+            }
+        },
+        WindowEvent::HiDpiFactorChanged(dpi_factor) => {
+            app_handler.dpi_factor_changed(app_control, window_id, *dpi_factor);
+        },
+        _ => {}
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_timestep_rejects_invalid_values() {
+        let default_step = FixedTimestep::new(60.0).step;
+        assert_eq!(FixedTimestep::new(0.0).step, default_step);
+        assert_eq!(FixedTimestep::new(-30.0).step, default_step);
+        assert_eq!(FixedTimestep::new(std::f32::NAN).step, default_step);
+        assert_eq!(FixedTimestep::new(120.0).step, std::time::Duration::from_secs_f32(1.0 / 120.0));
+
+        let fixed_timestep = FixedTimestep::new(60.0).max_updates_per_frame(3).max_updates_per_frame(0);
+        assert_eq!(fixed_timestep.max_updates_per_frame, 3);
+    }
+}
@@ -1,9 +1,40 @@
 
+use crate::PresentMode;
+use super::time_state::TimeContext;
+use super::time_state::TimeContextRequest;
+use super::app_window::AppWindowId;
+use super::app_window::AppWindowOptions;
+use super::app_window::FullscreenMode;
+use super::app_window::CursorIcon;
+use super::app_window::WindowRequest;
+
 /// State that drives high-level decision making for the app
 #[derive(Default)]
 pub struct AppControl {
     /// If true, the application will quit when the next frame ends
-    should_terminate_process: bool
+    should_terminate_process: bool,
+
+    /// If true, the app should update and draw another frame even if no input arrives
+    redraw_requested: bool,
+
+    /// Changes to time contexts that will be applied after the current update
+    time_context_requests: Vec<(TimeContext, TimeContextRequest)>,
+
+    /// If set, the renderer's present mode priority will be changed before the next frame is drawn
+    pending_present_mode_priority: Option<Vec<PresentMode>>,
+
+    /// Used to assign ids to windows opened with `open_window`. 0 is the main window
+    next_window_id: u64,
+
+    /// Windows that will be opened or closed after the current update
+    pending_window_opens: Vec<(AppWindowId, AppWindowOptions)>,
+    pending_window_closes: Vec<AppWindowId>,
+
+    /// Secondary windows that were open as of the start of the current update
+    open_windows: Vec<AppWindowId>,
+
+    /// Changes to windows (title, fullscreen, cursor) that will be applied after the current update
+    window_requests: Vec<(AppWindowId, WindowRequest)>
 }
 
 impl AppControl {
@@ -16,4 +47,119 @@ impl AppControl {
     pub fn should_terminate_process(&self) -> bool {
         self.should_terminate_process
     }
+
+    /// Request another update and draw. Only needed when using `RedrawMode::Reactive`, for example
+    /// to keep an animation running until it finishes.
+    pub fn request_redraw(&mut self) {
+        self.redraw_requested = true;
+    }
+
+    pub(super) fn take_redraw_request(&mut self) -> bool {
+        std::mem::replace(&mut self.redraw_requested, false)
+    }
+
+    /// Stop the given time context (i.e. `TimeContext::Simulation`) from advancing, starting with
+    /// the next update
+    pub fn pause_time_context(&mut self, time_context: TimeContext) {
+        self.time_context_requests.push((time_context, TimeContextRequest::Pause));
+    }
+
+    pub fn resume_time_context(&mut self, time_context: TimeContext) {
+        self.time_context_requests.push((time_context, TimeContextRequest::Resume));
+    }
+
+    /// Multiply time passing in the given context by a scale (i.e. 0.5 for slow motion, 2.0 for
+    /// fast forward)
+    pub fn set_time_scale(&mut self, time_context: TimeContext, time_scale: f32) {
+        self.time_context_requests.push((time_context, TimeContextRequest::SetTimeScale(time_scale)));
+    }
+
+    /// Advance a paused time context by a single update
+    pub fn step_time_context(&mut self, time_context: TimeContext) {
+        self.time_context_requests.push((time_context, TimeContextRequest::Step));
+    }
+
+    pub(super) fn take_time_context_requests(&mut self) -> Vec<(TimeContext, TimeContextRequest)> {
+        std::mem::replace(&mut self.time_context_requests, vec![])
+    }
+
+    /// Change the renderer's preferred present modes (i.e. to toggle vsync). This recreates the
+    /// swapchain before the next frame is drawn.
+    pub fn set_present_mode_priority(&mut self, present_mode_priority: Vec<PresentMode>) {
+        self.pending_present_mode_priority = Some(present_mode_priority);
+    }
+
+    pub(super) fn take_pending_present_mode_priority(&mut self) -> Option<Vec<PresentMode>> {
+        self.pending_present_mode_priority.take()
+    }
+
+    /// Open another window that shares the main window's renderer device. The window is created
+    /// after the current update, and is drawn with `AppHandler::draw` using the returned id
+    pub fn open_window(&mut self, options: AppWindowOptions) -> AppWindowId {
+        self.next_window_id += 1;
+        let window_id = AppWindowId(self.next_window_id);
+        self.pending_window_opens.push((window_id, options));
+        window_id
+    }
+
+    /// Close a window opened with `open_window`. Closing `AppWindowId::MAIN` terminates the app
+    pub fn close_window(&mut self, window_id: AppWindowId) {
+        if window_id == AppWindowId::MAIN {
+            self.enqueue_terminate_process();
+        } else {
+            self.pending_window_closes.push(window_id);
+        }
+    }
+
+    /// Ids of the windows opened with `open_window` that are currently open. Does not include the
+    /// main window
+    pub fn window_ids(&self) -> &[AppWindowId] {
+        &self.open_windows
+    }
+
+    pub fn is_window_open(&self, window_id: AppWindowId) -> bool {
+        window_id == AppWindowId::MAIN || self.open_windows.contains(&window_id)
+    }
+
+    pub fn set_window_title(&mut self, window_id: AppWindowId, title: &str) {
+        self.window_requests.push((window_id, WindowRequest::SetTitle(title.to_string())));
+    }
+
+    /// Make the window fullscreen on the monitor it is on, or None to return to a normal window
+    pub fn set_fullscreen(&mut self, window_id: AppWindowId, fullscreen: Option<FullscreenMode>) {
+        self.window_requests.push((window_id, WindowRequest::SetFullscreen(fullscreen)));
+    }
+
+    /// Change the cursor's appearance while it is over the window
+    pub fn set_cursor_icon(&mut self, window_id: AppWindowId, cursor_icon: CursorIcon) {
+        self.window_requests.push((window_id, WindowRequest::SetCursorIcon(cursor_icon)));
+    }
+
+    /// Hide or show the cursor while it is over the window
+    pub fn set_cursor_visible(&mut self, window_id: AppWindowId, visible: bool) {
+        self.window_requests.push((window_id, WindowRequest::SetCursorVisible(visible)));
+    }
+
+    /// Keep the cursor inside the window (i.e. for mouse look). Combine with
+    /// `set_cursor_visible(false)` to hide it as well. Not supported on every platform, errors are
+    /// logged
+    pub fn set_cursor_grab(&mut self, window_id: AppWindowId, grab: bool) {
+        self.window_requests.push((window_id, WindowRequest::SetCursorGrab(grab)));
+    }
+
+    pub(super) fn take_window_requests(&mut self) -> Vec<(AppWindowId, WindowRequest)> {
+        std::mem::replace(&mut self.window_requests, vec![])
+    }
+
+    pub(super) fn take_pending_window_opens(&mut self) -> Vec<(AppWindowId, AppWindowOptions)> {
+        std::mem::replace(&mut self.pending_window_opens, vec![])
+    }
+
+    pub(super) fn take_pending_window_closes(&mut self) -> Vec<AppWindowId> {
+        std::mem::replace(&mut self.pending_window_closes, vec![])
+    }
+
+    pub(super) fn set_open_windows(&mut self, open_windows: Vec<AppWindowId>) {
+        self.open_windows = open_windows;
+    }
 }
\ No newline at end of file
@@ -0,0 +1,640 @@
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::app::AppHandler;
+use super::app_control::AppControl;
+use super::app_window::AppWindowId;
+use super::input_state::InputState;
+use super::input_state::LogicalSize;
+use super::input_recording::ScriptedInputEvent;
+use super::input_recording::InputRecording;
+use super::time_state::TimeState;
+use super::time_state::TimeContext;
+
+use crate::CapturedFrame;
+use crate::HeadlessRenderer;
+use crate::RendererBuilder;
+
+/// What the test harness draws with
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TestHarnessBackend {
+    /// Skia's CPU rasterizer, requires no GPU or vulkan driver
+    Raster,
+
+    /// A headless vulkan renderer. May be used with a software vulkan driver
+    Vulkan
+}
+
+/// Result of comparing a captured frame against a reference image
+pub struct FrameComparison {
+    /// Number of pixels where any channel differs by more than the tolerance
+    pub mismatched_pixel_count: usize,
+
+    /// Largest difference of any channel of any pixel
+    pub max_channel_difference: u8,
+
+    /// Mismatched pixels are red, everything else is a faded grayscale copy of the actual image
+    pub diff: CapturedFrame
+}
+
+impl FrameComparison {
+    /// Compare two frames. Returns None if the frames are not the same size.
+    pub fn compare(expected: &CapturedFrame, actual: &CapturedFrame, tolerance: u8) -> Option<Self> {
+        if expected.width != actual.width || expected.height != actual.height {
+            return None;
+        }
+
+        let mut mismatched_pixel_count = 0;
+        let mut max_channel_difference = 0;
+        let mut diff_pixels = Vec::with_capacity(actual.pixels.len());
+
+        let expected_pixels = expected.pixels.chunks(CapturedFrame::BYTES_PER_PIXEL);
+        let actual_pixels = actual.pixels.chunks(CapturedFrame::BYTES_PER_PIXEL);
+        for (expected_pixel, actual_pixel) in expected_pixels.zip(actual_pixels) {
+            let difference = expected_pixel.iter()
+                .zip(actual_pixel.iter())
+                .map(|(e, a)| (*e as i16 - *a as i16).abs() as u8)
+                .max()
+                .unwrap_or(0);
+
+            max_channel_difference = max_channel_difference.max(difference);
+
+            if difference > tolerance {
+                mismatched_pixel_count += 1;
+                diff_pixels.extend_from_slice(&[255, 0, 0, 255]);
+            } else {
+                let luminance = (actual_pixel[0] as u32 + actual_pixel[1] as u32 + actual_pixel[2] as u32) / 3;
+                let faded = (luminance / 4) as u8;
+                diff_pixels.extend_from_slice(&[faded, faded, faded, 255]);
+            }
+        }
+
+        Some(FrameComparison {
+            mismatched_pixel_count,
+            max_channel_difference,
+            diff: CapturedFrame::new(actual.width, actual.height, diff_pixels)
+        })
+    }
+}
+
+/// A frame that did not match its reference image
+#[derive(Debug)]
+pub struct FrameMismatch {
+    pub frame_index: u64,
+    pub mismatched_pixel_count: usize,
+    pub max_channel_difference: u8,
+    pub reference_path: PathBuf,
+    pub actual_path: PathBuf,
+    pub diff_path: Option<PathBuf>
+}
+
+#[derive(Debug)]
+pub enum TestHarnessError {
+    /// The renderer failed to initialize or draw
+    Renderer(crate::Error),
+
+    /// Skia could not create a raster surface or read pixels back from it
+    Skia(String),
+
+    /// Failed to read or write an image
+    Io(std::io::Error),
+
+    /// There is no reference image for a captured frame. Set `update_references` to create it.
+    MissingReference(PathBuf),
+
+    /// One or more frames did not match their reference images
+    Mismatch(Vec<FrameMismatch>)
+}
+
+impl std::fmt::Display for TestHarnessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TestHarnessError::Renderer(e) => write!(f, "Renderer error: {}", e),
+            TestHarnessError::Skia(e) => write!(f, "Skia error: {}", e),
+            TestHarnessError::Io(e) => write!(f, "IO error: {}", e),
+            TestHarnessError::MissingReference(path) => write!(f, "Missing reference image {:?}", path),
+            TestHarnessError::Mismatch(mismatches) => {
+                writeln!(f, "{} frame(s) did not match their reference image", mismatches.len())?;
+                for mismatch in mismatches {
+                    writeln!(
+                        f,
+                        "  frame {}: {} pixels differ (max difference {}), actual: {:?} diff: {:?}",
+                        mismatch.frame_index,
+                        mismatch.mismatched_pixel_count,
+                        mismatch.max_channel_difference,
+                        mismatch.actual_path,
+                        mismatch.diff_path
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for TestHarnessError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TestHarnessError::Renderer(e) => Some(e),
+            TestHarnessError::Io(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl From<std::io::Error> for TestHarnessError {
+    fn from(error: std::io::Error) -> Self {
+        TestHarnessError::Io(error)
+    }
+}
+
+impl From<crate::Error> for TestHarnessError {
+    fn from(error: crate::Error) -> Self {
+        TestHarnessError::Renderer(error)
+    }
+}
+
+enum TestRenderTarget {
+    Raster(skia_safe::Surface),
+    Vulkan(HeadlessRenderer)
+}
+
+impl TestRenderTarget {
+    fn new(backend: TestHarnessBackend, width: u32, height: u32) -> Result<Self, TestHarnessError> {
+        match backend {
+            TestHarnessBackend::Raster => {
+                let surface = skia_safe::Surface::new_raster_n32_premul((width as i32, height as i32))
+                    .ok_or_else(|| TestHarnessError::Skia("Could not create raster surface".to_string()))?;
+                Ok(TestRenderTarget::Raster(surface))
+            },
+            TestHarnessBackend::Vulkan => {
+                let renderer = RendererBuilder::new().build_headless(width, height)?;
+                Ok(TestRenderTarget::Vulkan(renderer))
+            }
+        }
+    }
+
+    fn draw<F : FnOnce(&mut skia_safe::Canvas)>(
+        &mut self,
+        dpi_factor: f64,
+        f: F
+    ) -> Result<CapturedFrame, TestHarnessError> {
+        match self {
+            TestRenderTarget::Raster(surface) => {
+                {
+                    let mut canvas = surface.canvas();
+                    canvas.reset_matrix();
+                    canvas.scale((dpi_factor as f32, dpi_factor as f32));
+                    f(&mut canvas);
+                    canvas.flush();
+                }
+
+                CapturedFrame::read_from_surface(surface)
+                    .ok_or_else(|| TestHarnessError::Skia("Could not read raster surface".to_string()))
+            },
+            TestRenderTarget::Vulkan(renderer) => {
+                Ok(renderer.draw(|canvas| {
+                    canvas.scale((dpi_factor as f32, dpi_factor as f32));
+                    f(canvas);
+                })?)
+            }
+        }
+    }
+}
+
+/// Drives an `AppHandler` for a number of frames with scripted input and a fixed time step,
+/// comparing each captured frame against reference PNGs. Reference images are named
+/// `<test_name>_<frame_index>.png`. When a frame doesn't match, the actual image and a diff image
+/// are written to the output directory.
+///
+/// Set `update_references` (or the `SKULPIN_UPDATE_REFERENCE_IMAGES` environment variable) to
+/// write the captured frames as the new reference images instead of comparing.
+pub struct AppTestHarness {
+    test_name: String,
+    frame_count: u64,
+    time_step: Duration,
+    logical_size: LogicalSize,
+    dpi_factor: f64,
+    backend: TestHarnessBackend,
+    tolerance: u8,
+    max_mismatched_pixels: usize,
+    reference_dir: PathBuf,
+    output_dir: PathBuf,
+    update_references: bool,
+    captured_frames: Option<Vec<u64>>,
+    scripted_input: Vec<(u64, ScriptedInputEvent)>,
+
+    // Per-frame time steps from an input recording. Overrides time_step for the frames it covers
+    recorded_time_steps: Vec<Duration>
+}
+
+impl AppTestHarness {
+    pub fn new(test_name: &str) -> Self {
+        AppTestHarness {
+            test_name: test_name.to_string(),
+            frame_count: 1,
+            time_step: Duration::from_secs_f64(1.0 / 60.0),
+            logical_size: LogicalSize::new(900.0, 600.0),
+            dpi_factor: 1.0,
+            backend: TestHarnessBackend::Raster,
+            tolerance: 0,
+            max_mismatched_pixels: 0,
+            reference_dir: PathBuf::from("tests/reference_images"),
+            output_dir: PathBuf::from("target/test_output"),
+            update_references: std::env::var_os("SKULPIN_UPDATE_REFERENCE_IMAGES").is_some(),
+            captured_frames: None,
+            scripted_input: Vec::new(),
+            recorded_time_steps: Vec::new()
+        }
+    }
+
+    /// Number of frames to run the app for
+    pub fn frame_count(mut self, frame_count: u64) -> Self {
+        self.frame_count = frame_count;
+        self
+    }
+
+    /// Amount of time that passes each frame
+    pub fn time_step(mut self, time_step: Duration) -> Self {
+        self.time_step = time_step;
+        self
+    }
+
+    pub fn logical_size(mut self, logical_size: LogicalSize) -> Self {
+        self.logical_size = logical_size;
+        self
+    }
+
+    pub fn dpi_factor(mut self, dpi_factor: f64) -> Self {
+        self.dpi_factor = dpi_factor;
+        self
+    }
+
+    pub fn backend(mut self, backend: TestHarnessBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Maximum difference allowed per color channel before a pixel is considered mismatched
+    pub fn tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Number of mismatched pixels allowed before a frame fails
+    pub fn max_mismatched_pixels(mut self, max_mismatched_pixels: usize) -> Self {
+        self.max_mismatched_pixels = max_mismatched_pixels;
+        self
+    }
+
+    pub fn reference_dir<P: AsRef<Path>>(mut self, reference_dir: P) -> Self {
+        self.reference_dir = reference_dir.as_ref().to_path_buf();
+        self
+    }
+
+    pub fn output_dir<P: AsRef<Path>>(mut self, output_dir: P) -> Self {
+        self.output_dir = output_dir.as_ref().to_path_buf();
+        self
+    }
+
+    pub fn update_references(mut self, update_references: bool) -> Self {
+        self.update_references = update_references;
+        self
+    }
+
+    /// Only compare the given frames. By default, every frame is compared
+    pub fn captured_frames(mut self, captured_frames: Vec<u64>) -> Self {
+        self.captured_frames = Some(captured_frames);
+        self
+    }
+
+    /// Inject an input event at the start of the given frame
+    pub fn input(mut self, frame_index: u64, event: ScriptedInputEvent) -> Self {
+        self.scripted_input.push((frame_index, event));
+        self
+    }
+
+    /// Replay a recording made with `InputRecorder` (or `AppBuilder::record_input`). Sets the
+    /// window size, dpi factor and frame count from the recording, injects its events, and uses
+    /// its recorded frame times instead of the fixed time step.
+    pub fn input_recording(mut self, recording: &InputRecording) -> Self {
+        self.logical_size = recording.logical_size;
+        self.dpi_factor = recording.dpi_factor;
+        self.frame_count = recording.frames.len() as u64;
+        self.recorded_time_steps = recording.frames.iter().map(|x| x.elapsed).collect();
+
+        for (frame_index, frame) in recording.frames.iter().enumerate() {
+            for event in &frame.events {
+                self.scripted_input.push((frame_index as u64, event.clone()));
+            }
+        }
+
+        self
+    }
+
+    pub fn reference_path(&self, frame_index: u64) -> PathBuf {
+        self.reference_dir.join(format!("{}_{:04}.png", self.test_name, frame_index))
+    }
+
+    fn output_path(&self, frame_index: u64, suffix: &str) -> PathBuf {
+        self.output_dir.join(format!("{}_{:04}_{}.png", self.test_name, frame_index, suffix))
+    }
+
+    fn should_capture(&self, frame_index: u64) -> bool {
+        match &self.captured_frames {
+            Some(captured_frames) => captured_frames.contains(&frame_index),
+            None => true
+        }
+    }
+
+    /// Run the app. Returns an error if any captured frame doesn't match its reference image.
+    /// The app may end the run early by calling `AppControl::enqueue_terminate_process`
+    pub fn run<T : AppHandler>(&self, app_handler: &mut T) -> Result<(), TestHarnessError> {
+        let physical_size = self.logical_size.to_physical(self.dpi_factor);
+        let mut render_target = TestRenderTarget::new(
+            self.backend,
+            physical_size.width.round() as u32,
+            physical_size.height.round() as u32
+        )?;
+
+        let mut app_control = AppControl::default();
+        let mut time_state = TimeState::default();
+        let mut input_state = InputState::new_with_window_size(self.logical_size, self.dpi_factor);
+
+        let mut mismatches = vec![];
+
+        for frame_index in 0..self.frame_count {
+            for (_, event) in self.scripted_input.iter().filter(|(index, _)| *index == frame_index) {
+                event.apply(&mut input_state);
+            }
+
+            // This mirrors the order of operations in App::run
+            let time_step = self.recorded_time_steps.get(frame_index as usize).cloned().unwrap_or(self.time_step);
+            time_state.update_with_elapsed(TimeContext::Simulation, time_step);
+            input_state.advance_time(time_step);
+
+            app_handler.update(
+                &mut app_control,
+                &input_state,
+                &time_state
+            );
+
+            for (time_context, request) in app_control.take_time_context_requests() {
+                time_state.apply_time_context_request(time_context, request);
+            }
+
+            input_state.end_frame();
+
+            let frame = render_target.draw(self.dpi_factor, |canvas| {
+                app_handler.draw(
+                    &app_control,
+                    AppWindowId::MAIN,
+                    &input_state,
+                    &time_state,
+                    canvas
+                );
+            })?;
+
+            if self.should_capture(frame_index) {
+                if let Some(mismatch) = self.check_frame(frame_index, &frame)? {
+                    mismatches.push(mismatch);
+                }
+            }
+
+            if app_control.should_terminate_process() {
+                break;
+            }
+        }
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(TestHarnessError::Mismatch(mismatches))
+        }
+    }
+
+    fn check_frame(&self, frame_index: u64, frame: &CapturedFrame) -> Result<Option<FrameMismatch>, TestHarnessError> {
+        let reference_path = self.reference_path(frame_index);
+
+        if self.update_references {
+            info!("Writing reference image {:?}", reference_path);
+            std::fs::create_dir_all(&self.reference_dir)?;
+            frame.write_png(&reference_path)?;
+            return Ok(None);
+        }
+
+        if !reference_path.exists() {
+            return Err(TestHarnessError::MissingReference(reference_path));
+        }
+
+        let reference = CapturedFrame::read_png(&reference_path)?;
+        let comparison = FrameComparison::compare(&reference, frame, self.tolerance);
+
+        let (mismatched_pixel_count, max_channel_difference, diff) = match comparison {
+            Some(comparison) => {
+                if comparison.mismatched_pixel_count <= self.max_mismatched_pixels {
+                    return Ok(None);
+                }
+
+                (comparison.mismatched_pixel_count, comparison.max_channel_difference, Some(comparison.diff))
+            },
+            None => {
+                // Size mismatch, every pixel is considered different and no diff can be produced
+                warn!(
+                    "Reference image {:?} is {}x{} but frame is {}x{}",
+                    reference_path,
+                    reference.width,
+                    reference.height,
+                    frame.width,
+                    frame.height
+                );
+                ((frame.width * frame.height) as usize, 255, None)
+            }
+        };
+
+        std::fs::create_dir_all(&self.output_dir)?;
+
+        let actual_path = self.output_path(frame_index, "actual");
+        frame.write_png(&actual_path)?;
+
+        let diff_path = match diff {
+            Some(diff) => {
+                let diff_path = self.output_path(frame_index, "diff");
+                diff.write_png(&diff_path)?;
+                Some(diff_path)
+            },
+            None => None
+        };
+
+        Ok(Some(FrameMismatch {
+            frame_index,
+            mismatched_pixel_count,
+            max_channel_difference,
+            reference_path,
+            actual_path,
+            diff_path
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::input_state::ElementState;
+    use crate::app::input_state::MouseButton;
+
+    fn solid_frame(width: u32, height: u32, rgba: [u8; 4]) -> CapturedFrame {
+        let pixels = rgba.iter().cloned().cycle().take(width as usize * height as usize * 4).collect();
+        CapturedFrame::new(width, height, pixels)
+    }
+
+    // An empty directory under the system temp dir, unique to this test and process
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("skulpin_app_test_harness_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    // Clears to blue, or to red once the left mouse button has been pressed
+    #[derive(Default)]
+    struct ClickToRedApp {
+        clicked: bool,
+        update_count: u64
+    }
+
+    impl AppHandler for ClickToRedApp {
+        fn update(&mut self, _app_control: &mut AppControl, input_state: &InputState, _time_state: &TimeState) {
+            self.update_count += 1;
+            if input_state.is_mouse_just_down(MouseButton::Left) {
+                self.clicked = true;
+            }
+        }
+
+        fn draw(
+            &mut self,
+            _app_control: &AppControl,
+            _window_id: AppWindowId,
+            _input_state: &InputState,
+            _time_state: &TimeState,
+            canvas: &mut skia_safe::Canvas
+        ) {
+            if self.clicked {
+                canvas.clear(skia_safe::Color::from_rgb(255, 0, 0));
+            } else {
+                canvas.clear(skia_safe::Color::from_rgb(0, 0, 255));
+            }
+        }
+    }
+
+    #[test]
+    fn compare_identical_frames() {
+        let frame = solid_frame(4, 3, [10, 20, 30, 255]);
+        let comparison = FrameComparison::compare(&frame, &frame.clone(), 0).unwrap();
+        assert_eq!(comparison.mismatched_pixel_count, 0);
+        assert_eq!(comparison.max_channel_difference, 0);
+        assert_eq!((comparison.diff.width, comparison.diff.height), (4, 3));
+    }
+
+    #[test]
+    fn compare_respects_tolerance() {
+        let expected = solid_frame(2, 2, [100, 100, 100, 255]);
+        let mut actual = expected.clone();
+
+        // One pixel slightly off, one pixel far off
+        actual.pixels[0] = 103;
+        actual.pixels[CapturedFrame::BYTES_PER_PIXEL + 2] = 150;
+
+        let comparison = FrameComparison::compare(&expected, &actual, 3).unwrap();
+        assert_eq!(comparison.mismatched_pixel_count, 1);
+        assert_eq!(comparison.max_channel_difference, 50);
+        assert_eq!(comparison.diff.pixel(1, 0), [255, 0, 0, 255]);
+        assert_ne!(comparison.diff.pixel(0, 0), [255, 0, 0, 255]);
+
+        let comparison = FrameComparison::compare(&expected, &actual, 2).unwrap();
+        assert_eq!(comparison.mismatched_pixel_count, 2);
+    }
+
+    #[test]
+    fn compare_size_mismatch() {
+        let expected = solid_frame(4, 3, [0, 0, 0, 255]);
+        assert!(FrameComparison::compare(&expected, &solid_frame(3, 4, [0, 0, 0, 255]), 255).is_none());
+        assert!(FrameComparison::compare(&expected, &solid_frame(4, 4, [0, 0, 0, 255]), 255).is_none());
+    }
+
+    // Draws 16x8 pixel frames, optionally clicking at the start of frame 1
+    fn click_to_red_harness(dir: &Path, click: bool) -> AppTestHarness {
+        let harness = AppTestHarness::new("click_to_red")
+            .frame_count(3)
+            .logical_size(LogicalSize::new(16.0, 8.0))
+            .reference_dir(dir.join("reference"))
+            .output_dir(dir.join("output"))
+            .update_references(false);
+
+        if click {
+            harness.input(1, ScriptedInputEvent::MouseButton(MouseButton::Left, ElementState::Pressed))
+        } else {
+            harness
+        }
+    }
+
+    #[test]
+    fn raster_run_matches_references() {
+        let dir = test_dir("raster_run_matches_references");
+
+        // No references yet
+        match click_to_red_harness(&dir, true).run(&mut ClickToRedApp::default()) {
+            Err(TestHarnessError::MissingReference(_)) => {},
+            result => panic!("expected a missing reference, got {:?}", result)
+        }
+
+        let mut app = ClickToRedApp::default();
+        click_to_red_harness(&dir, true).update_references(true).run(&mut app).unwrap();
+        assert_eq!(app.update_count, 3);
+
+        let harness = click_to_red_harness(&dir, true);
+        let reference = CapturedFrame::read_png(harness.reference_path(0)).unwrap();
+        assert_eq!((reference.width, reference.height), (16, 8));
+        assert_eq!(reference.pixel(0, 0), [0, 0, 255, 255]);
+        assert_eq!(CapturedFrame::read_png(harness.reference_path(2)).unwrap().pixel(15, 7), [255, 0, 0, 255]);
+
+        // Same input matches
+        harness.run(&mut ClickToRedApp::default()).unwrap();
+
+        // Without the click, frames 1 and 2 stay blue and don't match
+        match click_to_red_harness(&dir, false).run(&mut ClickToRedApp::default()) {
+            Err(TestHarnessError::Mismatch(mismatches)) => {
+                let frame_indices : Vec<u64> = mismatches.iter().map(|x| x.frame_index).collect();
+                assert_eq!(frame_indices, vec![1, 2]);
+                assert_eq!(mismatches[0].mismatched_pixel_count, 16 * 8);
+                assert!(mismatches[0].actual_path.exists());
+                assert!(mismatches[0].diff_path.as_ref().unwrap().exists());
+            },
+            result => panic!("expected a mismatch, got {:?}", result)
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn only_captured_frames_are_compared() {
+        let dir = test_dir("only_captured_frames_are_compared");
+
+        click_to_red_harness(&dir, true)
+            .captured_frames(vec![0])
+            .update_references(true)
+            .run(&mut ClickToRedApp::default())
+            .unwrap();
+
+        assert!(click_to_red_harness(&dir, true).reference_path(0).exists());
+        assert!(!click_to_red_harness(&dir, true).reference_path(1).exists());
+
+        // Frame 0 is blue either way, and frames 1 and 2 have no reference but aren't captured
+        click_to_red_harness(&dir, false)
+            .captured_frames(vec![0])
+            .run(&mut ClickToRedApp::default())
+            .unwrap();
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
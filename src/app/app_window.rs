@@ -0,0 +1,266 @@
+use super::input_state::InputState;
+use super::input_state::LogicalSize;
+use super::input_state::LogicalPosition;
+use crate::Renderer;
+use crate::CompositeAlpha;
+
+pub use winit::window::Icon;
+pub use winit::window::CursorIcon;
+
+/// Identifies a window opened by `App`. The window created when the app starts is
+/// `AppWindowId::MAIN`, additional windows are opened with `AppControl::open_window`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AppWindowId(pub(super) u64);
+
+impl AppWindowId {
+    pub const MAIN : AppWindowId = AppWindowId(0);
+}
+
+/// How a fullscreen window covers the monitor it is on
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FullscreenMode {
+    /// A borderless window the size of the monitor. The monitor's video mode is not changed, so
+    /// switching in and out is fast
+    Borderless,
+
+    /// Take exclusive control of the monitor. The video mode keeps the monitor's current desktop
+    /// resolution, using the highest refresh rate and bit depth available at that resolution. If no
+    /// video mode has that resolution, the largest one is used
+    Exclusive
+}
+
+impl FullscreenMode {
+    fn to_winit(self, monitor: winit::monitor::MonitorHandle) -> winit::window::Fullscreen {
+        match self {
+            FullscreenMode::Borderless => winit::window::Fullscreen::Borderless(monitor),
+            FullscreenMode::Exclusive => {
+                // winit doesn't report the current video mode, so match the monitor's size
+                let desktop_size = monitor.size();
+                let desktop_size = (desktop_size.width as u64, desktop_size.height as u64);
+
+                let video_mode = monitor.video_modes().max_by_key(|video_mode| {
+                    let size = video_mode.size();
+                    let size = (size.width as u64, size.height as u64);
+                    (
+                        size == desktop_size,
+                        size.0 * size.1,
+                        video_mode.refresh_rate(),
+                        video_mode.bit_depth()
+                    )
+                });
+
+                match video_mode {
+                    Some(video_mode) => winit::window::Fullscreen::Exclusive(video_mode),
+                    None => {
+                        warn!("Monitor has no video modes for exclusive fullscreen, using borderless fullscreen");
+                        winit::window::Fullscreen::Borderless(monitor)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Settings for the main window (see `AppBuilder::window_options`) or a window opened with
+/// `AppControl::open_window`
+#[derive(Clone, Debug)]
+pub struct AppWindowOptions {
+    pub title: String,
+    pub logical_size: LogicalSize,
+    pub min_logical_size: Option<LogicalSize>,
+    pub max_logical_size: Option<LogicalSize>,
+    pub resizable: bool,
+    pub decorations: bool,
+    pub always_on_top: bool,
+    pub fullscreen: Option<FullscreenMode>,
+
+    /// Position of the window's top-left corner on the desktop. If None, the OS chooses
+    pub position: Option<LogicalPosition>,
+
+    pub icon: Option<Icon>,
+
+    /// Let the desktop show through where the app draws with alpha less than 1.0. Not supported
+    /// by every platform and compositor. Windows opened with `AppControl::open_window` use
+    /// `CompositeAlpha::PreMultiplied` if transparent (see `AppBuilder::composite_alpha` for the
+    /// main window)
+    pub transparent: bool
+}
+
+impl Default for AppWindowOptions {
+    fn default() -> Self {
+        AppWindowOptions {
+            title: "Skulpin".to_string(),
+            logical_size: LogicalSize::new(450.0, 300.0),
+            min_logical_size: None,
+            max_logical_size: None,
+            resizable: true,
+            decorations: true,
+            always_on_top: false,
+            fullscreen: None,
+            position: None,
+            icon: None,
+            transparent: false
+        }
+    }
+}
+
+impl AppWindowOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    pub fn logical_size(mut self, logical_size: LogicalSize) -> Self {
+        self.logical_size = logical_size;
+        self
+    }
+
+    pub fn min_logical_size(mut self, min_logical_size: Option<LogicalSize>) -> Self {
+        self.min_logical_size = min_logical_size;
+        self
+    }
+
+    pub fn max_logical_size(mut self, max_logical_size: Option<LogicalSize>) -> Self {
+        self.max_logical_size = max_logical_size;
+        self
+    }
+
+    pub fn resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    /// Show the title bar and border
+    pub fn decorations(mut self, decorations: bool) -> Self {
+        self.decorations = decorations;
+        self
+    }
+
+    pub fn always_on_top(mut self, always_on_top: bool) -> Self {
+        self.always_on_top = always_on_top;
+        self
+    }
+
+    /// Start fullscreen on the monitor the window would otherwise open on. None (the default)
+    /// opens a normal window
+    pub fn fullscreen(mut self, fullscreen: Option<FullscreenMode>) -> Self {
+        self.fullscreen = fullscreen;
+        self
+    }
+
+    pub fn position(mut self, position: Option<LogicalPosition>) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// The icon shown in the title bar and taskbar. Create one from RGBA pixels with
+    /// `Icon::from_rgba`. Not supported on every platform
+    pub fn icon(mut self, icon: Option<Icon>) -> Self {
+        self.icon = icon;
+        self
+    }
+
+    pub fn transparent(mut self, transparent: bool) -> Self {
+        self.transparent = transparent;
+        self
+    }
+
+    // The composite alpha for a window opened with AppControl::open_window
+    pub(super) fn composite_alpha(&self) -> CompositeAlpha {
+        if self.transparent {
+            CompositeAlpha::PreMultiplied
+        } else {
+            CompositeAlpha::Opaque
+        }
+    }
+
+    pub(super) fn build_window<T>(
+        &self,
+        window_target: &winit::event_loop::EventLoopWindowTarget<T>
+    ) -> Result<winit::window::Window, winit::error::OsError> {
+        // The position and fullscreen monitor can only be set once the window exists. Keep it hidden
+        // until then so that it doesn't visibly jump
+        let configure_after_build = self.position.is_some() || self.fullscreen.is_some();
+
+        let mut window_builder = winit::window::WindowBuilder::new()
+            .with_title(self.title.clone())
+            .with_inner_size(self.logical_size)
+            .with_resizable(self.resizable)
+            .with_decorations(self.decorations)
+            .with_always_on_top(self.always_on_top)
+            .with_window_icon(self.icon.clone())
+            .with_transparent(self.transparent)
+            .with_visible(!configure_after_build);
+
+        if let Some(min_logical_size) = self.min_logical_size {
+            window_builder = window_builder.with_min_inner_size(min_logical_size);
+        }
+
+        if let Some(max_logical_size) = self.max_logical_size {
+            window_builder = window_builder.with_max_inner_size(max_logical_size);
+        }
+
+        let window = window_builder.build(window_target)?;
+
+        if configure_after_build {
+            if let Some(position) = self.position {
+                window.set_outer_position(position);
+            }
+
+            if let Some(fullscreen) = self.fullscreen {
+                window.set_fullscreen(Some(fullscreen.to_winit(window.current_monitor())));
+            }
+
+            window.set_visible(true);
+        }
+
+        Ok(window)
+    }
+}
+
+/// A change to a window, queued with `AppControl` and applied after the update that queued it
+#[derive(Clone, Debug)]
+pub(super) enum WindowRequest {
+    SetTitle(String),
+    SetFullscreen(Option<FullscreenMode>),
+    SetCursorIcon(CursorIcon),
+    SetCursorVisible(bool),
+    SetCursorGrab(bool)
+}
+
+pub(super) fn apply_window_request(window: &winit::window::Window, request: WindowRequest) {
+    match request {
+        WindowRequest::SetTitle(title) => window.set_title(&title),
+        WindowRequest::SetFullscreen(fullscreen) => {
+            window.set_fullscreen(fullscreen.map(|fullscreen| fullscreen.to_winit(window.current_monitor())))
+        },
+        WindowRequest::SetCursorIcon(cursor_icon) => window.set_cursor_icon(cursor_icon),
+        WindowRequest::SetCursorVisible(visible) => window.set_cursor_visible(visible),
+        WindowRequest::SetCursorGrab(grab) => {
+            if let Err(e) = window.set_cursor_grab(grab) {
+                warn!("Error changing cursor grab: {}", e);
+            }
+        }
+    }
+}
+
+// A window other than the main window, with its own renderer and input
+pub(super) struct AppWindow {
+    pub id: AppWindowId,
+
+    // Declared before the window so that it is dropped first
+    pub renderer: Renderer,
+    pub input_state: InputState,
+    pub window: winit::window::Window,
+
+    // Set when the window is resized to zero size
+    pub is_minimized: bool,
+
+    // Set while the window is minimized or the renderer finds its surface has zero size. Nothing is
+    // drawn while paused
+    pub is_paused: bool
+}
@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+use std::time;
+
+/// Summary of the frame times in a `FrameTimeHistory`. All durations are zero if no frames have
+/// been recorded.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FrameTimeStats {
+    /// Number of frames the stats were computed from
+    pub frame_count: usize,
+
+    /// Shortest frame time
+    pub min: time::Duration,
+
+    /// Longest frame time
+    pub max: time::Duration,
+
+    /// Average frame time
+    pub mean: time::Duration,
+
+    /// Median frame time
+    pub p50: time::Duration,
+
+    /// 95% of frames took this long or less
+    pub p95: time::Duration,
+
+    /// 99% of frames took this long or less
+    pub p99: time::Duration,
+
+    /// Number of frames that took longer than the hitch threshold
+    pub hitch_count: usize
+}
+
+/// A rolling window of the most recent frame times
+pub struct FrameTimeHistory {
+    frame_times: VecDeque<time::Duration>,
+    max_frame_count: usize,
+    hitch_threshold: time::Duration
+}
+
+impl Default for FrameTimeHistory {
+    fn default() -> Self {
+        // Roughly 2 seconds at 120hz, and treat anything slower than 30fps as a hitch
+        FrameTimeHistory::new(240, time::Duration::from_secs_f32(1.0 / 30.0))
+    }
+}
+
+impl FrameTimeHistory {
+    pub fn new(max_frame_count: usize, hitch_threshold: time::Duration) -> Self {
+        FrameTimeHistory {
+            frame_times: VecDeque::with_capacity(max_frame_count),
+            max_frame_count,
+            hitch_threshold
+        }
+    }
+
+    /// Record a frame time, discarding the oldest one if the window is full
+    pub fn push(&mut self, frame_time: time::Duration) {
+        if self.max_frame_count == 0 {
+            return;
+        }
+
+        while self.frame_times.len() >= self.max_frame_count {
+            self.frame_times.pop_front();
+        }
+
+        self.frame_times.push_back(frame_time);
+    }
+
+    pub fn clear(&mut self) {
+        self.frame_times.clear();
+    }
+
+    /// Frame times in the window, oldest first
+    pub fn frame_times(&self) -> impl Iterator<Item = &time::Duration> {
+        self.frame_times.iter()
+    }
+
+    pub fn max_frame_count(&self) -> usize {
+        self.max_frame_count
+    }
+
+    /// Change how many frames are kept. Drops the oldest frames if there are too many.
+    pub fn set_max_frame_count(&mut self, max_frame_count: usize) {
+        self.max_frame_count = max_frame_count;
+        while self.frame_times.len() > max_frame_count {
+            self.frame_times.pop_front();
+        }
+    }
+
+    pub fn hitch_threshold(&self) -> time::Duration {
+        self.hitch_threshold
+    }
+
+    /// Frames that take longer than this are counted as hitches
+    pub fn set_hitch_threshold(&mut self, hitch_threshold: time::Duration) {
+        self.hitch_threshold = hitch_threshold;
+    }
+
+    /// Compute min/max/mean, percentiles and the hitch count for the frames in the window
+    pub fn stats(&self) -> FrameTimeStats {
+        if self.frame_times.is_empty() {
+            return FrameTimeStats::default();
+        }
+
+        let mut sorted : Vec<time::Duration> = self.frame_times.iter().cloned().collect();
+        sorted.sort();
+
+        let frame_count = sorted.len();
+        let total : time::Duration = sorted.iter().sum();
+
+        // Nearest-rank percentile: the smallest value with at least the given percent of frames at
+        // or below it. Integer math so that i.e. the 95th of 100 frames is exactly rank 95
+        let percentile = |percent: usize| {
+            let rank = (percent * frame_count + 99) / 100;
+            sorted[rank.max(1).min(frame_count) - 1]
+        };
+
+        FrameTimeStats {
+            frame_count,
+            min: sorted[0],
+            max: sorted[frame_count - 1],
+            mean: total / frame_count as u32,
+            p50: percentile(50),
+            p95: percentile(95),
+            p99: percentile(99),
+            hitch_count: sorted.iter().filter(|x| **x > self.hitch_threshold).count()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(milliseconds: u64) -> time::Duration {
+        time::Duration::from_millis(milliseconds)
+    }
+
+    #[test]
+    fn empty_history_has_zero_stats() {
+        let stats = FrameTimeHistory::default().stats();
+        assert_eq!(stats.frame_count, 0);
+        assert_eq!(stats.min, ms(0));
+        assert_eq!(stats.max, ms(0));
+        assert_eq!(stats.mean, ms(0));
+        assert_eq!(stats.p99, ms(0));
+        assert_eq!(stats.hitch_count, 0);
+    }
+
+    #[test]
+    fn single_frame_is_every_statistic() {
+        let mut history = FrameTimeHistory::new(10, ms(20));
+        history.push(ms(25));
+
+        let stats = history.stats();
+        assert_eq!(stats.frame_count, 1);
+        assert_eq!(stats.min, ms(25));
+        assert_eq!(stats.max, ms(25));
+        assert_eq!(stats.mean, ms(25));
+        assert_eq!(stats.p50, ms(25));
+        assert_eq!(stats.p95, ms(25));
+        assert_eq!(stats.p99, ms(25));
+        assert_eq!(stats.hitch_count, 1);
+    }
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        // 1ms to 100ms, pushed out of order
+        let mut history = FrameTimeHistory::new(100, ms(90));
+        for i in 0..100 {
+            history.push(ms((i * 37) % 100 + 1));
+        }
+
+        let stats = history.stats();
+        assert_eq!(stats.frame_count, 100);
+        assert_eq!(stats.min, ms(1));
+        assert_eq!(stats.max, ms(100));
+        assert_eq!(stats.mean, time::Duration::from_micros(50_500));
+        assert_eq!(stats.p50, ms(50));
+        assert_eq!(stats.p95, ms(95));
+        assert_eq!(stats.p99, ms(99));
+        assert_eq!(stats.hitch_count, 10);
+
+        // With few frames, a percentile rounds up to the next frame
+        let mut history = FrameTimeHistory::new(10, ms(90));
+        for i in 1..=10 {
+            history.push(ms(i));
+        }
+
+        let stats = history.stats();
+        assert_eq!(stats.p50, ms(5));
+        assert_eq!(stats.p95, ms(10));
+        assert_eq!(stats.p99, ms(10));
+    }
+
+    #[test]
+    fn oldest_frames_are_dropped() {
+        let mut history = FrameTimeHistory::new(3, ms(100));
+        for i in 1..=4 {
+            history.push(ms(i));
+        }
+
+        assert_eq!(history.frame_times().cloned().collect::<Vec<_>>(), vec![ms(2), ms(3), ms(4)]);
+
+        history.set_max_frame_count(1);
+        assert_eq!(history.stats().min, ms(4));
+    }
+}
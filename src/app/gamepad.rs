@@ -0,0 +1,770 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use super::input_state::ElementState;
+
+/// Identifies a connected gamepad. On linux this is the N in /dev/input/jsN
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GamepadId(pub usize);
+
+/// Buttons are named by position on an xbox-style controller (South is A on xbox, cross on
+/// playstation)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    South,
+    East,
+    West,
+    North,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    Mode,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+
+    /// A button without a standard mapping, numbered by the driver
+    Other(u8)
+}
+
+/// Sticks range from -1.0 to 1.0, with positive x to the right and positive y down. Triggers range
+/// from 0.0 (released) to 1.0 (fully pressed)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+
+    /// An axis without a standard mapping, numbered by the driver
+    Other(u8)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GamepadEvent {
+    Connected(GamepadId),
+    Disconnected(GamepadId),
+    Button(GamepadId, GamepadButton, ElementState),
+    Axis(GamepadId, GamepadAxis, f32)
+}
+
+/// A source of gamepad events. `App` polls this once per frame and passes the events to
+/// `InputState::handle_gamepad_event`.
+pub trait GamepadBackend {
+    /// Append any events that arrived since the last poll. Must not block
+    fn poll_events(&mut self, events: &mut Vec<GamepadEvent>);
+
+    /// Called once by `App` with a function that wakes its event loop. Backends that receive events
+    /// on other threads should call it when one arrives, so that an idle reactive app notices the
+    /// event without polling. The default does nothing
+    fn set_waker(&mut self, _waker: Box<dyn Fn() + Send>) {
+
+    }
+}
+
+impl GamepadButton {
+    // Number of buttons with a standard mapping
+    const NAMED_COUNT : usize = 15;
+
+    pub(super) const COUNT : usize = 32;
+
+    pub(super) fn to_index(self) -> Option<usize> {
+        let index = match self {
+            GamepadButton::South => 0,
+            GamepadButton::East => 1,
+            GamepadButton::West => 2,
+            GamepadButton::North => 3,
+            GamepadButton::LeftBumper => 4,
+            GamepadButton::RightBumper => 5,
+            GamepadButton::Select => 6,
+            GamepadButton::Start => 7,
+            GamepadButton::Mode => 8,
+            GamepadButton::LeftStick => 9,
+            GamepadButton::RightStick => 10,
+            GamepadButton::DPadUp => 11,
+            GamepadButton::DPadDown => 12,
+            GamepadButton::DPadLeft => 13,
+            GamepadButton::DPadRight => 14,
+            GamepadButton::Other(n) => Self::NAMED_COUNT + n as usize
+        };
+
+        if index < Self::COUNT {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// The name printed with `{:?}`, i.e. "South" or "Other(12)"
+    pub fn from_name(name: &str) -> Option<Self> {
+        let button = match name {
+            "South" => GamepadButton::South,
+            "East" => GamepadButton::East,
+            "West" => GamepadButton::West,
+            "North" => GamepadButton::North,
+            "LeftBumper" => GamepadButton::LeftBumper,
+            "RightBumper" => GamepadButton::RightBumper,
+            "Select" => GamepadButton::Select,
+            "Start" => GamepadButton::Start,
+            "Mode" => GamepadButton::Mode,
+            "LeftStick" => GamepadButton::LeftStick,
+            "RightStick" => GamepadButton::RightStick,
+            "DPadUp" => GamepadButton::DPadUp,
+            "DPadDown" => GamepadButton::DPadDown,
+            "DPadLeft" => GamepadButton::DPadLeft,
+            "DPadRight" => GamepadButton::DPadRight,
+            _ => return parse_other(name).map(GamepadButton::Other)
+        };
+
+        Some(button)
+    }
+}
+
+impl GamepadAxis {
+    // Number of axes with a standard mapping
+    const NAMED_COUNT : usize = 6;
+
+    pub(super) const COUNT : usize = 16;
+
+    pub(super) fn to_index(self) -> Option<usize> {
+        let index = match self {
+            GamepadAxis::LeftStickX => 0,
+            GamepadAxis::LeftStickY => 1,
+            GamepadAxis::RightStickX => 2,
+            GamepadAxis::RightStickY => 3,
+            GamepadAxis::LeftTrigger => 4,
+            GamepadAxis::RightTrigger => 5,
+            GamepadAxis::Other(n) => Self::NAMED_COUNT + n as usize
+        };
+
+        if index < Self::COUNT {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// The name printed with `{:?}`, i.e. "LeftStickX" or "Other(3)"
+    pub fn from_name(name: &str) -> Option<Self> {
+        let axis = match name {
+            "LeftStickX" => GamepadAxis::LeftStickX,
+            "LeftStickY" => GamepadAxis::LeftStickY,
+            "RightStickX" => GamepadAxis::RightStickX,
+            "RightStickY" => GamepadAxis::RightStickY,
+            "LeftTrigger" => GamepadAxis::LeftTrigger,
+            "RightTrigger" => GamepadAxis::RightTrigger,
+            _ => return parse_other(name).map(GamepadAxis::Other)
+        };
+
+        Some(axis)
+    }
+}
+
+fn parse_other(name: &str) -> Option<u8> {
+    if name.starts_with("Other(") && name.ends_with(')') {
+        name["Other(".len()..name.len() - 1].parse().ok()
+    } else {
+        None
+    }
+}
+
+/// State of a single gamepad slot, owned by `InputState`
+#[derive(Copy, Clone)]
+pub(super) struct GamepadState {
+    pub is_connected: bool,
+    pub just_connected: bool,
+    pub just_disconnected: bool,
+
+    pub button_is_down: [bool; GamepadButton::COUNT],
+    pub button_just_down: [bool; GamepadButton::COUNT],
+    pub button_just_up: [bool; GamepadButton::COUNT],
+
+    pub axes: [f32; GamepadAxis::COUNT],
+}
+
+impl Default for GamepadState {
+    fn default() -> Self {
+        GamepadState {
+            is_connected: false,
+            just_connected: false,
+            just_disconnected: false,
+            button_is_down: [false; GamepadButton::COUNT],
+            button_just_down: [false; GamepadButton::COUNT],
+            button_just_up: [false; GamepadButton::COUNT],
+            axes: [0.0; GamepadAxis::COUNT],
+        }
+    }
+}
+
+impl GamepadState {
+    pub fn end_frame(&mut self) {
+        self.just_connected = false;
+        self.just_disconnected = false;
+
+        for value in self.button_just_down.iter_mut() {
+            *value = false;
+        }
+
+        for value in self.button_just_up.iter_mut() {
+            *value = false;
+        }
+    }
+}
+
+/// A backend that only reports the events pushed into it. Clones share the same queue, so keep a
+/// clone to push events after handing the backend to the app.
+#[derive(Clone, Default)]
+pub struct MockGamepadBackend {
+    events: Rc<RefCell<VecDeque<GamepadEvent>>>
+}
+
+impl MockGamepadBackend {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Queue an event to be returned by the next poll
+    pub fn push_event(&self, event: GamepadEvent) {
+        self.events.borrow_mut().push_back(event);
+    }
+}
+
+impl GamepadBackend for MockGamepadBackend {
+    fn poll_events(&mut self, events: &mut Vec<GamepadEvent>) {
+        events.extend(self.events.borrow_mut().drain(..));
+    }
+}
+
+/// A backend that never reports any gamepads. Used on platforms without gamepad support
+#[derive(Default)]
+pub struct NullGamepadBackend;
+
+impl GamepadBackend for NullGamepadBackend {
+    fn poll_events(&mut self, _events: &mut Vec<GamepadEvent>) {
+
+    }
+}
+
+/// The linux joystick backend on linux, otherwise a backend that reports no gamepads
+pub fn default_gamepad_backend() -> Box<dyn GamepadBackend> {
+    #[cfg(target_os = "linux")]
+    {
+        Box::new(LinuxJoystickBackend::new())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        warn!("Gamepads are only supported on linux");
+        Box::new(NullGamepadBackend)
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use linux::LinuxJoystickBackend;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::io::Read;
+    use std::os::unix::io::AsRawFd;
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::time;
+
+    use super::GamepadAxis;
+    use super::GamepadBackend;
+    use super::GamepadButton;
+    use super::GamepadEvent;
+    use super::GamepadId;
+    use crate::app::input_state::ElementState;
+
+    // Number of /dev/input/jsN devices to look for
+    const MAX_JOYSTICK_DEVICES : usize = 16;
+
+    // How often to look for newly connected devices
+    const DEVICE_SCAN_INTERVAL : time::Duration = time::Duration::from_secs(1);
+
+    // How long a reader thread waits for input before checking whether the backend was dropped
+    const READ_TIMEOUT_MILLISECONDS : i32 = 100;
+
+    // Flags in js_event.type, see linux/joystick.h
+    const JS_EVENT_BUTTON : u8 = 0x01;
+    const JS_EVENT_AXIS : u8 = 0x02;
+    const JS_EVENT_INIT : u8 = 0x80;
+
+    // Axis numbers used by the xpad driver for the d-pad, which are reported as buttons instead
+    const JS_AXIS_DPAD_X : u8 = 6;
+    const JS_AXIS_DPAD_Y : u8 = 7;
+
+    enum JoystickMessage {
+        Connected(usize),
+
+        // The raw 8 byte js_event
+        Event(usize, [u8; 8]),
+        Closed(usize)
+    }
+
+    // State shared between the backend and its threads
+    #[derive(Default)]
+    struct SharedState {
+        // Set when the backend is dropped. The threads exit the next time they check it
+        shutdown: AtomicBool,
+
+        // Set while a reader thread has the device open
+        is_open: [AtomicBool; MAX_JOYSTICK_DEVICES],
+
+        // Wakes the app's event loop, see GamepadBackend::set_waker
+        waker: Mutex<Option<Box<dyn Fn() + Send>>>
+    }
+
+    impl SharedState {
+        // Returns false if the backend was dropped
+        fn send(&self, sender: &mpsc::Sender<JoystickMessage>, message: JoystickMessage) -> bool {
+            if self.shutdown.load(Ordering::Relaxed) || sender.send(message).is_err() {
+                return false;
+            }
+
+            if let Some(waker) = &*self.waker.lock().unwrap() {
+                waker();
+            }
+
+            true
+        }
+    }
+
+    /// Reads the linux joystick API (/dev/input/jsN). Each device is read on its own thread, and
+    /// new devices are picked up within a second of being plugged in. Buttons and axes are mapped
+    /// assuming the layout of the xpad driver, which most controllers follow.
+    ///
+    /// The user must be able to read /dev/input/jsN, usually by being in the `input` group.
+    pub struct LinuxJoystickBackend {
+        receiver: mpsc::Receiver<JoystickMessage>,
+        shared_state: Arc<SharedState>
+    }
+
+    impl LinuxJoystickBackend {
+        pub fn new() -> Self {
+            let (sender, receiver) = mpsc::channel();
+            let shared_state = Arc::new(SharedState::default());
+
+            let scan_shared_state = shared_state.clone();
+            let spawn_result = std::thread::Builder::new().name("joystick scan".to_string()).spawn(move || {
+                let mut is_first_scan = true;
+                while !scan_shared_state.shutdown.load(Ordering::Relaxed) {
+                    scan_for_devices(&scan_shared_state, &sender, is_first_scan);
+                    is_first_scan = false;
+                    std::thread::sleep(DEVICE_SCAN_INTERVAL);
+                }
+            });
+
+            if let Err(e) = spawn_result {
+                warn!("Could not start thread to look for joysticks: {}", e);
+            }
+
+            LinuxJoystickBackend {
+                receiver,
+                shared_state
+            }
+        }
+    }
+
+    impl Default for LinuxJoystickBackend {
+        fn default() -> Self {
+            LinuxJoystickBackend::new()
+        }
+    }
+
+    impl Drop for LinuxJoystickBackend {
+        fn drop(&mut self) {
+            // The threads notice within READ_TIMEOUT_MILLISECONDS (or DEVICE_SCAN_INTERVAL for the
+            // scan thread) and close their devices
+            self.shared_state.shutdown.store(true, Ordering::Relaxed);
+        }
+    }
+
+    impl GamepadBackend for LinuxJoystickBackend {
+        fn poll_events(&mut self, events: &mut Vec<GamepadEvent>) {
+            while let Ok(message) = self.receiver.try_recv() {
+                match message {
+                    JoystickMessage::Connected(device_index) => {
+                        events.push(GamepadEvent::Connected(GamepadId(device_index)));
+                    },
+                    JoystickMessage::Event(device_index, buffer) => {
+                        translate_js_event(GamepadId(device_index), buffer, events);
+                    },
+                    JoystickMessage::Closed(device_index) => {
+                        info!("Joystick {} disconnected", device_index);
+                        events.push(GamepadEvent::Disconnected(GamepadId(device_index)));
+                    }
+                }
+            }
+        }
+
+        fn set_waker(&mut self, waker: Box<dyn Fn() + Send>) {
+            *self.shared_state.waker.lock().unwrap() = Some(waker);
+        }
+    }
+
+    // Open any devices that aren't already open, and start a thread to read each one
+    fn scan_for_devices(
+        shared_state: &Arc<SharedState>,
+        sender: &mpsc::Sender<JoystickMessage>,
+        is_first_scan: bool
+    ) {
+        for device_index in 0..MAX_JOYSTICK_DEVICES {
+            if shared_state.is_open[device_index].load(Ordering::Relaxed) {
+                continue;
+            }
+
+            let path = PathBuf::from(format!("/dev/input/js{}", device_index));
+            if !path.exists() {
+                continue;
+            }
+
+            let file = match std::fs::File::open(&path) {
+                Ok(file) => file,
+                Err(e) => {
+                    // Likely a permissions problem, only log it once
+                    if is_first_scan {
+                        warn!("Could not open joystick {:?}: {}", path, e);
+                    }
+                    continue;
+                }
+            };
+
+            info!("Opened joystick {:?}", path);
+            shared_state.is_open[device_index].store(true, Ordering::Relaxed);
+
+            // Sent before the reader thread starts so that it arrives before the device's events
+            if !shared_state.send(sender, JoystickMessage::Connected(device_index)) {
+                return;
+            }
+
+            let reader_shared_state = shared_state.clone();
+            let reader_sender = sender.clone();
+            let thread_name = format!("joystick {}", device_index);
+            let spawn_result = std::thread::Builder::new().name(thread_name).spawn(move || {
+                read_device(file, device_index, &reader_shared_state, &reader_sender);
+                reader_shared_state.is_open[device_index].store(false, Ordering::Relaxed);
+            });
+
+            if let Err(e) = spawn_result {
+                warn!("Could not start thread for joystick {:?}: {}", path, e);
+                shared_state.is_open[device_index].store(false, Ordering::Relaxed);
+                shared_state.send(sender, JoystickMessage::Closed(device_index));
+            }
+        }
+    }
+
+    // Forward events from the device until it is unplugged or the backend is dropped. The file is
+    // closed when this returns
+    fn read_device(
+        mut file: std::fs::File,
+        device_index: usize,
+        shared_state: &SharedState,
+        sender: &mpsc::Sender<JoystickMessage>
+    ) {
+        let mut buffer = [0u8; 8];
+        while !shared_state.shutdown.load(Ordering::Relaxed) {
+            // Wait with a timeout rather than blocking in read, so that shutdown is noticed
+            let mut poll_fd = libc::pollfd {
+                fd: file.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0
+            };
+
+            let poll_result = unsafe { libc::poll(&mut poll_fd, 1, READ_TIMEOUT_MILLISECONDS) };
+            if poll_result < 0 {
+                if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+            } else if poll_result == 0 {
+                continue;
+            }
+
+            // Either there is input, or poll reported an error (i.e. the device was unplugged) which
+            // the read will report too
+            if file.read_exact(&mut buffer).is_err() {
+                shared_state.send(sender, JoystickMessage::Closed(device_index));
+                return;
+            }
+
+            if !shared_state.send(sender, JoystickMessage::Event(device_index, buffer)) {
+                return;
+            }
+        }
+    }
+
+    // struct js_event { __u32 time; __s16 value; __u8 type; __u8 number; }
+    fn translate_js_event(id: GamepadId, buffer: [u8; 8], events: &mut Vec<GamepadEvent>) {
+        let value = i16::from_ne_bytes([buffer[4], buffer[5]]);
+        let event_type = buffer[6] & !JS_EVENT_INIT;
+        let number = buffer[7];
+
+        match event_type {
+            JS_EVENT_BUTTON => {
+                let state = if value != 0 { ElementState::Pressed } else { ElementState::Released };
+                events.push(GamepadEvent::Button(id, js_button(number), state));
+            },
+            JS_EVENT_AXIS => {
+                match number {
+                    JS_AXIS_DPAD_X => push_dpad_events(id, value, GamepadButton::DPadLeft, GamepadButton::DPadRight, events),
+                    JS_AXIS_DPAD_Y => push_dpad_events(id, value, GamepadButton::DPadUp, GamepadButton::DPadDown, events),
+                    _ => {
+                        let axis = js_axis(number);
+                        let normalized = (value as f32 / 32767.0).max(-1.0).min(1.0);
+                        let value = match axis {
+                            // Triggers rest at -1.0
+                            GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger => (normalized + 1.0) / 2.0,
+                            _ => normalized
+                        };
+
+                        events.push(GamepadEvent::Axis(id, axis, value));
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn push_dpad_events(
+        id: GamepadId,
+        value: i16,
+        negative: GamepadButton,
+        positive: GamepadButton,
+        events: &mut Vec<GamepadEvent>
+    ) {
+        let state = |is_pressed: bool| if is_pressed { ElementState::Pressed } else { ElementState::Released };
+        events.push(GamepadEvent::Button(id, negative, state(value < 0)));
+        events.push(GamepadEvent::Button(id, positive, state(value > 0)));
+    }
+
+    fn js_button(number: u8) -> GamepadButton {
+        match number {
+            0 => GamepadButton::South,
+            1 => GamepadButton::East,
+            2 => GamepadButton::West,
+            3 => GamepadButton::North,
+            4 => GamepadButton::LeftBumper,
+            5 => GamepadButton::RightBumper,
+            6 => GamepadButton::Select,
+            7 => GamepadButton::Start,
+            8 => GamepadButton::Mode,
+            9 => GamepadButton::LeftStick,
+            10 => GamepadButton::RightStick,
+            _ => GamepadButton::Other(number)
+        }
+    }
+
+    fn js_axis(number: u8) -> GamepadAxis {
+        match number {
+            0 => GamepadAxis::LeftStickX,
+            1 => GamepadAxis::LeftStickY,
+            2 => GamepadAxis::LeftTrigger,
+            3 => GamepadAxis::RightStickX,
+            4 => GamepadAxis::RightStickY,
+            5 => GamepadAxis::RightTrigger,
+            _ => GamepadAxis::Other(number)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn js_event(value: i16, event_type: u8, number: u8) -> [u8; 8] {
+            let value = value.to_ne_bytes();
+            [0, 0, 0, 0, value[0], value[1], event_type, number]
+        }
+
+        fn translate(buffer: [u8; 8]) -> Vec<GamepadEvent> {
+            let mut events = vec![];
+            translate_js_event(GamepadId(0), buffer, &mut events);
+            events
+        }
+
+        #[test]
+        fn dpad_axes_are_reported_as_buttons() {
+            let id = GamepadId(0);
+
+            assert_eq!(translate(js_event(-32767, JS_EVENT_AXIS, JS_AXIS_DPAD_X)), vec![
+                GamepadEvent::Button(id, GamepadButton::DPadLeft, ElementState::Pressed),
+                GamepadEvent::Button(id, GamepadButton::DPadRight, ElementState::Released)
+            ]);
+
+            assert_eq!(translate(js_event(32767, JS_EVENT_AXIS, JS_AXIS_DPAD_Y)), vec![
+                GamepadEvent::Button(id, GamepadButton::DPadUp, ElementState::Released),
+                GamepadEvent::Button(id, GamepadButton::DPadDown, ElementState::Pressed)
+            ]);
+
+            // Centering the d-pad releases both directions
+            assert_eq!(translate(js_event(0, JS_EVENT_AXIS, JS_AXIS_DPAD_X)), vec![
+                GamepadEvent::Button(id, GamepadButton::DPadLeft, ElementState::Released),
+                GamepadEvent::Button(id, GamepadButton::DPadRight, ElementState::Released)
+            ]);
+        }
+
+        #[test]
+        fn triggers_range_from_zero_to_one() {
+            let id = GamepadId(0);
+
+            assert_eq!(translate(js_event(-32767, JS_EVENT_AXIS, 2)), vec![GamepadEvent::Axis(id, GamepadAxis::LeftTrigger, 0.0)]);
+            assert_eq!(translate(js_event(0, JS_EVENT_AXIS, 2)), vec![GamepadEvent::Axis(id, GamepadAxis::LeftTrigger, 0.5)]);
+            assert_eq!(translate(js_event(32767, JS_EVENT_AXIS, 5)), vec![GamepadEvent::Axis(id, GamepadAxis::RightTrigger, 1.0)]);
+        }
+
+        #[test]
+        fn sticks_are_clamped() {
+            let id = GamepadId(0);
+
+            assert_eq!(translate(js_event(-32768, JS_EVENT_AXIS, 0)), vec![GamepadEvent::Axis(id, GamepadAxis::LeftStickX, -1.0)]);
+            assert_eq!(translate(js_event(32767, JS_EVENT_AXIS, 4)), vec![GamepadEvent::Axis(id, GamepadAxis::RightStickY, 1.0)]);
+        }
+
+        #[test]
+        fn buttons_include_initial_state() {
+            let id = GamepadId(0);
+
+            // The driver reports the state of every button when the device is opened
+            assert_eq!(translate(js_event(1, JS_EVENT_BUTTON | JS_EVENT_INIT, 0)), vec![
+                GamepadEvent::Button(id, GamepadButton::South, ElementState::Pressed)
+            ]);
+
+            assert_eq!(translate(js_event(0, JS_EVENT_BUTTON, 20)), vec![
+                GamepadEvent::Button(id, GamepadButton::Other(20), ElementState::Released)
+            ]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::input_state::InputState;
+    use crate::app::input_state::LogicalSize;
+
+    // Deliver queued events the way App does once per frame
+    fn poll(backend: &mut MockGamepadBackend, input_state: &mut InputState) {
+        let mut events = vec![];
+        backend.poll_events(&mut events);
+        for event in events {
+            input_state.handle_gamepad_event(event);
+        }
+    }
+
+    #[test]
+    fn button_just_down_and_just_up() {
+        let id = GamepadId(0);
+        let mut backend = MockGamepadBackend::new();
+        let mut input_state = InputState::new_with_window_size(LogicalSize::new(800.0, 600.0), 1.0);
+
+        backend.push_event(GamepadEvent::Connected(id));
+        backend.push_event(GamepadEvent::Button(id, GamepadButton::South, ElementState::Pressed));
+        poll(&mut backend, &mut input_state);
+
+        assert!(input_state.is_gamepad_just_connected(id));
+        assert!(input_state.is_gamepad_button_down(id, GamepadButton::South));
+        assert!(input_state.is_gamepad_button_just_down(id, GamepadButton::South));
+        assert!(!input_state.is_gamepad_button_just_up(id, GamepadButton::South));
+
+        // Held into the next frame
+        input_state.end_frame();
+        assert!(input_state.is_gamepad_button_down(id, GamepadButton::South));
+        assert!(!input_state.is_gamepad_button_just_down(id, GamepadButton::South));
+
+        // A repeated press while held is not a new press
+        backend.push_event(GamepadEvent::Button(id, GamepadButton::South, ElementState::Pressed));
+        poll(&mut backend, &mut input_state);
+        assert!(!input_state.is_gamepad_button_just_down(id, GamepadButton::South));
+
+        backend.push_event(GamepadEvent::Button(id, GamepadButton::South, ElementState::Released));
+        poll(&mut backend, &mut input_state);
+        assert!(!input_state.is_gamepad_button_down(id, GamepadButton::South));
+        assert!(input_state.is_gamepad_button_just_up(id, GamepadButton::South));
+
+        input_state.end_frame();
+        assert!(!input_state.is_gamepad_button_just_up(id, GamepadButton::South));
+    }
+
+    #[test]
+    fn disconnect_releases_buttons_and_axes() {
+        let id = GamepadId(1);
+        let mut backend = MockGamepadBackend::new();
+        let mut input_state = InputState::new_with_window_size(LogicalSize::new(800.0, 600.0), 1.0);
+
+        backend.push_event(GamepadEvent::Connected(id));
+        backend.push_event(GamepadEvent::Button(id, GamepadButton::DPadUp, ElementState::Pressed));
+        backend.push_event(GamepadEvent::Axis(id, GamepadAxis::LeftTrigger, 0.75));
+        poll(&mut backend, &mut input_state);
+        assert_eq!(input_state.gamepad_axis(id, GamepadAxis::LeftTrigger), 0.75);
+
+        input_state.end_frame();
+        backend.push_event(GamepadEvent::Disconnected(id));
+        poll(&mut backend, &mut input_state);
+
+        assert!(!input_state.is_gamepad_connected(id));
+        assert!(input_state.is_gamepad_just_disconnected(id));
+        assert!(!input_state.is_gamepad_button_down(id, GamepadButton::DPadUp));
+        assert_eq!(input_state.gamepad_axis(id, GamepadAxis::LeftTrigger), 0.0);
+    }
+
+    #[test]
+    fn button_names_round_trip() {
+        let buttons = [
+            GamepadButton::South,
+            GamepadButton::East,
+            GamepadButton::West,
+            GamepadButton::North,
+            GamepadButton::LeftBumper,
+            GamepadButton::RightBumper,
+            GamepadButton::Select,
+            GamepadButton::Start,
+            GamepadButton::Mode,
+            GamepadButton::LeftStick,
+            GamepadButton::RightStick,
+            GamepadButton::DPadUp,
+            GamepadButton::DPadDown,
+            GamepadButton::DPadLeft,
+            GamepadButton::DPadRight,
+            GamepadButton::Other(0),
+            GamepadButton::Other(255)
+        ];
+
+        for button in buttons.iter() {
+            assert_eq!(GamepadButton::from_name(&format!("{:?}", button)), Some(*button));
+        }
+
+        assert_eq!(GamepadButton::from_name("Other(256)"), None);
+        assert_eq!(GamepadButton::from_name("Other()"), None);
+        assert_eq!(GamepadButton::from_name("south"), None);
+    }
+
+    #[test]
+    fn axis_names_round_trip() {
+        let axes = [
+            GamepadAxis::LeftStickX,
+            GamepadAxis::LeftStickY,
+            GamepadAxis::RightStickX,
+            GamepadAxis::RightStickY,
+            GamepadAxis::LeftTrigger,
+            GamepadAxis::RightTrigger,
+            GamepadAxis::Other(7)
+        ];
+
+        for axis in axes.iter() {
+            assert_eq!(GamepadAxis::from_name(&format!("{:?}", axis)), Some(*axis));
+        }
+
+        assert_eq!(GamepadAxis::from_name("Other(-1)"), None);
+    }
+}
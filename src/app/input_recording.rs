@@ -0,0 +1,686 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use super::app::AppHandler;
+use super::app_control::AppControl;
+use super::input_state::InputState;
+use super::input_state::VirtualKeyCode;
+use super::input_state::MouseButton;
+use super::input_state::ElementState;
+use super::input_state::ModifiersState;
+use super::input_state::MouseScrollDelta;
+use super::input_state::LogicalSize;
+use super::input_state::LogicalPosition;
+use super::input_state::ImeEvent;
+use super::gamepad::GamepadEvent;
+use super::gamepad::GamepadId;
+use super::gamepad::GamepadButton;
+use super::gamepad::GamepadAxis;
+use super::touch::TouchPhase;
+use super::time_state::TimeState;
+use super::time_state::TimeContext;
+use super::key_names;
+
+// First line of a recording file
+const INPUT_RECORDING_HEADER : &str = "skulpin_input_recording 1";
+
+/// Input that is injected into the app at the start of a given frame, before `AppHandler::update`
+/// is called
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScriptedInputEvent {
+    Keyboard(VirtualKeyCode, ElementState),
+    MouseButton(MouseButton, ElementState),
+    MouseMove(LogicalPosition),
+    MouseWheel(MouseScrollDelta),
+    PinchGesture(f64),
+    Touch(u64, TouchPhase, LogicalPosition),
+    WindowResized(LogicalSize),
+    DpiFactorChanged(f64),
+    Character(char),
+    ModifiersChanged(ModifiersState),
+    Gamepad(GamepadEvent),
+
+    /// winit doesn't report IME events yet, so `App` never records these. Code that feeds IME
+    /// events to `InputState::handle_ime_event` should also pass them to `InputRecorder::record`
+    Ime(ImeEvent)
+}
+
+impl ScriptedInputEvent {
+    pub fn apply(&self, input_state: &mut InputState) {
+        match *self {
+            ScriptedInputEvent::Keyboard(key, state) => input_state.handle_keyboard_event(key, state),
+            ScriptedInputEvent::MouseButton(button, state) => input_state.handle_mouse_button_event(button, state),
+            ScriptedInputEvent::MouseMove(position) => input_state.handle_mouse_move_event(position),
+            ScriptedInputEvent::MouseWheel(delta) => input_state.handle_mouse_wheel_event(delta),
+            ScriptedInputEvent::PinchGesture(scale) => input_state.handle_pinch_gesture(scale),
+            ScriptedInputEvent::Touch(id, phase, position) => input_state.handle_touch_event(id, phase, position),
+            ScriptedInputEvent::WindowResized(window_size) => input_state.handle_window_size_changed(window_size),
+            ScriptedInputEvent::DpiFactorChanged(dpi_factor) => input_state.handle_hidpi_factor_changed(dpi_factor),
+            ScriptedInputEvent::Character(c) => input_state.handle_received_character(c),
+            ScriptedInputEvent::ModifiersChanged(modifiers) => input_state.handle_modifiers_changed(modifiers),
+            ScriptedInputEvent::Gamepad(event) => input_state.handle_gamepad_event(event),
+            ScriptedInputEvent::Ime(ref ime_event) => input_state.handle_ime_event(ime_event.clone())
+        }
+    }
+
+    /// Convert a winit event to the equivalent scripted event. Returns None for events that don't
+    /// affect `InputState`. Modifiers carried by the event are not included, see
+    /// `winit_event_modifiers`
+    pub fn from_winit_event<T>(event: &winit::event::Event<T>) -> Option<Self> {
+        use winit::event::Event;
+        use winit::event::WindowEvent;
+
+        let window_event = match event {
+            Event::WindowEvent { event, .. } => event,
+            _ => return None
+        };
+
+        match window_event {
+            WindowEvent::KeyboardInput { input, .. } => {
+                input.virtual_keycode.map(|key| ScriptedInputEvent::Keyboard(key, input.state))
+            },
+            WindowEvent::MouseInput { state, button, .. } => Some(ScriptedInputEvent::MouseButton(*button, *state)),
+            WindowEvent::CursorMoved { position, .. } => Some(ScriptedInputEvent::MouseMove(*position)),
+            WindowEvent::MouseWheel { delta, .. } => Some(ScriptedInputEvent::MouseWheel(*delta)),
+            WindowEvent::Touch(touch) => Some(ScriptedInputEvent::Touch(touch.id, touch.phase, touch.location)),
+            WindowEvent::Resized(window_size) => Some(ScriptedInputEvent::WindowResized(*window_size)),
+            WindowEvent::HiDpiFactorChanged(dpi_factor) => Some(ScriptedInputEvent::DpiFactorChanged(*dpi_factor)),
+            WindowEvent::ReceivedCharacter(c) => Some(ScriptedInputEvent::Character(*c)),
+            _ => None
+        }
+    }
+
+    /// The modifier state that winit reports along with some events (keyboard, mouse buttons, cursor
+    /// movement and mouse wheel)
+    pub fn winit_event_modifiers<T>(event: &winit::event::Event<T>) -> Option<ModifiersState> {
+        use winit::event::Event;
+        use winit::event::WindowEvent;
+
+        match event {
+            Event::WindowEvent { event: WindowEvent::KeyboardInput { input, .. }, .. } => Some(input.modifiers),
+            Event::WindowEvent { event: WindowEvent::MouseInput { modifiers, .. }, .. } => Some(*modifiers),
+            Event::WindowEvent { event: WindowEvent::CursorMoved { modifiers, .. }, .. } => Some(*modifiers),
+            Event::WindowEvent { event: WindowEvent::MouseWheel { modifiers, .. }, .. } => Some(*modifiers),
+            _ => None
+        }
+    }
+
+    fn to_line(&self) -> String {
+        match *self {
+            ScriptedInputEvent::Keyboard(key, state) => format!(
+                "key {} {}",
+                key_names::virtual_key_code_name(key),
+                element_state_name(state)
+            ),
+            ScriptedInputEvent::MouseButton(button, state) => format!(
+                "mouse_button {} {}",
+                key_names::mouse_button_name(button),
+                element_state_name(state)
+            ),
+            ScriptedInputEvent::MouseMove(position) => format!("mouse_move {} {}", position.x, position.y),
+            ScriptedInputEvent::MouseWheel(MouseScrollDelta::LineDelta(x, y)) => format!("scroll_lines {} {}", x, y),
+            ScriptedInputEvent::MouseWheel(MouseScrollDelta::PixelDelta(delta)) => format!("scroll_pixels {} {}", delta.x, delta.y),
+            ScriptedInputEvent::PinchGesture(scale) => format!("pinch {}", scale),
+            ScriptedInputEvent::Touch(id, phase, position) => format!("touch {} {:?} {} {}", id, phase, position.x, position.y),
+            ScriptedInputEvent::WindowResized(window_size) => format!("window_resized {} {}", window_size.width, window_size.height),
+            ScriptedInputEvent::DpiFactorChanged(dpi_factor) => format!("dpi_factor_changed {}", dpi_factor),
+            // Written as a code point so that whitespace and control characters survive
+            ScriptedInputEvent::Character(c) => format!("character {}", c as u32),
+            ScriptedInputEvent::ModifiersChanged(modifiers) => format!("modifiers {}", modifiers_names(modifiers)),
+            ScriptedInputEvent::Gamepad(GamepadEvent::Connected(id)) => format!("gamepad_connected {}", id.0),
+            ScriptedInputEvent::Gamepad(GamepadEvent::Disconnected(id)) => format!("gamepad_disconnected {}", id.0),
+            ScriptedInputEvent::Gamepad(GamepadEvent::Button(id, button, state)) => format!(
+                "gamepad_button {} {:?} {}",
+                id.0,
+                button,
+                element_state_name(state)
+            ),
+            ScriptedInputEvent::Gamepad(GamepadEvent::Axis(id, axis, value)) => format!("gamepad_axis {} {:?} {}", id.0, axis, value),
+            ScriptedInputEvent::Ime(ImeEvent::Preedit { ref text, cursor: Some((start, end)) }) => format!(
+                "ime_preedit {} {} {}",
+                start,
+                end,
+                ime_text_code_points(text)
+            ),
+            ScriptedInputEvent::Ime(ImeEvent::Preedit { ref text, cursor: None }) => format!("ime_preedit none {}", ime_text_code_points(text)),
+            ScriptedInputEvent::Ime(ImeEvent::Commit(ref text)) => format!("ime_commit {}", ime_text_code_points(text))
+        }
+    }
+
+    fn parse(keyword: &str, args: &[&str]) -> Result<Self, String> {
+        match (keyword, args) {
+            ("key", [key, state]) => {
+                let key = key_names::virtual_key_code_from_name(key)
+                    .ok_or_else(|| format!("unknown key {:?}", key))?;
+                Ok(ScriptedInputEvent::Keyboard(key, parse_element_state(state)?))
+            },
+            ("mouse_button", [button, state]) => {
+                let button = key_names::mouse_button_from_name(button)
+                    .ok_or_else(|| format!("unknown mouse button {:?}", button))?;
+                Ok(ScriptedInputEvent::MouseButton(button, parse_element_state(state)?))
+            },
+            ("mouse_move", [x, y]) => Ok(ScriptedInputEvent::MouseMove(LogicalPosition::new(parse_f64(x)?, parse_f64(y)?))),
+            ("scroll_lines", [x, y]) => {
+                let x = parse_f64(x)? as f32;
+                let y = parse_f64(y)? as f32;
+                Ok(ScriptedInputEvent::MouseWheel(MouseScrollDelta::LineDelta(x, y)))
+            },
+            ("scroll_pixels", [x, y]) => Ok(ScriptedInputEvent::MouseWheel(MouseScrollDelta::PixelDelta(LogicalPosition::new(parse_f64(x)?, parse_f64(y)?)))),
+            ("touch", [id, phase, x, y]) => {
+                let id = id.parse().map_err(|_| format!("invalid touch id {:?}", id))?;
+                let phase = match *phase {
+                    "Started" => TouchPhase::Started,
+                    "Moved" => TouchPhase::Moved,
+                    "Ended" => TouchPhase::Ended,
+                    "Cancelled" => TouchPhase::Cancelled,
+                    _ => return Err(format!("unknown touch phase {:?}", phase))
+                };
+                Ok(ScriptedInputEvent::Touch(id, phase, LogicalPosition::new(parse_f64(x)?, parse_f64(y)?)))
+            },
+            ("pinch", [scale]) => Ok(ScriptedInputEvent::PinchGesture(parse_f64(scale)?)),
+            ("window_resized", [width, height]) => Ok(ScriptedInputEvent::WindowResized(LogicalSize::new(parse_f64(width)?, parse_f64(height)?))),
+            ("dpi_factor_changed", [dpi_factor]) => Ok(ScriptedInputEvent::DpiFactorChanged(parse_f64(dpi_factor)?)),
+            ("character", [code_point]) => {
+                let c = code_point.parse::<u32>().ok()
+                    .and_then(std::char::from_u32)
+                    .ok_or_else(|| format!("invalid character code point {:?}", code_point))?;
+                Ok(ScriptedInputEvent::Character(c))
+            },
+            ("gamepad_connected", [id]) => Ok(ScriptedInputEvent::Gamepad(GamepadEvent::Connected(parse_gamepad_id(id)?))),
+            ("gamepad_disconnected", [id]) => Ok(ScriptedInputEvent::Gamepad(GamepadEvent::Disconnected(parse_gamepad_id(id)?))),
+            ("gamepad_button", [id, button, state]) => {
+                let button = GamepadButton::from_name(button)
+                    .ok_or_else(|| format!("unknown gamepad button {:?}", button))?;
+                Ok(ScriptedInputEvent::Gamepad(GamepadEvent::Button(parse_gamepad_id(id)?, button, parse_element_state(state)?)))
+            },
+            ("gamepad_axis", [id, axis, value]) => {
+                let axis = GamepadAxis::from_name(axis)
+                    .ok_or_else(|| format!("unknown gamepad axis {:?}", axis))?;
+                Ok(ScriptedInputEvent::Gamepad(GamepadEvent::Axis(parse_gamepad_id(id)?, axis, parse_f64(value)? as f32)))
+            },
+            ("ime_preedit", ["none", text]) => Ok(ScriptedInputEvent::Ime(ImeEvent::Preedit {
+                text: parse_ime_text(text)?,
+                cursor: None
+            })),
+            ("ime_preedit", [start, end, text]) => {
+                let start = start.parse().map_err(|_| format!("invalid ime cursor {:?}", start))?;
+                let end = end.parse().map_err(|_| format!("invalid ime cursor {:?}", end))?;
+                Ok(ScriptedInputEvent::Ime(ImeEvent::Preedit {
+                    text: parse_ime_text(text)?,
+                    cursor: Some((start, end))
+                }))
+            },
+            ("ime_commit", [text]) => Ok(ScriptedInputEvent::Ime(ImeEvent::Commit(parse_ime_text(text)?))),
+            ("modifiers", names) => Ok(ScriptedInputEvent::ModifiersChanged(parse_modifiers(names)?)),
+            _ => Err(format!("unrecognized event {:?} with {} argument(s)", keyword, args.len()))
+        }
+    }
+}
+
+/// The input events that arrived before a single call to `AppHandler::update`, and how much time
+/// passed in that update
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedFrame {
+    pub elapsed: Duration,
+    pub events: Vec<ScriptedInputEvent>
+}
+
+impl RecordedFrame {
+    fn to_text(&self) -> String {
+        let mut text = format!("frame {}\n", self.elapsed.as_nanos());
+        for event in &self.events {
+            text.push_str("    ");
+            text.push_str(&event.to_line());
+            text.push('\n');
+        }
+
+        text
+    }
+}
+
+/// A sequence of input events and frame times that can be saved to a file and replayed. The file
+/// is plain text with one event per line, so it can be attached to a bug report or edited by hand.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputRecording {
+    /// Window size when recording started
+    pub logical_size: LogicalSize,
+
+    /// DPI factor when recording started
+    pub dpi_factor: f64,
+
+    pub frames: Vec<RecordedFrame>
+}
+
+impl InputRecording {
+    pub fn new(logical_size: LogicalSize, dpi_factor: f64) -> Self {
+        InputRecording {
+            logical_size,
+            dpi_factor,
+            frames: vec![]
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = self.header_text();
+        for frame in &self.frames {
+            text.push_str(&frame.to_text());
+        }
+
+        text
+    }
+
+    // Everything before the first frame
+    fn header_text(&self) -> String {
+        format!(
+            "{}\nwindow_size {} {}\ndpi_factor {}\n",
+            INPUT_RECORDING_HEADER,
+            self.logical_size.width,
+            self.logical_size.height,
+            self.dpi_factor
+        )
+    }
+
+    /// Parse a recording. Blank lines and lines starting with # are ignored.
+    pub fn from_text(text: &str) -> std::io::Result<Self> {
+        let mut lines = text.lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        match lines.next() {
+            Some((_, INPUT_RECORDING_HEADER)) => {},
+            _ => return Err(invalid_data(format!("Input recording must start with {:?}", INPUT_RECORDING_HEADER)))
+        }
+
+        let mut recording = InputRecording::new(LogicalSize::new(900.0, 600.0), 1.0);
+
+        for (line_number, line) in lines {
+            let parts : Vec<&str> = line.split_whitespace().collect();
+            let result = match (parts[0], &parts[1..]) {
+                ("window_size", [width, height]) => parse_f64(width).and_then(|width| {
+                    recording.logical_size = LogicalSize::new(width, parse_f64(height)?);
+                    Ok(())
+                }),
+                ("dpi_factor", [dpi_factor]) => parse_f64(dpi_factor).map(|dpi_factor| {
+                    recording.dpi_factor = dpi_factor;
+                }),
+                ("frame", [elapsed_nanos]) => elapsed_nanos.parse::<u64>()
+                    .map_err(|_| format!("invalid frame time {:?}", elapsed_nanos))
+                    .map(|elapsed_nanos| {
+                        recording.frames.push(RecordedFrame {
+                            elapsed: Duration::from_nanos(elapsed_nanos),
+                            events: vec![]
+                        });
+                    }),
+                (keyword, args) => ScriptedInputEvent::parse(keyword, args).and_then(|event| {
+                    match recording.frames.last_mut() {
+                        Some(frame) => {
+                            frame.events.push(event);
+                            Ok(())
+                        },
+                        None => Err("event before the first frame".to_string())
+                    }
+                })
+            };
+
+            if let Err(e) = result {
+                return Err(invalid_data(format!("Input recording line {}: {}", line_number, e)));
+            }
+        }
+
+        Ok(recording)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, self.to_text())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::from_text(&std::fs::read_to_string(path)?)
+    }
+
+    /// Total time covered by the recording
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|x| x.elapsed).sum()
+    }
+}
+
+/// Captures winit events into an `InputRecording`. Call `record_winit_event` for every event and
+/// `end_frame` once per call to `AppHandler::update`. `AppBuilder::record_input` does this
+/// automatically for the main window only. Events don't carry a window id, so events from other
+/// windows should not be passed in; they would be replayed as main window input.
+pub struct InputRecorder {
+    recording: InputRecording,
+
+    // If recording to a file, each frame is written and flushed as it ends so that a crash loses at
+    // most the frame in progress
+    writer: Option<BufWriter<File>>,
+
+    // Events since the last call to end_frame
+    pending_events: Vec<ScriptedInputEvent>,
+
+    // Modifiers as of the last recorded event. A ModifiersChanged event is recorded when they change
+    modifiers: ModifiersState
+}
+
+impl InputRecorder {
+    pub fn new(logical_size: LogicalSize, dpi_factor: f64) -> Self {
+        InputRecorder {
+            recording: InputRecording::new(logical_size, dpi_factor),
+            writer: None,
+            pending_events: vec![],
+            modifiers: ModifiersState::default()
+        }
+    }
+
+    /// Record to the given file, which is created (or truncated) immediately. Frames are written
+    /// as they end rather than when recording finishes, so the recording survives a crash.
+    pub fn create_file<P: AsRef<Path>>(path: P, logical_size: LogicalSize, dpi_factor: f64) -> std::io::Result<Self> {
+        let mut recorder = InputRecorder::new(logical_size, dpi_factor);
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(recorder.recording.header_text().as_bytes())?;
+        writer.flush()?;
+        recorder.writer = Some(writer);
+        Ok(recorder)
+    }
+
+    pub fn record(&mut self, event: ScriptedInputEvent) {
+        self.pending_events.push(event);
+    }
+
+    pub fn record_winit_event<T>(&mut self, event: &winit::event::Event<T>) {
+        if let Some(modifiers) = ScriptedInputEvent::winit_event_modifiers(event) {
+            if modifiers != self.modifiers {
+                self.modifiers = modifiers;
+                self.record(ScriptedInputEvent::ModifiersChanged(modifiers));
+            }
+        }
+
+        if let Some(event) = ScriptedInputEvent::from_winit_event(event) {
+            self.record(event);
+        }
+    }
+
+    /// Close the current frame. Events recorded so far are replayed before the update that
+    /// advances time by `elapsed`.
+    pub fn end_frame(&mut self, elapsed: Duration) {
+        let frame = RecordedFrame {
+            elapsed,
+            events: std::mem::replace(&mut self.pending_events, vec![])
+        };
+
+        if let Some(writer) = &mut self.writer {
+            let result = writer.write_all(frame.to_text().as_bytes())
+                .and_then(|_| writer.flush());
+
+            if let Err(e) = result {
+                // Keep recording in memory, but don't retry (and warn) every frame
+                warn!("Error writing input recording, no further frames will be written: {}", e);
+                self.writer = None;
+            }
+        }
+
+        self.recording.frames.push(frame);
+    }
+
+    pub fn recording(&self) -> &InputRecording {
+        &self.recording
+    }
+
+    /// Events recorded after the last call to `end_frame` are discarded. If recording to a file,
+    /// it already contains every ended frame.
+    pub fn finish(self) -> InputRecording {
+        self.recording
+    }
+}
+
+/// Feeds an `InputRecording` back into an `InputState` and `TimeState`, one frame at a time,
+/// without a window. Time advances by exactly the recorded amount each frame, so a replay produces
+/// the same sequence of updates as the original run.
+pub struct InputReplay {
+    recording: InputRecording,
+    next_frame_index: usize
+}
+
+impl InputReplay {
+    pub fn new(recording: InputRecording) -> Self {
+        InputReplay {
+            recording,
+            next_frame_index: 0
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Ok(Self::new(InputRecording::load(path)?))
+    }
+
+    pub fn recording(&self) -> &InputRecording {
+        &self.recording
+    }
+
+    /// Input state matching the window when recording started
+    pub fn create_input_state(&self) -> InputState {
+        InputState::new_with_window_size(self.recording.logical_size, self.recording.dpi_factor)
+    }
+
+    /// Index of the next frame that will be replayed
+    pub fn frame_index(&self) -> usize {
+        self.next_frame_index
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_frame_index >= self.recording.frames.len()
+    }
+
+    /// Apply the next frame's input and advance the simulation by its recorded elapsed time. Call
+    /// `AppHandler::update` afterwards. Returns false once every frame has been replayed.
+    pub fn advance_frame(&mut self, input_state: &mut InputState, time_state: &mut TimeState) -> bool {
+        let frame = match self.recording.frames.get(self.next_frame_index) {
+            Some(frame) => frame,
+            None => return false
+        };
+
+        for event in &frame.events {
+            event.apply(input_state);
+        }
+
+        time_state.update_with_elapsed(TimeContext::Simulation, frame.elapsed);
+        input_state.advance_time(frame.elapsed);
+        self.next_frame_index += 1;
+        true
+    }
+
+    /// Replay the remaining frames through `AppHandler::update`, stopping early if the app asks to
+    /// terminate. Nothing is drawn; use `AppTestHarness::input_recording` to also compare frames.
+    pub fn run_updates<T : AppHandler>(&mut self, app_handler: &mut T) -> (InputState, TimeState) {
+        let mut app_control = AppControl::default();
+        let mut time_state = TimeState::default();
+        let mut input_state = self.create_input_state();
+
+        while self.advance_frame(&mut input_state, &mut time_state) {
+            // This mirrors the order of operations in App::run
+            app_handler.update(&mut app_control, &input_state, &time_state);
+
+            for (time_context, request) in app_control.take_time_context_requests() {
+                time_state.apply_time_context_request(time_context, request);
+            }
+
+            input_state.end_frame();
+
+            if app_control.should_terminate_process() {
+                break;
+            }
+        }
+
+        (input_state, time_state)
+    }
+}
+
+fn element_state_name(state: ElementState) -> &'static str {
+    match state {
+        ElementState::Pressed => "pressed",
+        ElementState::Released => "released"
+    }
+}
+
+fn parse_element_state(state: &str) -> Result<ElementState, String> {
+    match state {
+        "pressed" => Ok(ElementState::Pressed),
+        "released" => Ok(ElementState::Released),
+        _ => Err(format!("expected pressed or released, found {:?}", state))
+    }
+}
+
+fn modifiers_names(modifiers: ModifiersState) -> String {
+    let mut names = vec![];
+    if modifiers.shift { names.push("shift"); }
+    if modifiers.ctrl { names.push("ctrl"); }
+    if modifiers.alt { names.push("alt"); }
+    if modifiers.logo { names.push("logo"); }
+
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join(" ")
+    }
+}
+
+fn parse_modifiers(names: &[&str]) -> Result<ModifiersState, String> {
+    let mut modifiers = ModifiersState::default();
+    for name in names {
+        match *name {
+            "shift" => modifiers.shift = true,
+            "ctrl" => modifiers.ctrl = true,
+            "alt" => modifiers.alt = true,
+            "logo" => modifiers.logo = true,
+            "none" => {},
+            _ => return Err(format!("unknown modifier {:?}", name))
+        }
+    }
+
+    Ok(modifiers)
+}
+
+// IME text is written as comma separated code points (like character events) so that whitespace
+// survives. Empty text is written as "-"
+fn ime_text_code_points(text: &str) -> String {
+    if text.is_empty() {
+        "-".to_string()
+    } else {
+        text.chars().map(|c| (c as u32).to_string()).collect::<Vec<_>>().join(",")
+    }
+}
+
+fn parse_ime_text(code_points: &str) -> Result<String, String> {
+    if code_points == "-" {
+        return Ok(String::new());
+    }
+
+    code_points.split(',')
+        .map(|code_point| code_point.parse::<u32>().ok()
+            .and_then(std::char::from_u32)
+            .ok_or_else(|| format!("invalid ime text code point {:?}", code_point)))
+        .collect()
+}
+
+fn parse_gamepad_id(id: &str) -> Result<GamepadId, String> {
+    id.parse().map(GamepadId).map_err(|_| format!("invalid gamepad id {:?}", id))
+}
+
+fn parse_f64(value: &str) -> Result<f64, String> {
+    value.parse().map_err(|_| format!("invalid number {:?}", value))
+}
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(elapsed_millis: u64, events: Vec<ScriptedInputEvent>) -> RecordedFrame {
+        RecordedFrame {
+            elapsed: Duration::from_millis(elapsed_millis),
+            events
+        }
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let gamepad = GamepadId(2);
+        let mut recording = InputRecording::new(LogicalSize::new(1024.0, 768.0), 1.5);
+        recording.frames = vec![
+            frame(16, vec![]),
+            frame(17, vec![
+                ScriptedInputEvent::Keyboard(VirtualKeyCode::A, ElementState::Pressed),
+                ScriptedInputEvent::MouseButton(MouseButton::Left, ElementState::Released),
+                ScriptedInputEvent::MouseMove(LogicalPosition::new(10.5, -3.25)),
+                ScriptedInputEvent::Character(' '),
+                ScriptedInputEvent::ModifiersChanged(ModifiersState { shift: true, ctrl: false, alt: true, logo: false }),
+                ScriptedInputEvent::ModifiersChanged(ModifiersState::default())
+            ]),
+            frame(15, vec![
+                ScriptedInputEvent::MouseWheel(MouseScrollDelta::LineDelta(0.0, -2.5)),
+                ScriptedInputEvent::MouseWheel(MouseScrollDelta::PixelDelta(LogicalPosition::new(4.0, 12.75))),
+                ScriptedInputEvent::PinchGesture(1.125)
+            ]),
+            frame(16, vec![
+                ScriptedInputEvent::Touch(7, TouchPhase::Started, LogicalPosition::new(100.0, 200.0)),
+                ScriptedInputEvent::Touch(7, TouchPhase::Moved, LogicalPosition::new(110.5, 190.0)),
+                ScriptedInputEvent::Touch(7, TouchPhase::Ended, LogicalPosition::new(110.5, 190.0)),
+                ScriptedInputEvent::Touch(8, TouchPhase::Cancelled, LogicalPosition::new(0.0, 0.0))
+            ]),
+            frame(16, vec![
+                ScriptedInputEvent::Gamepad(GamepadEvent::Connected(gamepad)),
+                ScriptedInputEvent::Gamepad(GamepadEvent::Button(gamepad, GamepadButton::South, ElementState::Pressed)),
+                ScriptedInputEvent::Gamepad(GamepadEvent::Axis(gamepad, GamepadAxis::LeftTrigger, -0.75)),
+                ScriptedInputEvent::Gamepad(GamepadEvent::Disconnected(gamepad))
+            ]),
+            frame(16, vec![
+                ScriptedInputEvent::Ime(ImeEvent::Preedit { text: "\u{65e5} \u{672c}".to_string(), cursor: Some((0, 3)) }),
+                ScriptedInputEvent::Ime(ImeEvent::Preedit { text: String::new(), cursor: None }),
+                ScriptedInputEvent::Ime(ImeEvent::Commit("\u{65e5}\u{672c}".to_string()))
+            ]),
+            frame(33, vec![
+                ScriptedInputEvent::WindowResized(LogicalSize::new(640.0, 480.0)),
+                ScriptedInputEvent::DpiFactorChanged(2.0)
+            ])
+        ];
+
+        let text = recording.to_text();
+        let parsed = InputRecording::from_text(&text).unwrap();
+        assert_eq!(parsed, recording);
+        assert_eq!(parsed.to_text(), text);
+        assert_eq!(parsed.duration(), Duration::from_millis(129));
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_ignored() {
+        let text = "# recorded by hand\n\nskulpin_input_recording 1\nframe 1000\n\n    # a comment\n    pinch 2\n";
+        let recording = InputRecording::from_text(text).unwrap();
+        assert_eq!(recording.frames, vec![
+            RecordedFrame {
+                elapsed: Duration::from_nanos(1000),
+                events: vec![ScriptedInputEvent::PinchGesture(2.0)]
+            }
+        ]);
+    }
+
+    #[test]
+    fn invalid_text_is_rejected() {
+        // Missing header
+        assert!(InputRecording::from_text("frame 1000\n").is_err());
+
+        // Event before the first frame
+        assert!(InputRecording::from_text("skulpin_input_recording 1\npinch 2\n").is_err());
+
+        // Unknown event, wrong argument count, unknown touch phase
+        assert!(InputRecording::from_text("skulpin_input_recording 1\nframe 1\n    jump\n").is_err());
+        assert!(InputRecording::from_text("skulpin_input_recording 1\nframe 1\n    scroll_lines 1\n").is_err());
+        assert!(InputRecording::from_text("skulpin_input_recording 1\nframe 1\n    touch 0 Hovering 1 2\n").is_err());
+    }
+}
@@ -3,12 +3,23 @@
 pub use winit::event::VirtualKeyCode;
 pub use winit::event::MouseButton;
 pub use winit::event::ElementState;
+pub use winit::event::ModifiersState;
+pub use winit::event::MouseScrollDelta;
 pub use winit::dpi::LogicalSize;
 pub use winit::dpi::PhysicalSize;
 pub use winit::dpi::LogicalPosition;
 pub use winit::dpi::PhysicalPosition;
 
 use super::AppControl;
+use super::gamepad::GamepadState;
+use super::gamepad::GamepadId;
+use super::gamepad::GamepadButton;
+use super::gamepad::GamepadAxis;
+use super::gamepad::GamepadEvent;
+use super::touch::TouchTracker;
+use super::touch::TouchPoint;
+use super::touch::TouchPhase;
+use super::touch::TouchGesture;
 use winit::window::Window;
 
 impl InputState {
@@ -25,10 +36,41 @@ pub struct MouseDragState {
     pub accumulated_frame_delta: LogicalPosition
 }
 
+/// A key press or release, in the order it arrived
+#[derive(Copy, Clone, Debug)]
+pub struct KeyEvent {
+    pub key: VirtualKeyCode,
+    pub state: ElementState,
+
+    /// Modifiers held when the key was pressed or released
+    pub modifiers: ModifiersState,
+
+    /// True if this press was generated by the OS because the key was held down
+    pub is_repeat: bool
+}
+
+/// Input method editor events, used for composing text (i.e. CJK input). winit does not report
+/// these yet, so they are only present if fed in with `InputState::handle_ime_event`. On most
+/// platforms the OS still delivers the composed text as typed characters.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImeEvent {
+    /// Text being composed. The cursor is a byte range within the text, if known. Empty text
+    /// means composition was cancelled.
+    Preedit {
+        text: String,
+        cursor: Option<(usize, usize)>
+    },
+
+    /// Composition finished, the text should be inserted
+    Commit(String)
+}
+
 pub struct InputState {
     window_size: LogicalSize,
     dpi_factor: f64,
 
+    modifiers: ModifiersState,
+
     key_is_down: [bool; Self::KEYBOARD_BUTTON_COUNT],
     key_just_down: [bool; Self::KEYBOARD_BUTTON_COUNT],
     key_just_up: [bool; Self::KEYBOARD_BUTTON_COUNT],
@@ -45,13 +87,38 @@ pub struct InputState {
 
     mouse_drag_in_progress: [Option<MouseDragState>; Self::MOUSE_BUTTON_COUNT],
     mouse_drag_just_finished: [Option<MouseDragState>; Self::MOUSE_BUTTON_COUNT],
+
+    // Scroll wheel/trackpad movement and pinch zoom accumulated this frame
+    scroll_line_delta: LogicalPosition,
+    scroll_pixel_delta: LogicalPosition,
+    pinch_scale: f64,
+
+    touch_tracker: TouchTracker,
+
+    // Indexed by GamepadId, grows as gamepads are connected
+    gamepads: Vec<GamepadState>,
+
+    // Cleared at the end of every frame
+    key_events: Vec<KeyEvent>,
+    typed_characters: Vec<char>,
+    ime_events: Vec<ImeEvent>,
+
+    // Text currently being composed by an IME, kept until it is committed or cancelled
+    ime_preedit: Option<String>,
 }
 
 impl InputState {
     pub fn new(window: &Window) -> InputState {
+        Self::new_with_window_size(window.inner_size(), window.hidpi_factor())
+    }
+
+    /// Create input state that isn't tied to a real window. Useful for driving an app from
+    /// scripted input (i.e. tests)
+    pub fn new_with_window_size(window_size: LogicalSize, dpi_factor: f64) -> InputState {
         return InputState {
-            window_size: window.inner_size(),
-            dpi_factor: window.hidpi_factor(),
+            window_size,
+            dpi_factor,
+            modifiers: ModifiersState::default(),
             key_is_down: [false; Self::KEYBOARD_BUTTON_COUNT],
             key_just_down: [false; Self::KEYBOARD_BUTTON_COUNT],
             key_just_up: [false; Self::KEYBOARD_BUTTON_COUNT],
@@ -64,6 +131,15 @@ impl InputState {
             mouse_button_went_up_position: [None; Self::MOUSE_BUTTON_COUNT],
             mouse_drag_in_progress: [None; Self::MOUSE_BUTTON_COUNT],
             mouse_drag_just_finished: [None; Self::MOUSE_BUTTON_COUNT],
+            scroll_line_delta: LogicalPosition::new(0.0, 0.0),
+            scroll_pixel_delta: LogicalPosition::new(0.0, 0.0),
+            pinch_scale: 1.0,
+            touch_tracker: TouchTracker::default(),
+            gamepads: vec![],
+            key_events: vec![],
+            typed_characters: vec![],
+            ime_events: vec![],
+            ime_preedit: None,
         };
     }
 
@@ -78,6 +154,11 @@ impl InputState {
         self.dpi_factor
     }
 
+    /// Modifier keys (shift, ctrl, alt, logo) held as of the most recent keyboard or mouse event
+    pub fn modifiers(&self) -> ModifiersState {
+        self.modifiers
+    }
+
     pub fn is_key_down(&self, key: VirtualKeyCode) -> bool {
         if let Some(index) = Self::keyboard_button_to_index(key) {
             return self.key_is_down[index];
@@ -102,10 +183,149 @@ impl InputState {
         }
     }
 
+    /// Fingers touching the screen, plus any lifted this frame
+    pub fn touches(&self) -> &[TouchPoint] {
+        &self.touch_tracker.touches
+    }
+
+    pub fn touch(&self, id: u64) -> Option<&TouchPoint> {
+        self.touch_tracker.touches.iter().find(|touch| touch.id == id)
+    }
+
+    /// Taps, double taps, two-finger pans and pinches recognized this frame, in order
+    pub fn touch_gestures(&self) -> &[TouchGesture] {
+        &self.touch_tracker.gestures
+    }
+
+    /// Total two-finger pan movement this frame
+    pub fn two_finger_pan_delta(&self) -> LogicalPosition {
+        self.touch_tracker.gestures.iter().fold(LogicalPosition::new(0.0, 0.0), |total, gesture| {
+            match gesture {
+                TouchGesture::TwoFingerPan(delta) => Self::add(total, *delta),
+                _ => total
+            }
+        })
+    }
+
+    /// Gamepads that are currently connected
+    pub fn connected_gamepads(&self) -> Vec<GamepadId> {
+        self.gamepads.iter()
+            .enumerate()
+            .filter(|(_, gamepad)| gamepad.is_connected)
+            .map(|(index, _)| GamepadId(index))
+            .collect()
+    }
+
+    pub fn is_gamepad_connected(&self, gamepad: GamepadId) -> bool {
+        self.gamepad_state(gamepad).map(|x| x.is_connected).unwrap_or(false)
+    }
+
+    pub fn is_gamepad_just_connected(&self, gamepad: GamepadId) -> bool {
+        self.gamepad_state(gamepad).map(|x| x.just_connected).unwrap_or(false)
+    }
+
+    pub fn is_gamepad_just_disconnected(&self, gamepad: GamepadId) -> bool {
+        self.gamepad_state(gamepad).map(|x| x.just_disconnected).unwrap_or(false)
+    }
+
+    pub fn is_gamepad_button_down(&self, gamepad: GamepadId, button: GamepadButton) -> bool {
+        match (self.gamepad_state(gamepad), button.to_index()) {
+            (Some(state), Some(index)) => state.button_is_down[index],
+            _ => false
+        }
+    }
+
+    pub fn is_gamepad_button_just_down(&self, gamepad: GamepadId, button: GamepadButton) -> bool {
+        match (self.gamepad_state(gamepad), button.to_index()) {
+            (Some(state), Some(index)) => state.button_just_down[index],
+            _ => false
+        }
+    }
+
+    pub fn is_gamepad_button_just_up(&self, gamepad: GamepadId, button: GamepadButton) -> bool {
+        match (self.gamepad_state(gamepad), button.to_index()) {
+            (Some(state), Some(index)) => state.button_just_up[index],
+            _ => false
+        }
+    }
+
+    /// Current value of a stick or trigger. See `GamepadAxis` for ranges
+    pub fn gamepad_axis(&self, gamepad: GamepadId, axis: GamepadAxis) -> f32 {
+        match (self.gamepad_state(gamepad), axis.to_index()) {
+            (Some(state), Some(index)) => state.axes[index],
+            _ => 0.0
+        }
+    }
+
+    /// True if the button is down on any connected gamepad
+    pub fn is_any_gamepad_button_down(&self, button: GamepadButton) -> bool {
+        self.connected_gamepads().into_iter().any(|gamepad| self.is_gamepad_button_down(gamepad, button))
+    }
+
+    /// True if the button was just pressed on any connected gamepad
+    pub fn is_any_gamepad_button_just_down(&self, button: GamepadButton) -> bool {
+        self.connected_gamepads().into_iter().any(|gamepad| self.is_gamepad_button_just_down(gamepad, button))
+    }
+
+    /// Key presses and releases this frame, in order, including key repeats. Useful for text
+    /// editing where `is_key_just_down` would miss repeats and multiple presses per frame
+    pub fn key_events(&self) -> &[KeyEvent] {
+        &self.key_events
+    }
+
+    /// Every character received this frame, including control characters like backspace
+    pub fn typed_characters(&self) -> &[char] {
+        &self.typed_characters
+    }
+
+    /// Text typed this frame with control characters removed. Handle editing keys (backspace,
+    /// enter, arrows) with `key_events`
+    pub fn text_input(&self) -> String {
+        self.typed_characters.iter().filter(|c| !c.is_control()).collect()
+    }
+
+    /// IME events received this frame
+    pub fn ime_events(&self) -> &[ImeEvent] {
+        &self.ime_events
+    }
+
+    /// Text currently being composed by an IME, which should be drawn at the cursor but not
+    /// inserted yet
+    pub fn ime_preedit(&self) -> Option<&str> {
+        self.ime_preedit.as_ref().map(|x| x.as_str())
+    }
+
     pub fn mouse_position(&self) -> LogicalPosition {
         return self.mouse_position;
     }
 
+    /// Scrolling this frame in lines, as reported by mouse wheels. Positive y is scrolling up
+    /// (away from the user), positive x is scrolling right
+    pub fn scroll_line_delta(&self) -> LogicalPosition {
+        self.scroll_line_delta
+    }
+
+    /// Scrolling this frame in logical pixels, as reported by trackpads and other precise scrolling
+    /// devices. Uses the same direction as `scroll_line_delta`
+    pub fn scroll_pixel_delta(&self) -> LogicalPosition {
+        self.scroll_pixel_delta
+    }
+
+    /// All scrolling this frame in logical pixels, converting lines with the given line height
+    pub fn scroll_delta(&self, line_height: f64) -> LogicalPosition {
+        LogicalPosition::new(
+            self.scroll_pixel_delta.x + self.scroll_line_delta.x * line_height,
+            self.scroll_pixel_delta.y + self.scroll_line_delta.y * line_height
+        )
+    }
+
+    /// How much a pinch gesture zoomed this frame, as a multiplier (1.0 means no change, greater
+    /// than 1.0 means zooming in). Includes two-finger touchscreen pinches. winit does not report
+    /// trackpad pinch gestures, so those are only included if fed in with `handle_pinch_gesture`
+    pub fn pinch_scale(&self) -> f64 {
+        self.pinch_scale
+    }
+
     pub fn is_mouse_down(&self, mouse_button: MouseButton) -> bool {
         if let Some(index) = Self::mouse_button_to_index(mouse_button) {
             return self.mouse_button_is_down[index];
@@ -246,6 +466,20 @@ impl InputState {
                 v.previous_frame_delta = LogicalPosition::new(0.0, 0.0);
             }
         }
+
+        for gamepad in self.gamepads.iter_mut() {
+            gamepad.end_frame();
+        }
+
+        self.touch_tracker.end_frame();
+
+        self.scroll_line_delta = LogicalPosition::new(0.0, 0.0);
+        self.scroll_pixel_delta = LogicalPosition::new(0.0, 0.0);
+        self.pinch_scale = 1.0;
+
+        self.key_events.clear();
+        self.typed_characters.clear();
+        self.ime_events.clear();
     }
 
     pub fn handle_hidpi_factor_changed(&mut self, dpi_factor: f64) {
@@ -256,11 +490,112 @@ impl InputState {
         self.window_size = window_size;
     }
 
+    pub fn handle_modifiers_changed(&mut self, modifiers: ModifiersState) {
+        self.modifiers = modifiers;
+    }
+
+    pub fn handle_mouse_wheel_event(&mut self, delta: MouseScrollDelta) {
+        match delta {
+            MouseScrollDelta::LineDelta(x, y) => {
+                self.scroll_line_delta = Self::add(self.scroll_line_delta, LogicalPosition::new(x as f64, y as f64));
+            },
+            MouseScrollDelta::PixelDelta(delta) => {
+                self.scroll_pixel_delta = Self::add(self.scroll_pixel_delta, delta);
+            }
+        }
+    }
+
+    /// Apply a pinch gesture that scaled by the given multiplier
+    pub fn handle_pinch_gesture(&mut self, scale: f64) {
+        self.pinch_scale *= scale;
+    }
+
+    /// Advance the clock used to time touch gestures (taps and double taps). `App` calls this with
+    /// the wall-clock time that passed before each batch of events arrives, `AppTestHarness` and
+    /// `InputReplay` once per update.
+    pub fn advance_time(&mut self, elapsed: std::time::Duration) {
+        self.touch_tracker.advance_time(elapsed);
+    }
+
+    pub fn handle_touch_event(&mut self, id: u64, phase: TouchPhase, position: LogicalPosition) {
+        let first_new_gesture = self.touch_tracker.gestures.len();
+        self.touch_tracker.handle_touch_event(id, phase, position);
+
+        for gesture in &self.touch_tracker.gestures[first_new_gesture..] {
+            if let TouchGesture::Pinch { scale, .. } = gesture {
+                self.pinch_scale *= scale;
+            }
+        }
+    }
+
+    pub fn handle_gamepad_event(&mut self, event: GamepadEvent) {
+        match event {
+            GamepadEvent::Connected(gamepad) => {
+                let state = self.gamepad_state_mut(gamepad);
+                *state = GamepadState::default();
+                state.is_connected = true;
+                state.just_connected = true;
+            },
+            GamepadEvent::Disconnected(gamepad) => {
+                // Clear buttons and axes so nothing stays held down
+                let state = self.gamepad_state_mut(gamepad);
+                *state = GamepadState::default();
+                state.just_disconnected = true;
+            },
+            GamepadEvent::Button(gamepad, button, button_state) => {
+                if let Some(index) = button.to_index() {
+                    let state = self.gamepad_state_mut(gamepad);
+                    if button_state == ElementState::Pressed {
+                        if !state.button_is_down[index] {
+                            state.button_just_down[index] = true;
+                        }
+                        state.button_is_down[index] = true;
+                    } else {
+                        if state.button_is_down[index] {
+                            state.button_just_up[index] = true;
+                        }
+                        state.button_is_down[index] = false;
+                    }
+                }
+            },
+            GamepadEvent::Axis(gamepad, axis, value) => {
+                if let Some(index) = axis.to_index() {
+                    self.gamepad_state_mut(gamepad).axes[index] = value;
+                }
+            }
+        }
+    }
+
+    pub fn handle_received_character(&mut self, c: char) {
+        self.typed_characters.push(c);
+    }
+
+    pub fn handle_ime_event(&mut self, ime_event: ImeEvent) {
+        match &ime_event {
+            ImeEvent::Preedit { text, .. } => {
+                self.ime_preedit = if text.is_empty() { None } else { Some(text.clone()) };
+            },
+            ImeEvent::Commit(text) => {
+                self.ime_preedit = None;
+                self.typed_characters.extend(text.chars());
+            }
+        }
+
+        self.ime_events.push(ime_event);
+    }
+
     pub fn handle_keyboard_event(
         &mut self,
         keyboard_button: VirtualKeyCode,
         button_state: ElementState
     ) {
+        self.key_events.push(KeyEvent {
+            key: keyboard_button,
+            state: button_state,
+            modifiers: self.modifiers,
+            is_repeat: button_state == ElementState::Pressed && self.is_key_down(keyboard_button)
+        });
+
         if let Some(kc) = Self::keyboard_button_to_index(keyboard_button) {
             // Assign true if key is down, or false if key is up
             if button_state == ElementState::Pressed {
@@ -411,6 +746,7 @@ impl InputState {
                 ..
             } => {
                 trace!("keyboard input {:?}", input);
+                self.handle_modifiers_changed(input.modifiers);
                 if let Some(vk) = input.virtual_keycode {
                     self.handle_keyboard_event(vk, input.state);
                 }
@@ -434,6 +770,7 @@ impl InputState {
                     modifiers
                 );
 
+                self.handle_modifiers_changed(*modifiers);
                 self.handle_mouse_button_event(*button, *state);
             }
 
@@ -447,9 +784,41 @@ impl InputState {
                 ..
             } => {
                 trace!("mouse move input {:?} {:?} {:?}", device_id, position, modifiers);
+                self.handle_modifiers_changed(*modifiers);
                 self.handle_mouse_move_event(*position);
             }
 
+            Event::WindowEvent {
+                event:
+                WindowEvent::MouseWheel {
+                    device_id,
+                    delta,
+                    phase,
+                    modifiers,
+                },
+                ..
+            } => {
+                trace!("mouse wheel input {:?} {:?} {:?} {:?}", device_id, delta, phase, modifiers);
+                self.handle_modifiers_changed(*modifiers);
+                self.handle_mouse_wheel_event(*delta);
+            }
+
+            Event::WindowEvent {
+                event: WindowEvent::Touch(touch),
+                ..
+            } => {
+                trace!("touch input {:?}", touch);
+                self.handle_touch_event(touch.id, touch.phase, touch.location);
+            }
+
+            Event::WindowEvent {
+                event: WindowEvent::ReceivedCharacter(c),
+                ..
+            } => {
+                trace!("received character {:?}", c);
+                self.handle_received_character(*c);
+            }
+
             // Ignore any other events
             _ => (),
         }
@@ -487,6 +856,18 @@ impl InputState {
         }
     }
 
+    fn gamepad_state(&self, gamepad: GamepadId) -> Option<&GamepadState> {
+        self.gamepads.get(gamepad.0)
+    }
+
+    fn gamepad_state_mut(&mut self, gamepad: GamepadId) -> &mut GamepadState {
+        if gamepad.0 >= self.gamepads.len() {
+            self.gamepads.resize(gamepad.0 + 1, GamepadState::default());
+        }
+
+        &mut self.gamepads[gamepad.0]
+    }
+
     fn add(p0: LogicalPosition, p1: LogicalPosition) -> LogicalPosition {
         return LogicalPosition::new(
             p0.x + p1.x,
@@ -507,4 +888,146 @@ impl InputState {
 
         ((x_diff * x_diff) + (y_diff * y_diff)).sqrt()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_input_state() -> InputState {
+        InputState::new_with_window_size(LogicalSize::new(800.0, 600.0), 1.0)
+    }
+
+    #[test]
+    fn typed_characters_accumulate_until_end_frame() {
+        let mut input_state = new_input_state();
+        for c in "hi!".chars() {
+            input_state.handle_received_character(c);
+        }
+
+        assert_eq!(input_state.typed_characters(), &['h', 'i', '!']);
+        assert_eq!(input_state.text_input(), "hi!");
+
+        input_state.end_frame();
+        assert!(input_state.typed_characters().is_empty());
+        assert_eq!(input_state.text_input(), "");
+    }
+
+    #[test]
+    fn text_input_filters_control_characters() {
+        let mut input_state = new_input_state();
+        for c in &['a', '\u{8}', 'b', '\r', '\t', '\u{7f}', '\u{e9}'] {
+            input_state.handle_received_character(*c);
+        }
+
+        // Control characters are still available for editing
+        assert_eq!(input_state.typed_characters().len(), 7);
+        assert_eq!(input_state.text_input(), "ab\u{e9}");
+    }
+
+    #[test]
+    fn ime_preedit_is_replaced_by_commit() {
+        let mut input_state = new_input_state();
+        input_state.handle_ime_event(ImeEvent::Preedit { text: "\u{306b}".to_string(), cursor: Some((0, 3)) });
+        assert_eq!(input_state.ime_preedit(), Some("\u{306b}"));
+        assert_eq!(input_state.text_input(), "");
+
+        input_state.handle_ime_event(ImeEvent::Preedit { text: "\u{306b}\u{307b}".to_string(), cursor: None });
+        assert_eq!(input_state.ime_preedit(), Some("\u{306b}\u{307b}"));
+
+        input_state.handle_ime_event(ImeEvent::Commit("\u{65e5}\u{672c}".to_string()));
+        assert_eq!(input_state.ime_preedit(), None);
+        assert_eq!(input_state.text_input(), "\u{65e5}\u{672c}");
+        assert_eq!(input_state.ime_events().len(), 3);
+
+        // The events are cleared each frame, but not the composition in progress
+        input_state.handle_ime_event(ImeEvent::Preedit { text: "x".to_string(), cursor: None });
+        input_state.end_frame();
+        assert!(input_state.ime_events().is_empty());
+        assert_eq!(input_state.ime_preedit(), Some("x"));
+
+        // Empty preedit text cancels composition
+        input_state.handle_ime_event(ImeEvent::Preedit { text: String::new(), cursor: None });
+        assert_eq!(input_state.ime_preedit(), None);
+    }
+
+    #[test]
+    fn repeated_presses_are_flagged() {
+        let mut input_state = new_input_state();
+        let is_repeat = |input_state: &InputState| -> Vec<bool> {
+            input_state.key_events().iter().map(|x| x.is_repeat).collect()
+        };
+
+        // A second press without a release is a repeat
+        input_state.handle_keyboard_event(VirtualKeyCode::A, ElementState::Pressed);
+        input_state.handle_keyboard_event(VirtualKeyCode::A, ElementState::Pressed);
+        assert_eq!(is_repeat(&input_state), vec![false, true]);
+
+        // Still held in the next frame, then released and pressed again
+        input_state.end_frame();
+        input_state.handle_keyboard_event(VirtualKeyCode::A, ElementState::Pressed);
+        input_state.handle_keyboard_event(VirtualKeyCode::A, ElementState::Released);
+        input_state.handle_keyboard_event(VirtualKeyCode::A, ElementState::Pressed);
+        assert_eq!(is_repeat(&input_state), vec![true, false, false]);
+
+        // Other keys don't count
+        input_state.handle_keyboard_event(VirtualKeyCode::B, ElementState::Pressed);
+        assert!(!input_state.key_events()[3].is_repeat);
+
+        input_state.end_frame();
+        assert!(input_state.key_events().is_empty());
+    }
+
+    #[test]
+    fn key_events_carry_modifiers() {
+        let mut input_state = new_input_state();
+        let ctrl = ModifiersState { ctrl: true, ..Default::default() };
+
+        input_state.handle_modifiers_changed(ctrl);
+        input_state.handle_keyboard_event(VirtualKeyCode::C, ElementState::Pressed);
+        input_state.handle_modifiers_changed(ModifiersState::default());
+        input_state.handle_keyboard_event(VirtualKeyCode::C, ElementState::Released);
+
+        let key_events = input_state.key_events();
+        assert_eq!(key_events.len(), 2);
+        assert_eq!((key_events[0].key, key_events[0].state, key_events[0].modifiers), (VirtualKeyCode::C, ElementState::Pressed, ctrl));
+        assert!(!key_events[0].is_repeat);
+        assert_eq!(key_events[1].modifiers, ModifiersState::default());
+    }
+
+    #[test]
+    fn scroll_lines_and_pixels_accumulate() {
+        let mut input_state = new_input_state();
+        input_state.handle_mouse_wheel_event(MouseScrollDelta::LineDelta(0.0, 1.0));
+        input_state.handle_mouse_wheel_event(MouseScrollDelta::LineDelta(-1.0, 2.0));
+        input_state.handle_mouse_wheel_event(MouseScrollDelta::PixelDelta(LogicalPosition::new(3.0, -4.0)));
+        input_state.handle_mouse_wheel_event(MouseScrollDelta::PixelDelta(LogicalPosition::new(1.5, 0.5)));
+
+        assert_eq!(input_state.scroll_line_delta(), LogicalPosition::new(-1.0, 3.0));
+        assert_eq!(input_state.scroll_pixel_delta(), LogicalPosition::new(4.5, -3.5));
+
+        // Lines are converted with the line height and added to the pixels
+        assert_eq!(input_state.scroll_delta(20.0), LogicalPosition::new(-15.5, 56.5));
+        assert_eq!(input_state.scroll_delta(0.0), LogicalPosition::new(4.5, -3.5));
+
+        input_state.end_frame();
+        assert_eq!(input_state.scroll_line_delta(), LogicalPosition::new(0.0, 0.0));
+        assert_eq!(input_state.scroll_pixel_delta(), LogicalPosition::new(0.0, 0.0));
+        assert_eq!(input_state.scroll_delta(20.0), LogicalPosition::new(0.0, 0.0));
+    }
+
+    #[test]
+    fn pinch_scale_multiplies_until_end_frame() {
+        let mut input_state = new_input_state();
+        assert_eq!(input_state.pinch_scale(), 1.0);
+
+        input_state.handle_pinch_gesture(2.0);
+        input_state.handle_pinch_gesture(1.5);
+        assert_eq!(input_state.pinch_scale(), 3.0);
+
+        input_state.handle_pinch_gesture(0.25);
+        assert_eq!(input_state.pinch_scale(), 0.75);
+
+        input_state.end_frame();
+        assert_eq!(input_state.pinch_scale(), 1.0);
+    }
+}
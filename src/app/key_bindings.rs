@@ -0,0 +1,323 @@
+use std::path::Path;
+
+use super::input_state::InputState;
+use super::input_state::VirtualKeyCode;
+use super::input_state::ModifiersState;
+use super::input_state::ElementState;
+use super::key_names;
+
+/// A key and the exact set of modifiers that must be held with it, i.e. Ctrl+Shift+Z
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct KeyChord {
+    pub key: VirtualKeyCode,
+    pub modifiers: ModifiersState
+}
+
+impl KeyChord {
+    pub fn new(key: VirtualKeyCode, modifiers: ModifiersState) -> Self {
+        KeyChord {
+            key,
+            modifiers
+        }
+    }
+
+    /// Parse a chord like "Ctrl+Shift+Z" or "Alt+F4". Modifiers are case insensitive and may be
+    /// Ctrl/Control, Shift, Alt/Option, or Logo/Super/Cmd/Win. The key is the name of a
+    /// `VirtualKeyCode` variant, single letters and digits may also be written as "z" or "1".
+    pub fn parse(chord: &str) -> Result<Self, String> {
+        let parts : Vec<&str> = chord.split('+').map(|x| x.trim()).collect();
+        let (key_name, modifier_names) = parts.split_last()
+            .ok_or_else(|| format!("empty key chord {:?}", chord))?;
+
+        let mut modifiers = ModifiersState::default();
+        for modifier_name in modifier_names {
+            match modifier_name.to_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "shift" => modifiers.shift = true,
+                "alt" | "option" => modifiers.alt = true,
+                "logo" | "super" | "cmd" | "win" => modifiers.logo = true,
+                _ => return Err(format!("unknown modifier {:?} in key chord {:?}", modifier_name, chord))
+            }
+        }
+
+        let key = parse_key_name(key_name)
+            .ok_or_else(|| format!("unknown key {:?} in key chord {:?}", key_name, chord))?;
+
+        Ok(KeyChord::new(key, modifiers))
+    }
+
+    /// True if the key was pressed this frame while exactly these modifiers were held. Key repeats
+    /// are ignored.
+    pub fn is_just_pressed(&self, input_state: &InputState) -> bool {
+        self.find_press(input_state, false)
+    }
+
+    /// Like `is_just_pressed`, but also true when the OS repeats the key because it is held down
+    pub fn is_just_pressed_or_repeated(&self, input_state: &InputState) -> bool {
+        self.find_press(input_state, true)
+    }
+
+    fn find_press(&self, input_state: &InputState, include_repeats: bool) -> bool {
+        input_state.key_events().iter().any(|key_event| {
+            key_event.key == self.key &&
+                key_event.state == ElementState::Pressed &&
+                key_event.modifiers == self.modifiers &&
+                (include_repeats || !key_event.is_repeat)
+        })
+    }
+}
+
+impl std::fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.modifiers.ctrl { write!(f, "Ctrl+")?; }
+        if self.modifiers.shift { write!(f, "Shift+")?; }
+        if self.modifiers.alt { write!(f, "Alt+")?; }
+        if self.modifiers.logo { write!(f, "Logo+")?; }
+        write!(f, "{}", key_names::virtual_key_code_name(self.key))
+    }
+}
+
+impl std::str::FromStr for KeyChord {
+    type Err = String;
+
+    fn from_str(chord: &str) -> Result<Self, Self::Err> {
+        KeyChord::parse(chord)
+    }
+}
+
+/// Maps command names to the key chords that trigger them. A command may have several chords, and
+/// the same chord may trigger several commands.
+///
+/// Bindings can be saved to and loaded from a text file with one command per line:
+///
+/// ```text
+/// # Comments start with #
+/// undo = Ctrl+Z
+/// redo = Ctrl+Shift+Z, Ctrl+Y
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct KeyBindings {
+    bindings: Vec<(String, KeyChord)>
+}
+
+impl KeyBindings {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a chord for the command, keeping any chords already bound to it
+    pub fn bind(&mut self, command: &str, chord: KeyChord) {
+        if !self.bindings.iter().any(|(c, k)| c == command && *k == chord) {
+            self.bindings.push((command.to_string(), chord));
+        }
+    }
+
+    /// Remove all chords for the command
+    pub fn unbind(&mut self, command: &str) {
+        self.bindings.retain(|(c, _)| c != command);
+    }
+
+    /// Replace the chords of every command in `other`. Useful for applying user overrides on top of
+    /// default bindings.
+    pub fn apply_overrides(&mut self, other: &KeyBindings) {
+        for (command, _) in &other.bindings {
+            self.unbind(command);
+        }
+
+        self.bindings.extend(other.bindings.iter().cloned());
+    }
+
+    pub fn chords<'a>(&'a self, command: &'a str) -> impl Iterator<Item = &'a KeyChord> + 'a {
+        self.bindings.iter()
+            .filter(move |(c, _)| c.as_str() == command)
+            .map(|(_, chord)| chord)
+    }
+
+    /// True if any chord bound to the command was just pressed
+    pub fn is_just_pressed(&self, command: &str, input_state: &InputState) -> bool {
+        self.chords(command).any(|chord| chord.is_just_pressed(input_state))
+    }
+
+    /// Every command with a chord that was just pressed, in the order they were bound
+    pub fn just_pressed_commands(&self, input_state: &InputState) -> Vec<&str> {
+        let mut commands : Vec<&str> = vec![];
+        for (command, chord) in &self.bindings {
+            if chord.is_just_pressed(input_state) && !commands.contains(&command.as_str()) {
+                commands.push(command);
+            }
+        }
+
+        commands
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut commands : Vec<&str> = vec![];
+        for (command, _) in &self.bindings {
+            if !commands.contains(&command.as_str()) {
+                commands.push(command);
+            }
+        }
+
+        let mut text = String::new();
+        for command in commands {
+            let chords : Vec<String> = self.chords(command).map(|x| x.to_string()).collect();
+            text.push_str(&format!("{} = {}\n", command, chords.join(", ")));
+        }
+
+        text
+    }
+
+    pub fn from_text(text: &str) -> std::io::Result<Self> {
+        let mut key_bindings = KeyBindings::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let result = match line.find('=') {
+                Some(separator) => {
+                    let command = line[..separator].trim();
+                    line[separator + 1..].split(',')
+                        .map(|chord| KeyChord::parse(chord))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map(|chords| {
+                            for chord in chords {
+                                key_bindings.bind(command, chord);
+                            }
+                        })
+                },
+                None => Err("expected <command> = <chord>".to_string())
+            };
+
+            if let Err(e) = result {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Key bindings line {}: {}", index + 1, e)
+                ));
+            }
+        }
+
+        Ok(key_bindings)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, self.to_text())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::from_text(&std::fs::read_to_string(path)?)
+    }
+}
+
+fn parse_key_name(name: &str) -> Option<VirtualKeyCode> {
+    if let Some(key) = key_names::virtual_key_code_from_name(name) {
+        return Some(key);
+    }
+
+    // Allow "z" for Z and "1" for Key1
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => key_names::virtual_key_code_from_name(&c.to_ascii_uppercase().to_string()),
+        (Some(c), None) if c.is_ascii_digit() => key_names::virtual_key_code_from_name(&format!("Key{}", c)),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::input_state::LogicalSize;
+
+    fn modifiers(ctrl: bool, shift: bool, alt: bool, logo: bool) -> ModifiersState {
+        ModifiersState { shift, ctrl, alt, logo }
+    }
+
+    #[test]
+    fn parse_modifier_aliases() {
+        let ctrl_shift = KeyChord::new(VirtualKeyCode::Z, modifiers(true, true, false, false));
+        assert_eq!(KeyChord::parse("Ctrl+Shift+Z"), Ok(ctrl_shift));
+        assert_eq!(KeyChord::parse("control + SHIFT + Z"), Ok(ctrl_shift));
+        assert_eq!(KeyChord::parse("Shift+Ctrl+Z"), Ok(ctrl_shift));
+
+        let alt = KeyChord::new(VirtualKeyCode::F4, modifiers(false, false, true, false));
+        assert_eq!(KeyChord::parse("Alt+F4"), Ok(alt));
+        assert_eq!(KeyChord::parse("option+F4"), Ok(alt));
+
+        let logo = KeyChord::new(VirtualKeyCode::Q, modifiers(false, false, false, true));
+        for chord in &["Logo+Q", "Super+Q", "Cmd+Q", "win+Q"] {
+            assert_eq!(KeyChord::parse(chord), Ok(logo));
+        }
+    }
+
+    #[test]
+    fn parse_single_character_keys() {
+        assert_eq!(KeyChord::parse("z").unwrap().key, VirtualKeyCode::Z);
+        assert_eq!(KeyChord::parse("Ctrl+1").unwrap().key, VirtualKeyCode::Key1);
+        assert_eq!(KeyChord::parse("Key1").unwrap().key, VirtualKeyCode::Key1);
+        assert_eq!(KeyChord::parse("Escape").unwrap(), KeyChord::new(VirtualKeyCode::Escape, ModifiersState::default()));
+    }
+
+    #[test]
+    fn parse_unknown_names() {
+        assert!(KeyChord::parse("").is_err());
+        assert!(KeyChord::parse("Ctrl+").is_err());
+        assert!(KeyChord::parse("Hyper+Z").is_err());
+        assert!(KeyChord::parse("Ctrl+NotAKey").is_err());
+        assert!(KeyChord::parse("escape").is_err());
+        assert!(KeyChord::parse("!").is_err());
+    }
+
+    #[test]
+    fn from_text_skips_comments_and_blank_lines() {
+        let text = "# Editing\n\nundo = Ctrl+Z\n   \n  # Redo has two chords\nredo = Ctrl+Shift+Z, Ctrl+Y\n";
+        let key_bindings = KeyBindings::from_text(text).unwrap();
+
+        assert_eq!(key_bindings.chords("undo").cloned().collect::<Vec<_>>(), vec![KeyChord::parse("Ctrl+Z").unwrap()]);
+        assert_eq!(
+            key_bindings.chords("redo").cloned().collect::<Vec<_>>(),
+            vec![KeyChord::parse("Ctrl+Shift+Z").unwrap(), KeyChord::parse("Ctrl+Y").unwrap()]
+        );
+        assert_eq!(key_bindings.chords("missing").count(), 0);
+
+        assert!(KeyBindings::from_text("undo Ctrl+Z\n").is_err());
+        assert!(KeyBindings::from_text("undo = Ctrl+Nope\n").is_err());
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let mut key_bindings = KeyBindings::new();
+        key_bindings.bind("undo", KeyChord::parse("Ctrl+Z").unwrap());
+        key_bindings.bind("redo", KeyChord::parse("Ctrl+Shift+Z").unwrap());
+        key_bindings.bind("redo", KeyChord::parse("Ctrl+Y").unwrap());
+        key_bindings.bind("quit", KeyChord::parse("Logo+Alt+F4").unwrap());
+
+        let text = key_bindings.to_text();
+        assert_eq!(text, "undo = Ctrl+Z\nredo = Ctrl+Shift+Z, Ctrl+Y\nquit = Alt+Logo+F4\n");
+        assert_eq!(KeyBindings::from_text(&text).unwrap().to_text(), text);
+    }
+
+    #[test]
+    fn overrides_replace_all_chords_of_a_command() {
+        let mut key_bindings = KeyBindings::from_text("undo = Ctrl+Z\nredo = Ctrl+Shift+Z, Ctrl+Y\n").unwrap();
+        key_bindings.apply_overrides(&KeyBindings::from_text("redo = Ctrl+R\n").unwrap());
+        assert_eq!(key_bindings.to_text(), "undo = Ctrl+Z\nredo = Ctrl+R\n");
+    }
+
+    #[test]
+    fn just_pressed_requires_exact_modifiers() {
+        let key_bindings = KeyBindings::from_text("undo = Ctrl+Z\nredo = Ctrl+Shift+Z\n").unwrap();
+        let mut input_state = InputState::new_with_window_size(LogicalSize::new(800.0, 600.0), 1.0);
+
+        input_state.handle_modifiers_changed(modifiers(true, true, false, false));
+        input_state.handle_keyboard_event(VirtualKeyCode::Z, ElementState::Pressed);
+
+        assert!(!key_bindings.is_just_pressed("undo", &input_state));
+        assert!(key_bindings.is_just_pressed("redo", &input_state));
+        assert_eq!(key_bindings.just_pressed_commands(&input_state), vec!["redo"]);
+
+        input_state.end_frame();
+        assert!(key_bindings.just_pressed_commands(&input_state).is_empty());
+    }
+}
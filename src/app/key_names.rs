@@ -0,0 +1,206 @@
+use super::input_state::VirtualKeyCode;
+use super::input_state::MouseButton;
+
+// Generates a lookup from the name of a VirtualKeyCode variant (as printed with {:?}) to the variant
+macro_rules! virtual_key_codes_by_name {
+    ($($name:ident,)*) => {
+        /// Look up a key by the name of its `VirtualKeyCode` variant (i.e. "A", "Escape", "F1").
+        /// This is the same as the name printed with `{:?}`
+        pub fn virtual_key_code_from_name(name: &str) -> Option<VirtualKeyCode> {
+            match name {
+                $(stringify!($name) => Some(VirtualKeyCode::$name),)*
+                _ => None
+            }
+        }
+    }
+}
+
+virtual_key_codes_by_name!(
+    Key1,
+    Key2,
+    Key3,
+    Key4,
+    Key5,
+    Key6,
+    Key7,
+    Key8,
+    Key9,
+    Key0,
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    Escape,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    F13,
+    F14,
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
+    Snapshot,
+    Scroll,
+    Pause,
+    Insert,
+    Home,
+    Delete,
+    End,
+    PageDown,
+    PageUp,
+    Left,
+    Up,
+    Right,
+    Down,
+    Back,
+    Return,
+    Space,
+    Compose,
+    Caret,
+    Numlock,
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    AbntC1,
+    AbntC2,
+    Add,
+    Apostrophe,
+    Apps,
+    At,
+    Ax,
+    Backslash,
+    Calculator,
+    Capital,
+    Colon,
+    Comma,
+    Convert,
+    Decimal,
+    Divide,
+    Equals,
+    Grave,
+    Kana,
+    Kanji,
+    LAlt,
+    LBracket,
+    LControl,
+    LShift,
+    LWin,
+    Mail,
+    MediaSelect,
+    MediaStop,
+    Minus,
+    Multiply,
+    Mute,
+    MyComputer,
+    NavigateForward,
+    NavigateBackward,
+    NextTrack,
+    NoConvert,
+    NumpadComma,
+    NumpadEnter,
+    NumpadEquals,
+    OEM102,
+    Period,
+    PlayPause,
+    Power,
+    PrevTrack,
+    RAlt,
+    RBracket,
+    RControl,
+    RShift,
+    RWin,
+    Semicolon,
+    Slash,
+    Sleep,
+    Stop,
+    Subtract,
+    Sysrq,
+    Tab,
+    Underline,
+    Unlabeled,
+    VolumeDown,
+    VolumeUp,
+    Wake,
+    WebBack,
+    WebFavorites,
+    WebForward,
+    WebHome,
+    WebRefresh,
+    WebSearch,
+    WebStop,
+    Yen,
+    Copy,
+    Paste,
+    Cut,
+);
+
+/// The name of a key's `VirtualKeyCode` variant. Can be converted back with
+/// `virtual_key_code_from_name`
+pub fn virtual_key_code_name(key: VirtualKeyCode) -> String {
+    format!("{:?}", key)
+}
+
+/// Names a mouse button as "Left", "Right", "Middle", or "Other(n)"
+pub fn mouse_button_name(button: MouseButton) -> String {
+    format!("{:?}", button)
+}
+
+pub fn mouse_button_from_name(name: &str) -> Option<MouseButton> {
+    match name {
+        "Left" => Some(MouseButton::Left),
+        "Right" => Some(MouseButton::Right),
+        "Middle" => Some(MouseButton::Middle),
+        _ => {
+            if name.starts_with("Other(") && name.ends_with(')') {
+                name["Other(".len()..name.len() - 1].parse().ok().map(MouseButton::Other)
+            } else {
+                None
+            }
+        }
+    }
+}
@@ -2,28 +2,86 @@ mod app;
 pub use app::App;
 pub use app::AppHandler;
 pub use app::AppBuilder;
+pub use app::RedrawMode;
+pub use app::FixedTimestep;
 
 mod app_control;
 pub use app_control::AppControl;
 
+mod app_window;
+pub use app_window::AppWindowId;
+pub use app_window::AppWindowOptions;
+pub use app_window::FullscreenMode;
+pub use app_window::Icon;
+pub use app_window::CursorIcon;
+
 mod input_state;
 pub use input_state::InputState;
 pub use input_state::MouseDragState;
+pub use input_state::KeyEvent;
+pub use input_state::ImeEvent;
 
 // These are re-exported winit types
 pub use input_state::VirtualKeyCode;
 pub use input_state::MouseButton;
 pub use input_state::ElementState;
+pub use input_state::ModifiersState;
+pub use input_state::MouseScrollDelta;
 pub use input_state::LogicalSize;
 pub use input_state::PhysicalSize;
 pub use input_state::LogicalPosition;
 pub use input_state::PhysicalPosition;
 
+mod gamepad;
+pub use gamepad::GamepadId;
+pub use gamepad::GamepadButton;
+pub use gamepad::GamepadAxis;
+pub use gamepad::GamepadEvent;
+pub use gamepad::GamepadBackend;
+pub use gamepad::MockGamepadBackend;
+pub use gamepad::NullGamepadBackend;
+pub use gamepad::default_gamepad_backend;
+#[cfg(target_os = "linux")]
+pub use gamepad::LinuxJoystickBackend;
+
+mod touch;
+pub use touch::TouchPoint;
+pub use touch::TouchPhase;
+pub use touch::TouchGesture;
+
+mod frame_time_history;
+pub use frame_time_history::FrameTimeHistory;
+pub use frame_time_history::FrameTimeStats;
+
+mod perf_overlay;
+pub use perf_overlay::PerfOverlay;
+
 mod time_state;
 pub use time_state::TimeState;
 pub use time_state::TimeContext;
+pub use time_state::ModeTimeState;
+
+mod key_names;
+
+mod key_bindings;
+pub use key_bindings::KeyChord;
+pub use key_bindings::KeyBindings;
+
+mod input_recording;
+pub use input_recording::ScriptedInputEvent;
+pub use input_recording::RecordedFrame;
+pub use input_recording::InputRecording;
+pub use input_recording::InputRecorder;
+pub use input_recording::InputReplay;
+
+mod app_test_harness;
+pub use app_test_harness::AppTestHarness;
+pub use app_test_harness::TestHarnessBackend;
+pub use app_test_harness::TestHarnessError;
+pub use app_test_harness::FrameComparison;
+pub use app_test_harness::FrameMismatch;
 
 mod util;
 pub use util::PeriodicEvent;
-pub use util::ScopeTimer;
+pub use crate::profiler::ScopeTimer;
 
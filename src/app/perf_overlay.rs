@@ -0,0 +1,224 @@
+use std::time;
+
+use super::input_state::InputState;
+use super::input_state::VirtualKeyCode;
+use super::time_state::TimeState;
+use crate::GpuTimings;
+
+// Number of frames shown in the frame time graph
+const PERF_OVERLAY_FRAME_COUNT : usize = 120;
+
+// Frame times at or above this fill the full height of the graph
+const PERF_OVERLAY_GRAPH_MAX_MS : f32 = 50.0;
+
+const PERF_OVERLAY_WIDTH : f32 = 360.0;
+const PERF_OVERLAY_GRAPH_HEIGHT : f32 = 60.0;
+const PERF_OVERLAY_LINE_HEIGHT : f32 = 16.0;
+const PERF_OVERLAY_MARGIN : f32 = 8.0;
+
+// Weight of the previous average when a frame's timings are added, same as ModeTimeState::fps_smoothed
+const PERF_OVERLAY_SMOOTHING_FACTOR : f32 = 0.95;
+
+// Smoothed breakdown of where the time in a frame went. Frame times themselves come from
+// TimeState's frame time history
+#[derive(Copy, Clone, Default)]
+struct PerfOverlayBreakdown {
+    // Time spent in AppHandler::update (all calls this frame)
+    update_time: time::Duration,
+
+    // Time spent in AppHandler::draw
+    draw_time: time::Duration,
+
+    // Time spent in the renderer outside of AppHandler::draw (flushing skia, submitting, presenting)
+    render_time: time::Duration,
+
+    // Time the GPU spent on a recent frame, if the renderer supports measuring it
+    gpu_timings: Option<GpuTimings>,
+}
+
+/// A HUD drawn on top of the app that shows fps, a frame time graph, and how long update, draw
+/// and the renderer took. Enable it with `AppBuilder::perf_overlay`.
+pub struct PerfOverlay {
+    visible: bool,
+    toggle_key: Option<VirtualKeyCode>,
+
+    // None until the first frame is recorded
+    breakdown: Option<PerfOverlayBreakdown>,
+}
+
+impl PerfOverlay {
+    pub fn new(toggle_key: Option<VirtualKeyCode>) -> Self {
+        PerfOverlay {
+            visible: true,
+            toggle_key,
+            breakdown: None,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    /// Toggle visibility if the toggle key was just pressed. Call before `InputState::end_frame`
+    pub fn handle_input(&mut self, input_state: &InputState) {
+        if let Some(toggle_key) = self.toggle_key {
+            if input_state.is_key_just_down(toggle_key) {
+                self.visible = !self.visible;
+            }
+        }
+    }
+
+    /// Record where the time went in a frame that was just drawn
+    pub fn record_frame(
+        &mut self,
+        update_time: time::Duration,
+        draw_time: time::Duration,
+        render_time: time::Duration,
+        gpu_timings: Option<GpuTimings>
+    ) {
+        let breakdown = match self.breakdown {
+            Some(previous) => PerfOverlayBreakdown {
+                update_time: smooth(previous.update_time, update_time),
+                draw_time: smooth(previous.draw_time, draw_time),
+                render_time: smooth(previous.render_time, render_time),
+                gpu_timings: match (previous.gpu_timings, gpu_timings) {
+                    (Some(previous), Some(gpu_timings)) => Some(GpuTimings {
+                        skia: smooth(previous.skia, gpu_timings.skia),
+                        blit: smooth(previous.blit, gpu_timings.blit),
+                        total: smooth(previous.total, gpu_timings.total)
+                    }),
+                    (previous, gpu_timings) => gpu_timings.or(previous)
+                }
+            },
+            None => PerfOverlayBreakdown {
+                update_time,
+                draw_time,
+                render_time,
+                gpu_timings
+            }
+        };
+
+        self.breakdown = Some(breakdown);
+    }
+
+    /// Draw the overlay in the top left corner of the canvas. The canvas matrix is reset so that
+    /// transforms left by the app don't affect the overlay.
+    pub fn draw(&self, canvas: &mut skia_safe::Canvas, dpi_factor: f64, time_state: &TimeState) {
+        if !self.visible {
+            return;
+        }
+
+        canvas.save();
+        canvas.reset_matrix();
+        canvas.scale((dpi_factor as f32, dpi_factor as f32));
+
+        let breakdown = self.breakdown.unwrap_or_default();
+        let frame_time_stats = time_state.frame_time_stats();
+        let frame_ms = duration_ms(frame_time_stats.mean);
+        let fps = if frame_ms > 0.0 { 1000.0 / frame_ms } else { 0.0 };
+
+        let gpu_line = match breakdown.gpu_timings {
+            Some(gpu_timings) => format!(
+                "gpu: {:.2}ms (skia: {:.2}ms  blit: {:.2}ms)",
+                duration_ms(gpu_timings.total),
+                duration_ms(gpu_timings.skia),
+                duration_ms(gpu_timings.blit)
+            ),
+            None => "gpu: n/a".to_string()
+        };
+
+        let lines = [
+            format!(
+                "fps: {:.1}  frame: {:.2}ms  p99: {:.2}ms  max: {:.2}ms",
+                fps,
+                frame_ms,
+                duration_ms(frame_time_stats.p99),
+                duration_ms(frame_time_stats.max)
+            ),
+            format!("update: {:.2}ms", duration_ms(breakdown.update_time)),
+            format!("draw: {:.2}ms", duration_ms(breakdown.draw_time)),
+            format!("render (submit/present): {:.2}ms", duration_ms(breakdown.render_time)),
+            gpu_line,
+        ];
+
+        let text_height = lines.len() as f32 * PERF_OVERLAY_LINE_HEIGHT;
+        let panel_height = PERF_OVERLAY_MARGIN * 3.0 + text_height + PERF_OVERLAY_GRAPH_HEIGHT;
+
+        // Background
+        let background_paint = skia_safe::Paint::new(skia_safe::Color4f::new(0.0, 0.0, 0.0, 0.7), None);
+        canvas.draw_rect(
+            skia_safe::Rect::from_xywh(0.0, 0.0, PERF_OVERLAY_WIDTH, panel_height),
+            &background_paint
+        );
+
+        // Text
+        let mut text_paint = skia_safe::Paint::new(skia_safe::Color4f::new(1.0, 1.0, 1.0, 1.0), None);
+        text_paint.set_anti_alias(true);
+
+        let mut font = skia_safe::Font::default();
+        font.set_size(13.0);
+
+        for (i, line) in lines.iter().enumerate() {
+            let y = PERF_OVERLAY_MARGIN + (i + 1) as f32 * PERF_OVERLAY_LINE_HEIGHT - 4.0;
+            canvas.draw_str(line, (PERF_OVERLAY_MARGIN, y), &font, &text_paint);
+        }
+
+        // Frame time graph, one bar per frame with the newest on the right
+        let graph_top = PERF_OVERLAY_MARGIN * 2.0 + text_height;
+        let graph_bottom = graph_top + PERF_OVERLAY_GRAPH_HEIGHT;
+        let graph_width = PERF_OVERLAY_WIDTH - PERF_OVERLAY_MARGIN * 2.0;
+        let bar_width = graph_width / PERF_OVERLAY_FRAME_COUNT as f32;
+        let ms_to_height = |ms: f32| (ms / PERF_OVERLAY_GRAPH_MAX_MS).min(1.0) * PERF_OVERLAY_GRAPH_HEIGHT;
+
+        let frame_times : Vec<time::Duration> = time_state.frame_time_history().frame_times().cloned().collect();
+        let frame_times = &frame_times[frame_times.len().saturating_sub(PERF_OVERLAY_FRAME_COUNT)..];
+
+        let first_bar_index = PERF_OVERLAY_FRAME_COUNT - frame_times.len();
+        for (i, frame_time) in frame_times.iter().enumerate() {
+            let ms = duration_ms(*frame_time);
+            let color = if ms > 1000.0 / 30.0 {
+                skia_safe::Color4f::new(1.0, 0.2, 0.2, 1.0)
+            } else if ms > 1000.0 / 60.0 {
+                skia_safe::Color4f::new(1.0, 1.0, 0.2, 1.0)
+            } else {
+                skia_safe::Color4f::new(0.2, 1.0, 0.2, 1.0)
+            };
+
+            let bar_paint = skia_safe::Paint::new(color, None);
+            let bar_height = ms_to_height(ms);
+            let x = PERF_OVERLAY_MARGIN + (first_bar_index + i) as f32 * bar_width;
+            canvas.draw_rect(
+                skia_safe::Rect::from_xywh(x, graph_bottom - bar_height, bar_width, bar_height),
+                &bar_paint
+            );
+        }
+
+        // Reference lines at 60 and 30 fps
+        let mut line_paint = skia_safe::Paint::new(skia_safe::Color4f::new(1.0, 1.0, 1.0, 0.5), None);
+        line_paint.set_style(skia_safe::paint::Style::Stroke);
+        line_paint.set_stroke_width(1.0);
+        for ms in &[1000.0 / 60.0, 1000.0 / 30.0] {
+            let y = graph_bottom - ms_to_height(*ms);
+            canvas.draw_line(
+                skia_safe::Point::new(PERF_OVERLAY_MARGIN, y),
+                skia_safe::Point::new(PERF_OVERLAY_MARGIN + graph_width, y),
+                &line_paint
+            );
+        }
+
+        canvas.restore();
+    }
+}
+
+// Move the average towards the new value
+fn smooth(average: time::Duration, value: time::Duration) -> time::Duration {
+    average.mul_f32(PERF_OVERLAY_SMOOTHING_FACTOR) + value.mul_f32(1.0 - PERF_OVERLAY_SMOOTHING_FACTOR)
+}
+
+fn duration_ms(duration: time::Duration) -> f32 {
+    duration.as_secs_f32() * 1000.0
+}
@@ -1,15 +1,50 @@
 use std::time;
 
+use super::frame_time_history::FrameTimeHistory;
+use super::frame_time_history::FrameTimeStats;
+
 #[derive(Copy, Clone, PartialEq, strum_macros::EnumCount, Debug)]
 pub enum TimeContext {
     // Normal system/wallclock time, never stops
     System,
+
+    // Time for animation/simulation. Can be paused, stepped, and scaled (i.e. slow motion)
+    Simulation,
 }
 
 const TIME_CONTEXT_COUNT : usize = TIMECONTEXT_COUNT;
 
 const NANOS_PER_SEC: u32 = 1_000_000_000;
 
+/// A change to a time context, queued with `AppControl` and applied after the update that queued it
+#[derive(Copy, Clone, Debug)]
+pub(super) enum TimeContextRequest {
+    Pause,
+    Resume,
+    SetTimeScale(f32),
+    Step
+}
+
+// Controls how a single time context advances
+#[derive(Copy, Clone)]
+struct TimeContextControl {
+    paused: bool,
+    time_scale: f32,
+
+    // If true, the context advances on the next update even if it is paused
+    step_requested: bool,
+}
+
+impl Default for TimeContextControl {
+    fn default() -> Self {
+        TimeContextControl {
+            paused: false,
+            time_scale: 1.0,
+            step_requested: false,
+        }
+    }
+}
+
 //TODO: Avoid using pub for fields
 
 // This is not intended to be accessed when the system time updates, but we can double buffer it
@@ -28,6 +63,14 @@ pub struct TimeState {
     pub previous_time_context: TimeContext,
 
     time_context_states: [ModeTimeState; TIME_CONTEXT_COUNT],
+
+    time_context_controls: [TimeContextControl; TIME_CONTEXT_COUNT],
+
+    // Wall clock time of recently drawn frames, recorded by App
+    frame_time_history: FrameTimeHistory,
+
+    // Fraction of a fixed timestep that has passed since the last update
+    interpolation_alpha: f32,
 }
 
 impl Default for TimeState {
@@ -41,28 +84,45 @@ impl Default for TimeState {
             previous_instant: now_instant,
             previous_time_context: TimeContext::System,
             time_context_states: [ModeTimeState::new(); TIME_CONTEXT_COUNT],
+            time_context_controls: [TimeContextControl::default(); TIME_CONTEXT_COUNT],
+            frame_time_history: FrameTimeHistory::default(),
+            interpolation_alpha: 1.0,
         };
     }
 }
 
 impl TimeState {
+    /// Advance every time context up to and including `time_context` (in declaration order).
+    /// Paused contexts don't advance, and each context's elapsed time is multiplied by its time
+    /// scale.
     pub fn update(&mut self, time_context: TimeContext) {
-        // Cache the mode we are in this frame
-        self.previous_time_context = time_context;
-
         // Determine length of time since last tick
         let now_instant = time::Instant::now();
         let elapsed = now_instant - self.previous_instant;
-        self.previous_instant = now_instant;
+        self.update_with_elapsed(time_context, elapsed);
+    }
+
+    /// Don't count the wall clock time that passed since the last update towards the next call to
+    /// `update` (i.e. time spent minimized, when nothing was updated)
+    pub fn discard_elapsed_time(&mut self) {
+        self.previous_instant = time::Instant::now();
+    }
+
+    /// Advance time by the given duration instead of measuring wall clock time. This allows
+    /// driving an app at a fixed step (i.e. for deterministic tests)
+    pub fn update_with_elapsed(&mut self, time_context: TimeContext, elapsed: time::Duration) {
+        // Cache the mode we are in this frame
+        self.previous_time_context = time_context;
+
+        self.previous_instant += elapsed;
 
-        for time_context_index in 0..TIME_CONTEXT_COUNT {
-            let mode_elapsed = if time_context_index <= (time_context as usize) {
-                elapsed
-            } else {
-                std::time::Duration::from_secs(0)
-            };
+        for time_context_index in 0..=(time_context as usize) {
+            self.advance_context(time_context_index, elapsed);
+        }
 
-            self.time_context_states[time_context_index].update(mode_elapsed);
+        // Contexts after the given one don't advance
+        for time_context_index in (time_context as usize + 1)..TIME_CONTEXT_COUNT {
+            self.time_context_states[time_context_index].skip_frame();
         }
 
 //        trace!(
@@ -76,9 +136,122 @@ impl TimeState {
         }
     }
 
+    /// Advance only the given time context, leaving the others and the wall clock reference used
+    /// by `update` untouched. With a fixed timestep, `App` advances System once per frame with
+    /// `update` and Simulation by the step once per fixed update with this.
+    pub fn update_context_with_elapsed(&mut self, time_context: TimeContext, elapsed: time::Duration) {
+        self.advance_context(time_context as usize, elapsed);
+    }
+
+    // Advance a single context by elapsed, taking its pause state and time scale into account
+    fn advance_context(&mut self, time_context_index: usize, elapsed: time::Duration) {
+        let control = &mut self.time_context_controls[time_context_index];
+        let is_advancing = !control.paused || control.step_requested;
+        control.step_requested = false;
+
+        if is_advancing {
+            self.time_context_states[time_context_index].update(elapsed.mul_f32(control.time_scale));
+        } else {
+            self.time_context_states[time_context_index].skip_frame();
+        }
+    }
+
     pub fn system(&self) -> &ModeTimeState {
         &self.time_context_states[TimeContext::System as usize]
     }
+
+    pub fn simulation(&self) -> &ModeTimeState {
+        &self.time_context_states[TimeContext::Simulation as usize]
+    }
+
+    pub fn time_context(&self, time_context: TimeContext) -> &ModeTimeState {
+        &self.time_context_states[time_context as usize]
+    }
+
+    /// The wall clock time of the most recently drawn frames. Unlike the time contexts, this isn't
+    /// affected by pausing, time scale or a fixed timestep
+    pub fn frame_time_history(&self) -> &FrameTimeHistory {
+        &self.frame_time_history
+    }
+
+    /// Min/max/mean, percentiles, and hitch count over the most recently drawn frames
+    pub fn frame_time_stats(&self) -> FrameTimeStats {
+        self.frame_time_history.stats()
+    }
+
+    /// Add the wall clock time between the start of the previous drawn frame and this one to the
+    /// frame time history. `App` calls this once per frame it draws
+    pub fn record_frame_time(&mut self, frame_time: time::Duration) {
+        self.frame_time_history.push(frame_time);
+    }
+
+    /// Change how many frames are kept for frame time statistics
+    pub fn set_frame_time_history_length(&mut self, max_frame_count: usize) {
+        self.frame_time_history.set_max_frame_count(max_frame_count);
+    }
+
+    /// Frames that take longer than this are counted as hitches in frame time statistics
+    pub fn set_hitch_threshold(&mut self, hitch_threshold: time::Duration) {
+        self.frame_time_history.set_hitch_threshold(hitch_threshold);
+    }
+
+    pub fn is_paused(&self, time_context: TimeContext) -> bool {
+        self.time_context_controls[time_context as usize].paused
+    }
+
+    pub fn time_scale(&self, time_context: TimeContext) -> f32 {
+        self.time_context_controls[time_context as usize].time_scale
+    }
+
+    /// Stop the given time context from advancing. System time can't be paused.
+    pub fn pause(&mut self, time_context: TimeContext) {
+        if time_context == TimeContext::System {
+            warn!("System time can't be paused");
+            return;
+        }
+
+        self.time_context_controls[time_context as usize].paused = true;
+    }
+
+    pub fn resume(&mut self, time_context: TimeContext) {
+        self.time_context_controls[time_context as usize].paused = false;
+    }
+
+    /// Multiply time passing in the given context by a scale (i.e. 0.5 for slow motion, 2.0 for
+    /// fast forward). Negative values are treated as 0. System time can't be scaled.
+    pub fn set_time_scale(&mut self, time_context: TimeContext, time_scale: f32) {
+        if time_context == TimeContext::System {
+            warn!("System time can't be scaled");
+            return;
+        }
+
+        self.time_context_controls[time_context as usize].time_scale = time_scale.max(0.0);
+    }
+
+    /// Advance a paused time context by a single update
+    pub fn step(&mut self, time_context: TimeContext) {
+        self.time_context_controls[time_context as usize].step_requested = true;
+    }
+
+    pub(super) fn apply_time_context_request(&mut self, time_context: TimeContext, request: TimeContextRequest) {
+        match request {
+            TimeContextRequest::Pause => self.pause(time_context),
+            TimeContextRequest::Resume => self.resume(time_context),
+            TimeContextRequest::SetTimeScale(time_scale) => self.set_time_scale(time_context, time_scale),
+            TimeContextRequest::Step => self.step(time_context)
+        }
+    }
+
+    /// When using a fixed timestep, the fraction (0..1) of a step that has passed since the last
+    /// update. Drawing can blend between the previous and current state by this amount. Always 1.0
+    /// with a variable timestep.
+    pub fn interpolation_alpha(&self) -> f32 {
+        self.interpolation_alpha
+    }
+
+    pub(super) fn set_interpolation_alpha(&mut self, interpolation_alpha: f32) {
+        self.interpolation_alpha = interpolation_alpha;
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -137,4 +310,140 @@ impl ModeTimeState {
 
         self.frame_count += 1;
     }
+
+    // The context didn't advance (it is paused or wasn't updated). No frame is counted and fps is
+    // kept, but the frame time is zero so that anything moved by it stays still
+    fn skip_frame(&mut self) {
+        self.previous_frame_time = time::Duration::from_secs(0);
+        self.previous_frame_dt = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(milliseconds: u64) -> time::Duration {
+        time::Duration::from_millis(milliseconds)
+    }
+
+    #[test]
+    fn fixed_steps_only_advance_simulation() {
+        let mut time_state = TimeState::default();
+        let start_instant = time_state.previous_instant;
+
+        // A 25ms frame with two 10ms fixed updates, the way App drives a fixed timestep
+        time_state.update_with_elapsed(TimeContext::System, ms(25));
+        time_state.update_context_with_elapsed(TimeContext::Simulation, ms(10));
+        time_state.update_context_with_elapsed(TimeContext::Simulation, ms(10));
+
+        assert_eq!(time_state.system().total_time, ms(25));
+        assert_eq!(time_state.system().previous_frame_time, ms(25));
+        assert_eq!(time_state.system().frame_count, 1);
+        assert_eq!(time_state.simulation().total_time, ms(20));
+        assert_eq!(time_state.simulation().previous_frame_time, ms(10));
+        assert_eq!(time_state.previous_instant, start_instant + ms(25));
+
+        // A short frame with no fixed updates still advances System
+        time_state.update_with_elapsed(TimeContext::System, ms(5));
+        assert_eq!(time_state.system().total_time, ms(30));
+        assert_eq!(time_state.simulation().total_time, ms(20));
+        assert_eq!(time_state.previous_instant, start_instant + ms(30));
+    }
+
+    #[test]
+    fn system_follows_wall_clock() {
+        let mut time_state = TimeState::default();
+        std::thread::sleep(ms(5));
+        time_state.update(TimeContext::System);
+        time_state.update_context_with_elapsed(TimeContext::Simulation, ms(1));
+
+        assert!(time_state.system().total_time >= ms(5));
+        assert_eq!(time_state.simulation().total_time, ms(1));
+    }
+
+    #[test]
+    fn pause_and_resume() {
+        let mut time_state = TimeState::default();
+        time_state.update_with_elapsed(TimeContext::Simulation, ms(10));
+        let fps = time_state.simulation().fps;
+        let fps_smoothed = time_state.simulation().fps_smoothed;
+
+        time_state.pause(TimeContext::Simulation);
+        assert!(time_state.is_paused(TimeContext::Simulation));
+        time_state.update_with_elapsed(TimeContext::Simulation, ms(10));
+        time_state.update_with_elapsed(TimeContext::Simulation, ms(10));
+
+        let simulation = time_state.simulation();
+        assert_eq!(simulation.total_time, ms(10));
+        assert_eq!(simulation.frame_count, 1);
+        assert_eq!(simulation.previous_frame_time, ms(0));
+        assert_eq!(simulation.fps, fps);
+        assert_eq!(simulation.fps_smoothed, fps_smoothed);
+        assert_eq!(time_state.system().total_time, ms(30));
+        assert_eq!(time_state.system().frame_count, 3);
+
+        time_state.resume(TimeContext::Simulation);
+        time_state.update_with_elapsed(TimeContext::Simulation, ms(10));
+        assert_eq!(time_state.simulation().total_time, ms(20));
+        assert_eq!(time_state.simulation().frame_count, 2);
+    }
+
+    #[test]
+    fn system_time_cant_be_paused_or_scaled() {
+        let mut time_state = TimeState::default();
+        time_state.pause(TimeContext::System);
+        time_state.set_time_scale(TimeContext::System, 0.5);
+        time_state.update_with_elapsed(TimeContext::Simulation, ms(10));
+
+        assert!(!time_state.is_paused(TimeContext::System));
+        assert_eq!(time_state.time_scale(TimeContext::System), 1.0);
+        assert_eq!(time_state.system().total_time, ms(10));
+    }
+
+    #[test]
+    fn time_scale() {
+        let mut time_state = TimeState::default();
+        time_state.set_time_scale(TimeContext::Simulation, 0.5);
+        time_state.update_with_elapsed(TimeContext::Simulation, ms(20));
+        assert_eq!(time_state.simulation().total_time, ms(10));
+        assert_eq!(time_state.system().total_time, ms(20));
+
+        time_state.set_time_scale(TimeContext::Simulation, 2.0);
+        time_state.update_with_elapsed(TimeContext::Simulation, ms(20));
+        assert_eq!(time_state.simulation().total_time, ms(50));
+
+        // Negative scales are treated as 0
+        time_state.set_time_scale(TimeContext::Simulation, -1.0);
+        assert_eq!(time_state.time_scale(TimeContext::Simulation), 0.0);
+        time_state.update_with_elapsed(TimeContext::Simulation, ms(20));
+        assert_eq!(time_state.simulation().total_time, ms(50));
+    }
+
+    #[test]
+    fn step_advances_a_paused_context_once() {
+        let mut time_state = TimeState::default();
+        time_state.pause(TimeContext::Simulation);
+        time_state.set_time_scale(TimeContext::Simulation, 0.5);
+
+        time_state.step(TimeContext::Simulation);
+        time_state.update_with_elapsed(TimeContext::Simulation, ms(20));
+        assert_eq!(time_state.simulation().total_time, ms(10));
+        assert_eq!(time_state.simulation().frame_count, 1);
+
+        time_state.update_with_elapsed(TimeContext::Simulation, ms(20));
+        assert_eq!(time_state.simulation().total_time, ms(10));
+        assert_eq!(time_state.simulation().frame_count, 1);
+        assert!(time_state.is_paused(TimeContext::Simulation));
+    }
+
+    #[test]
+    fn contexts_after_the_updated_one_dont_advance() {
+        let mut time_state = TimeState::default();
+        time_state.update_with_elapsed(TimeContext::System, ms(10));
+
+        assert_eq!(time_state.system().total_time, ms(10));
+        assert_eq!(time_state.simulation().total_time, ms(0));
+        assert_eq!(time_state.simulation().frame_count, 0);
+    }
 }
@@ -0,0 +1,417 @@
+use std::time::Duration;
+
+use super::input_state::LogicalPosition;
+use super::input_state::MouseDragState;
+
+pub use winit::event::TouchPhase;
+
+// A touch that moves further than this is a drag rather than a tap
+const TOUCH_MIN_DRAG_DISTANCE : f64 = 8.0;
+
+// A touch held longer than this is not a tap
+const TAP_MAX_DURATION : Duration = Duration::from_millis(300);
+
+// The second tap of a double tap must start within this long after the first ended, and this
+// close to it
+const DOUBLE_TAP_MAX_INTERVAL : Duration = Duration::from_millis(300);
+const DOUBLE_TAP_MAX_DISTANCE : f64 = 30.0;
+
+/// A finger on a touchscreen. Kept until the end of the frame in which it is lifted
+#[derive(Copy, Clone, Debug)]
+pub struct TouchPoint {
+    /// Identifies the finger for as long as it touches the screen. Ids may be reused afterwards
+    pub id: u64,
+
+    pub begin_position: LogicalPosition,
+    pub position: LogicalPosition,
+
+    /// Movement since the end of the previous frame
+    pub previous_frame_delta: LogicalPosition,
+
+    pub just_started: bool,
+
+    /// The finger was lifted this frame
+    pub just_ended: bool,
+
+    /// The OS cancelled the touch this frame (i.e. the window lost focus). Also sets `just_ended`
+    pub cancelled: bool,
+
+    /// Set once the finger moves far enough to be considered a drag
+    pub drag: Option<MouseDragState>,
+
+    // Value of the gesture clock when the touch started
+    begin_time: Duration
+}
+
+/// Gestures recognized from touch input
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TouchGesture {
+    /// A single finger touched and lifted without moving
+    Tap(LogicalPosition),
+
+    /// A second tap shortly after and close to the first. The second tap is also reported as a
+    /// `Tap`
+    DoubleTap(LogicalPosition),
+
+    /// Two fingers moved together. The delta is the movement of the point between them
+    TwoFingerPan(LogicalPosition),
+
+    /// Two fingers moved apart (scale greater than 1.0) or together (less than 1.0). The center is
+    /// the point between them
+    Pinch {
+        center: LogicalPosition,
+        scale: f64
+    }
+}
+
+// Tracks touches and recognizes gestures, owned by `InputState`
+#[derive(Default)]
+pub(super) struct TouchTracker {
+    pub touches: Vec<TouchPoint>,
+    pub gestures: Vec<TouchGesture>,
+
+    // Advanced by InputState::advance_time before events are handled. Used to time taps
+    clock: Duration,
+
+    // Where and when the last tap ended, for detecting double taps
+    previous_tap: Option<(LogicalPosition, Duration)>,
+
+    // Ids, center and distance of the two touches as of the last two-finger gesture update
+    two_finger_state: Option<((u64, u64), LogicalPosition, f64)>,
+}
+
+impl TouchTracker {
+    pub fn advance_time(&mut self, elapsed: Duration) {
+        self.clock += elapsed;
+    }
+
+    pub fn end_frame(&mut self) {
+        self.touches.retain(|touch| !touch.just_ended);
+        for touch in self.touches.iter_mut() {
+            touch.just_started = false;
+            touch.previous_frame_delta = LogicalPosition::new(0.0, 0.0);
+            if let Some(drag) = &mut touch.drag {
+                drag.previous_frame_delta = LogicalPosition::new(0.0, 0.0);
+            }
+        }
+
+        self.gestures.clear();
+    }
+
+    pub fn handle_touch_event(&mut self, id: u64, phase: TouchPhase, position: LogicalPosition) {
+        match phase {
+            TouchPhase::Started => {
+                // A touch that ended this frame may have the same id, it is no longer needed
+                self.touches.retain(|touch| touch.id != id);
+                self.touches.push(TouchPoint {
+                    id,
+                    begin_position: position,
+                    position,
+                    previous_frame_delta: LogicalPosition::new(0.0, 0.0),
+                    just_started: true,
+                    just_ended: false,
+                    cancelled: false,
+                    drag: None,
+                    begin_time: self.clock
+                });
+            },
+            TouchPhase::Moved => {
+                if let Some(touch) = self.active_touch_mut(id) {
+                    Self::move_touch(touch, position);
+                }
+            },
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                let clock = self.clock;
+                let other_touch_active = self.touches.iter().any(|touch| touch.id != id && !touch.just_ended);
+
+                let tap_position = match self.active_touch_mut(id) {
+                    Some(touch) => {
+                        Self::move_touch(touch, position);
+                        touch.just_ended = true;
+                        touch.cancelled = phase == TouchPhase::Cancelled;
+
+                        let is_tap = !touch.cancelled &&
+                            touch.drag.is_none() &&
+                            !other_touch_active &&
+                            clock - touch.begin_time <= TAP_MAX_DURATION;
+
+                        if is_tap { Some(touch.begin_position) } else { None }
+                    },
+                    None => None
+                };
+
+                if let Some(tap_position) = tap_position {
+                    self.handle_tap(tap_position);
+                }
+            }
+        }
+
+        self.update_two_finger_gestures();
+    }
+
+    fn handle_tap(&mut self, position: LogicalPosition) {
+        self.gestures.push(TouchGesture::Tap(position));
+
+        let is_double_tap = match self.previous_tap {
+            Some((previous_position, previous_time)) => {
+                self.clock - previous_time <= DOUBLE_TAP_MAX_INTERVAL &&
+                    distance(previous_position, position) <= DOUBLE_TAP_MAX_DISTANCE
+            },
+            None => false
+        };
+
+        if is_double_tap {
+            self.gestures.push(TouchGesture::DoubleTap(position));
+
+            // A third tap starts a new double tap rather than completing another one
+            self.previous_tap = None;
+        } else {
+            self.previous_tap = Some((position, self.clock));
+        }
+    }
+
+    fn update_two_finger_gestures(&mut self) {
+        let active : Vec<&TouchPoint> = self.touches.iter().filter(|touch| !touch.just_ended).collect();
+        if active.len() != 2 {
+            self.two_finger_state = None;
+            return;
+        }
+
+        let ids = (active[0].id, active[1].id);
+        let center = LogicalPosition::new(
+            (active[0].position.x + active[1].position.x) / 2.0,
+            (active[0].position.y + active[1].position.y) / 2.0
+        );
+        let finger_distance = distance(active[0].position, active[1].position);
+
+        if let Some((previous_ids, previous_center, previous_distance)) = self.two_finger_state {
+            if previous_ids == ids {
+                let delta = LogicalPosition::new(center.x - previous_center.x, center.y - previous_center.y);
+                if delta.x != 0.0 || delta.y != 0.0 {
+                    self.gestures.push(TouchGesture::TwoFingerPan(delta));
+                }
+
+                if previous_distance > 0.0 && finger_distance != previous_distance {
+                    self.gestures.push(TouchGesture::Pinch {
+                        center,
+                        scale: finger_distance / previous_distance
+                    });
+                }
+            }
+        }
+
+        self.two_finger_state = Some((ids, center, finger_distance));
+    }
+
+    fn active_touch_mut(&mut self, id: u64) -> Option<&mut TouchPoint> {
+        self.touches.iter_mut().find(|touch| touch.id == id && !touch.just_ended)
+    }
+
+    fn move_touch(touch: &mut TouchPoint, position: LogicalPosition) {
+        let delta = LogicalPosition::new(position.x - touch.position.x, position.y - touch.position.y);
+        touch.previous_frame_delta = LogicalPosition::new(
+            touch.previous_frame_delta.x + delta.x,
+            touch.previous_frame_delta.y + delta.y
+        );
+        touch.position = position;
+
+        touch.drag = match touch.drag {
+            Some(drag) => Some(MouseDragState {
+                begin_position: drag.begin_position,
+                end_position: position,
+                previous_frame_delta: LogicalPosition::new(
+                    drag.previous_frame_delta.x + delta.x,
+                    drag.previous_frame_delta.y + delta.y
+                ),
+                accumulated_frame_delta: LogicalPosition::new(
+                    position.x - drag.begin_position.x,
+                    position.y - drag.begin_position.y
+                )
+            }),
+            None => {
+                if distance(touch.begin_position, position) > TOUCH_MIN_DRAG_DISTANCE {
+                    let total_delta = LogicalPosition::new(
+                        position.x - touch.begin_position.x,
+                        position.y - touch.begin_position.y
+                    );
+
+                    Some(MouseDragState {
+                        begin_position: touch.begin_position,
+                        end_position: position,
+                        previous_frame_delta: total_delta,
+                        accumulated_frame_delta: total_delta
+                    })
+                } else {
+                    None
+                }
+            }
+        };
+    }
+}
+
+fn distance(p0: LogicalPosition, p1: LogicalPosition) -> f64 {
+    let x_diff = p1.x - p0.x;
+    let y_diff = p1.y - p0.y;
+
+    ((x_diff * x_diff) + (y_diff * y_diff)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::input_state::InputState;
+    use crate::app::input_state::LogicalSize;
+
+    fn ms(milliseconds: u64) -> Duration {
+        Duration::from_millis(milliseconds)
+    }
+
+    fn position(x: f64, y: f64) -> LogicalPosition {
+        LogicalPosition::new(x, y)
+    }
+
+    fn new_input_state() -> InputState {
+        InputState::new_with_window_size(LogicalSize::new(800.0, 600.0), 1.0)
+    }
+
+    // Touch and lift a finger at the same position, taking the given time
+    fn tap(input_state: &mut InputState, id: u64, at: LogicalPosition, held: Duration) {
+        input_state.handle_touch_event(id, TouchPhase::Started, at);
+        input_state.advance_time(held);
+        input_state.handle_touch_event(id, TouchPhase::Ended, at);
+    }
+
+    #[test]
+    fn single_tap() {
+        let mut input_state = new_input_state();
+        tap(&mut input_state, 0, position(100.0, 100.0), ms(50));
+        assert_eq!(input_state.touch_gestures(), &[TouchGesture::Tap(position(100.0, 100.0))]);
+        assert!(input_state.touch(0).unwrap().just_ended);
+
+        input_state.end_frame();
+        assert!(input_state.touch_gestures().is_empty());
+        assert!(input_state.touches().is_empty());
+
+        // Held too long to be a tap
+        input_state.advance_time(ms(1000));
+        tap(&mut input_state, 0, position(100.0, 100.0), ms(400));
+        assert!(input_state.touch_gestures().is_empty());
+    }
+
+    #[test]
+    fn double_tap_within_time_window() {
+        let mut input_state = new_input_state();
+        tap(&mut input_state, 0, position(100.0, 100.0), ms(50));
+        input_state.end_frame();
+
+        input_state.advance_time(ms(100));
+        tap(&mut input_state, 1, position(110.0, 100.0), ms(50));
+        assert_eq!(input_state.touch_gestures(), &[
+            TouchGesture::Tap(position(110.0, 100.0)),
+            TouchGesture::DoubleTap(position(110.0, 100.0))
+        ]);
+
+        // A third tap starts a new double tap
+        input_state.end_frame();
+        input_state.advance_time(ms(100));
+        tap(&mut input_state, 0, position(110.0, 100.0), ms(50));
+        assert_eq!(input_state.touch_gestures(), &[TouchGesture::Tap(position(110.0, 100.0))]);
+    }
+
+    #[test]
+    fn double_tap_outside_time_window_or_too_far() {
+        let mut input_state = new_input_state();
+        tap(&mut input_state, 0, position(100.0, 100.0), ms(50));
+        input_state.end_frame();
+
+        input_state.advance_time(ms(400));
+        tap(&mut input_state, 0, position(100.0, 100.0), ms(50));
+        assert_eq!(input_state.touch_gestures(), &[TouchGesture::Tap(position(100.0, 100.0))]);
+        input_state.end_frame();
+
+        input_state.advance_time(ms(100));
+        tap(&mut input_state, 0, position(200.0, 100.0), ms(50));
+        assert_eq!(input_state.touch_gestures(), &[TouchGesture::Tap(position(200.0, 100.0))]);
+    }
+
+    #[test]
+    fn move_under_drag_threshold_is_a_tap() {
+        let mut input_state = new_input_state();
+        input_state.handle_touch_event(0, TouchPhase::Started, position(0.0, 0.0));
+        input_state.handle_touch_event(0, TouchPhase::Moved, position(5.0, 5.0));
+
+        let touch = input_state.touch(0).unwrap();
+        assert!(touch.drag.is_none());
+        assert_eq!(touch.previous_frame_delta, position(5.0, 5.0));
+
+        input_state.advance_time(ms(50));
+        input_state.handle_touch_event(0, TouchPhase::Ended, position(5.0, 5.0));
+        assert_eq!(input_state.touch_gestures(), &[TouchGesture::Tap(position(0.0, 0.0))]);
+    }
+
+    #[test]
+    fn move_over_drag_threshold_is_a_drag() {
+        let mut input_state = new_input_state();
+        input_state.handle_touch_event(0, TouchPhase::Started, position(0.0, 0.0));
+        input_state.handle_touch_event(0, TouchPhase::Moved, position(10.0, 0.0));
+
+        let drag = input_state.touch(0).unwrap().drag.unwrap();
+        assert_eq!(drag.begin_position, position(0.0, 0.0));
+        assert_eq!(drag.end_position, position(10.0, 0.0));
+        assert_eq!(drag.accumulated_frame_delta, position(10.0, 0.0));
+
+        input_state.end_frame();
+        input_state.handle_touch_event(0, TouchPhase::Moved, position(10.0, 4.0));
+        let drag = input_state.touch(0).unwrap().drag.unwrap();
+        assert_eq!(drag.previous_frame_delta, position(0.0, 4.0));
+        assert_eq!(drag.accumulated_frame_delta, position(10.0, 4.0));
+
+        input_state.advance_time(ms(50));
+        input_state.handle_touch_event(0, TouchPhase::Ended, position(10.0, 4.0));
+        assert!(input_state.touch_gestures().is_empty());
+    }
+
+    #[test]
+    fn two_finger_pan() {
+        let mut input_state = new_input_state();
+        input_state.handle_touch_event(0, TouchPhase::Started, position(0.0, 0.0));
+        input_state.handle_touch_event(1, TouchPhase::Started, position(100.0, 0.0));
+        assert!(input_state.touch_gestures().is_empty());
+        input_state.end_frame();
+
+        // Both fingers move down by 10
+        input_state.handle_touch_event(0, TouchPhase::Moved, position(0.0, 10.0));
+        input_state.handle_touch_event(1, TouchPhase::Moved, position(100.0, 10.0));
+        assert_eq!(input_state.two_finger_pan_delta(), position(0.0, 10.0));
+        assert!((input_state.pinch_scale() - 1.0).abs() < 1e-9);
+
+        // Lifting one finger of a pan is not a tap
+        input_state.end_frame();
+        input_state.handle_touch_event(0, TouchPhase::Ended, position(0.0, 10.0));
+        assert!(input_state.touch_gestures().is_empty());
+    }
+
+    #[test]
+    fn pinch() {
+        let mut input_state = new_input_state();
+        input_state.handle_touch_event(0, TouchPhase::Started, position(0.0, 0.0));
+        input_state.handle_touch_event(1, TouchPhase::Started, position(100.0, 0.0));
+        input_state.end_frame();
+
+        input_state.handle_touch_event(1, TouchPhase::Moved, position(200.0, 0.0));
+        assert_eq!(input_state.touch_gestures(), &[
+            TouchGesture::TwoFingerPan(position(50.0, 0.0)),
+            TouchGesture::Pinch {
+                center: position(100.0, 0.0),
+                scale: 2.0
+            }
+        ]);
+        assert_eq!(input_state.pinch_scale(), 2.0);
+
+        input_state.end_frame();
+        assert_eq!(input_state.pinch_scale(), 1.0);
+
+        input_state.handle_touch_event(0, TouchPhase::Moved, position(100.0, 0.0));
+        assert_eq!(input_state.pinch_scale(), 0.5);
+    }
+}
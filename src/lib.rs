@@ -2,30 +2,91 @@
 #[macro_use]
 extern crate log;
 
+#[macro_use]
+extern crate lazy_static;
+
 mod app;
 pub use app::InputState;
 pub use app::MouseDragState;
+pub use app::KeyEvent;
+pub use app::ImeEvent;
+pub use app::KeyChord;
+pub use app::KeyBindings;
+pub use app::TouchPoint;
+pub use app::TouchPhase; // This is the same type as winit::event::TouchPhase
+pub use app::TouchGesture;
+pub use app::GamepadId;
+pub use app::GamepadButton;
+pub use app::GamepadAxis;
+pub use app::GamepadEvent;
+pub use app::GamepadBackend;
+pub use app::MockGamepadBackend;
+pub use app::NullGamepadBackend;
+pub use app::default_gamepad_backend;
+#[cfg(target_os = "linux")]
+pub use app::LinuxJoystickBackend;
 
 // These are re-exported winit types
 pub use app::VirtualKeyCode; // This is the same type as winit::event::VirtualKeyCode
 pub use app::MouseButton; // This is the same type as winit::event::MouseButton
 pub use app::ElementState; // This is the same type as winit::event::ElementState
+pub use app::ModifiersState; // This is the same type as winit::event::ModifiersState
+pub use app::MouseScrollDelta; // This is the same type as winit::event::MouseScrollDelta
 pub use app::LogicalSize; // This is the same type as winit::dpi::LogicalSize
 pub use app::PhysicalSize; // This is the same type as winit::dpi::PhysicalSize
 pub use app::LogicalPosition; // This is the same type as winit::dpi::LogicalPosition
 pub use app::PhysicalPosition; // This is the same type as winit::dpi::PhysicalPosition
 
 pub use app::TimeState;
+pub use app::TimeContext;
+pub use app::ModeTimeState;
+pub use app::FrameTimeHistory;
+pub use app::FrameTimeStats;
 pub use app::AppControl;
+pub use app::AppWindowId;
+pub use app::AppWindowOptions;
+pub use app::FullscreenMode;
+pub use app::Icon; // This is the same type as winit::window::Icon
+pub use app::CursorIcon; // This is the same type as winit::window::CursorIcon
 pub use app::PeriodicEvent;
 pub use app::ScopeTimer;
+pub use app::PerfOverlay;
 pub use app::App;
 pub use app::AppBuilder;
 pub use app::AppHandler;
+pub use app::RedrawMode;
+pub use app::FixedTimestep;
+
+pub use app::InputRecording;
+pub use app::InputRecorder;
+pub use app::InputReplay;
+pub use app::RecordedFrame;
+
+pub use app::AppTestHarness;
+pub use app::TestHarnessBackend;
+pub use app::TestHarnessError;
+pub use app::ScriptedInputEvent;
+pub use app::FrameComparison;
+pub use app::FrameMismatch;
+
+mod profiler;
+pub use profiler::Profiler;
+pub use profiler::ProfileScope;
+pub use profiler::ProfileFrame;
 
 mod renderer;
 pub use renderer::RendererBuilder;
 pub use renderer::Renderer;
+pub use renderer::RendererBackend;
+pub use renderer::PresentMode;
+pub use renderer::CompositeAlpha;
+pub use renderer::GpuTimings;
+pub use renderer::DeviceSelectionPolicy;
+pub use renderer::PhysicalDeviceInfo;
+pub use renderer::PhysicalDeviceType;
+pub use renderer::HeadlessRenderer;
+pub use renderer::CapturedFrame;
+pub use renderer::Error;
 
 // Export these crates so that downstream crates can easily use the same version of them as we do
 pub use ash;
@@ -0,0 +1,359 @@
+//! A hierarchical CPU profiler. `ScopeTimer`s created while the profiler is enabled are collected
+//! into a tree per frame, and the most recent frames are retained so they can be inspected or
+//! exported to the Chrome trace event format (viewable in chrome://tracing or Perfetto).
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time;
+
+/// A timed scope and the scopes that were nested inside it
+#[derive(Clone, Debug)]
+pub struct ProfileScope {
+    pub name: String,
+
+    // Small integer that identifies the thread the scope ran on. Stable for the life of the thread
+    pub thread_id: u64,
+    pub thread_name: Option<String>,
+
+    // Relative to when the profiler was first used
+    pub start: time::Duration,
+    pub duration: time::Duration,
+
+    pub children: Vec<ProfileScope>,
+}
+
+/// All the top-level scopes, from every thread, that finished during a frame
+#[derive(Clone, Debug)]
+pub struct ProfileFrame {
+    pub frame_index: u64,
+
+    // Relative to when the profiler was first used
+    pub start: time::Duration,
+    pub duration: time::Duration,
+
+    pub scopes: Vec<ProfileScope>,
+}
+
+struct ProfilerState {
+    frame_start: time::Duration,
+
+    // Top-level scopes that finished in the current frame
+    current_scopes: Vec<ProfileScope>,
+
+    retained_frames: VecDeque<ProfileFrame>,
+    max_retained_frames: usize,
+}
+
+lazy_static! {
+    // Scope and frame times are relative to this. Kept out of PROFILER_STATE so that timing a scope
+    // doesn't need the lock
+    static ref PROFILER_EPOCH: time::Instant = time::Instant::now();
+
+    static ref PROFILER_STATE: Mutex<ProfilerState> = Mutex::new(ProfilerState {
+        frame_start: time::Duration::from_secs(0),
+        current_scopes: vec![],
+        retained_frames: VecDeque::new(),
+        max_retained_frames: 300,
+    });
+}
+
+static PROFILER_ENABLED: AtomicBool = AtomicBool::new(false);
+static FRAME_INDEX: AtomicU64 = AtomicU64::new(0);
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+// A scope that has started but not finished
+struct OpenScope {
+    name: String,
+    start: time::Duration,
+    children: Vec<ProfileScope>,
+}
+
+thread_local! {
+    static THREAD_ID: u64 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+    static OPEN_SCOPES: RefCell<Vec<OpenScope>> = RefCell::new(vec![]);
+}
+
+/// Global controls for the profiler. Nothing is recorded until it is enabled.
+pub struct Profiler;
+
+impl Profiler {
+    pub fn set_enabled(enabled: bool) {
+        if enabled && !Self::is_enabled() {
+            // end_frame doesn't track frames while disabled, so the current frame starts now
+            let mut state = PROFILER_STATE.lock().unwrap();
+            state.frame_start = PROFILER_EPOCH.elapsed();
+            state.current_scopes.clear();
+        }
+
+        PROFILER_ENABLED.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled() -> bool {
+        PROFILER_ENABLED.load(Ordering::Relaxed)
+    }
+
+    /// How many frames are kept. Older frames are discarded.
+    pub fn set_max_retained_frames(max_retained_frames: usize) {
+        let mut state = PROFILER_STATE.lock().unwrap();
+        state.max_retained_frames = max_retained_frames;
+        while state.retained_frames.len() > max_retained_frames {
+            state.retained_frames.pop_front();
+        }
+    }
+
+    /// Finish the current frame and start a new one. `App` calls this once per frame. If using the
+    /// renderer directly, call it after drawing each frame.
+    pub fn end_frame() {
+        let frame_index = FRAME_INDEX.fetch_add(1, Ordering::Relaxed);
+
+        // Avoid taking the lock every frame while disabled
+        if !Self::is_enabled() {
+            return;
+        }
+
+        let now = PROFILER_EPOCH.elapsed();
+        let mut state = PROFILER_STATE.lock().unwrap();
+
+        let frame = ProfileFrame {
+            frame_index,
+            start: state.frame_start,
+            duration: now - state.frame_start,
+            scopes: std::mem::replace(&mut state.current_scopes, vec![]),
+        };
+
+        while state.retained_frames.len() >= state.max_retained_frames.max(1) {
+            state.retained_frames.pop_front();
+        }
+
+        if state.max_retained_frames > 0 {
+            state.retained_frames.push_back(frame);
+        }
+
+        state.frame_start = now;
+    }
+
+    /// A copy of the retained frames, oldest first
+    pub fn frames() -> Vec<ProfileFrame> {
+        PROFILER_STATE.lock().unwrap().retained_frames.iter().cloned().collect()
+    }
+
+    pub fn clear() {
+        PROFILER_STATE.lock().unwrap().retained_frames.clear();
+    }
+
+    /// Export the retained frames as Chrome trace event JSON
+    pub fn chrome_trace_json() -> String {
+        chrome_trace_json(&Self::frames())
+    }
+
+    /// Write the retained frames to a file in the Chrome trace event JSON format
+    pub fn write_chrome_trace<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<()> {
+        std::fs::write(path, Self::chrome_trace_json())
+    }
+
+    fn begin_scope(name: &str) {
+        let start = PROFILER_EPOCH.elapsed();
+        OPEN_SCOPES.with(|open_scopes| {
+            open_scopes.borrow_mut().push(OpenScope {
+                name: name.to_string(),
+                start,
+                children: vec![],
+            });
+        });
+    }
+
+    fn end_scope() {
+        let end = PROFILER_EPOCH.elapsed();
+
+        let finished_root = OPEN_SCOPES.with(|open_scopes| {
+            let mut open_scopes = open_scopes.borrow_mut();
+            let open_scope = open_scopes.pop()?;
+
+            let scope = ProfileScope {
+                name: open_scope.name,
+                thread_id: THREAD_ID.with(|x| *x),
+                thread_name: std::thread::current().name().map(|x| x.to_string()),
+                start: open_scope.start,
+                duration: end - open_scope.start,
+                children: open_scope.children,
+            };
+
+            match open_scopes.last_mut() {
+                Some(parent) => {
+                    parent.children.push(scope);
+                    None
+                },
+                None => Some(scope)
+            }
+        });
+
+        if let Some(scope) = finished_root {
+            PROFILER_STATE.lock().unwrap().current_scopes.push(scope);
+        }
+    }
+}
+
+/// Times a scope. The time is logged at trace level, and if the `Profiler` is enabled, the scope is
+/// recorded along with any scopes nested inside it.
+pub struct ScopeTimer<'a> {
+    start_time: std::time::Instant,
+    name: &'a str,
+    is_profiled: bool,
+}
+
+impl<'a> ScopeTimer<'a> {
+    #[allow(unused_must_use)]
+    pub fn new(name: &'a str) -> Self {
+        let is_profiled = Profiler::is_enabled();
+        if is_profiled {
+            Profiler::begin_scope(name);
+        }
+
+        ScopeTimer {
+            start_time: std::time::Instant::now(),
+            name,
+            is_profiled,
+        }
+    }
+}
+
+impl<'a> Drop for ScopeTimer<'a> {
+    fn drop(&mut self) {
+        let end_time = std::time::Instant::now();
+        trace!(
+            "ScopeTimer {}: {}",
+            self.name,
+            (end_time - self.start_time).as_micros() as f64 / 1000.0
+        );
+
+        if self.is_profiled {
+            Profiler::end_scope();
+        }
+    }
+}
+
+/// Convert frames to Chrome trace event JSON. Each scope becomes a complete ("X") event
+pub fn chrome_trace_json(frames: &[ProfileFrame]) -> String {
+    let mut events = vec![];
+    let mut thread_names = std::collections::BTreeMap::new();
+
+    for frame in frames {
+        events.push(format!(
+            "{{\"name\":\"frame {}\",\"cat\":\"frame\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":0}}",
+            frame.frame_index,
+            frame.start.as_micros(),
+            frame.duration.as_micros()
+        ));
+
+        let mut scopes : Vec<&ProfileScope> = frame.scopes.iter().collect();
+        while let Some(scope) = scopes.pop() {
+            events.push(format!(
+                "{{\"name\":\"{}\",\"cat\":\"scope\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":{}}}",
+                escape_json(&scope.name),
+                scope.start.as_micros(),
+                scope.duration.as_micros(),
+                scope.thread_id
+            ));
+
+            if let Some(thread_name) = &scope.thread_name {
+                thread_names.insert(scope.thread_id, thread_name.clone());
+            }
+
+            scopes.extend(scope.children.iter());
+        }
+    }
+
+    events.push("{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":0,\"args\":{\"name\":\"frames\"}}".to_string());
+    for (thread_id, thread_name) in thread_names {
+        events.push(format!(
+            "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},\"args\":{{\"name\":\"{}\"}}}}",
+            thread_id,
+            escape_json(&thread_name)
+        ));
+    }
+
+    format!("{{\"traceEvents\":[\n{}\n]}}\n", events.join(",\n"))
+}
+
+fn escape_json(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(milliseconds: u64) -> time::Duration {
+        time::Duration::from_millis(milliseconds)
+    }
+
+    fn scope(name: &str, thread_id: u64, start: u64, duration: u64, children: Vec<ProfileScope>) -> ProfileScope {
+        ProfileScope {
+            name: name.to_string(),
+            thread_id,
+            thread_name: None,
+            start: ms(start),
+            duration: ms(duration),
+            children
+        }
+    }
+
+    #[test]
+    fn escape_json_strings() {
+        assert_eq!(escape_json("plain"), "plain");
+        assert_eq!(escape_json("say \"hi\""), "say \\\"hi\\\"");
+        assert_eq!(escape_json("C:\\path"), "C:\\\\path");
+        assert_eq!(escape_json("a\nb\rc\td"), "a\\nb\\rc\\td");
+        assert_eq!(escape_json("\u{1}\u{1f}"), "\\u0001\\u001f");
+        assert_eq!(escape_json("\u{65e5}\u{672c}"), "\u{65e5}\u{672c}");
+    }
+
+    #[test]
+    fn chrome_trace_events() {
+        let mut update = scope("update \"world\"", 1, 1, 5, vec![scope("physics", 1, 2, 3, vec![])]);
+        update.thread_name = Some("main\\thread".to_string());
+
+        let frames = vec![ProfileFrame {
+            frame_index: 7,
+            start: ms(0),
+            duration: ms(16),
+            scopes: vec![update, scope("load", 2, 4, 1, vec![])]
+        }];
+
+        let json = chrome_trace_json(&frames);
+        assert!(json.starts_with("{\"traceEvents\":[\n"));
+        assert!(json.ends_with("\n]}\n"));
+
+        let events : Vec<&str> = json.lines().collect();
+
+        // Frame, three scopes, and the thread names of the frame track and the main thread
+        assert_eq!(events.len() - 2, 6);
+        assert!(json.contains("{\"name\":\"frame 7\",\"cat\":\"frame\",\"ph\":\"X\",\"ts\":0,\"dur\":16000,\"pid\":1,\"tid\":0}"));
+        assert!(json.contains("{\"name\":\"update \\\"world\\\"\",\"cat\":\"scope\",\"ph\":\"X\",\"ts\":1000,\"dur\":5000,\"pid\":1,\"tid\":1}"));
+        assert!(json.contains("{\"name\":\"physics\",\"cat\":\"scope\",\"ph\":\"X\",\"ts\":2000,\"dur\":3000,\"pid\":1,\"tid\":1}"));
+        assert!(json.contains("{\"name\":\"load\",\"cat\":\"scope\",\"ph\":\"X\",\"ts\":4000,\"dur\":1000,\"pid\":1,\"tid\":2}"));
+        assert!(json.contains("{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":1,\"args\":{\"name\":\"main\\\\thread\"}}"));
+
+        // No frames still produces a valid trace
+        assert_eq!(
+            chrome_trace_json(&[]),
+            "{\"traceEvents\":[\n{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":0,\"args\":{\"name\":\"frames\"}}\n]}\n"
+        );
+    }
+}
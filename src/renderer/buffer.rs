@@ -38,25 +38,38 @@ impl VkBuffer {
             logical_device.get_buffer_memory_requirements(buffer)
         };
 
-        //TODO: Return error
-        let buffer_memory_index = super::util::find_memorytype_index(
+        let buffer_memory_index = match super::util::find_memorytype_index(
             &buffer_memory_req,
             device_memory_properties,
             required_property_flags,
-        ).expect("Unable to find suitable memorytype for the vertex buffer.");
+        ) {
+            Some(buffer_memory_index) => buffer_memory_index,
+            None => {
+                warn!("Unable to find a suitable memory type for a buffer");
+                unsafe { logical_device.destroy_buffer(buffer, None) };
+                return Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY);
+            }
+        };
 
         let buffer_allocate_info = vk::MemoryAllocateInfo::builder()
             .allocation_size(buffer_memory_req.size)
             .memory_type_index(buffer_memory_index);
 
-        let buffer_memory = unsafe {
-            logical_device
-                .allocate_memory(&buffer_allocate_info, None)?
+        // Destroy the buffer (and memory) if a later step fails, so nothing leaks
+        let buffer_memory = match unsafe { logical_device.allocate_memory(&buffer_allocate_info, None) } {
+            Ok(buffer_memory) => buffer_memory,
+            Err(e) => {
+                unsafe { logical_device.destroy_buffer(buffer, None) };
+                return Err(e);
+            }
         };
 
-        unsafe {
-            logical_device
-                .bind_buffer_memory(buffer, buffer_memory, 0)?
+        if let Err(e) = unsafe { logical_device.bind_buffer_memory(buffer, buffer_memory, 0) } {
+            unsafe {
+                logical_device.destroy_buffer(buffer, None);
+                logical_device.free_memory(buffer_memory, None);
+            }
+            return Err(e);
         }
 
         Ok(VkBuffer {
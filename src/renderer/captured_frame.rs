@@ -0,0 +1,115 @@
+use std::path::Path;
+
+/// Pixels read back from a skia surface. Pixels are tightly packed, non-premultiplied RGBA (4 bytes
+/// per pixel, row-major, top row first)
+#[derive(Clone)]
+pub struct CapturedFrame {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>
+}
+
+impl CapturedFrame {
+    pub const BYTES_PER_PIXEL: usize = 4;
+
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        assert_eq!(pixels.len(), width as usize * height as usize * Self::BYTES_PER_PIXEL);
+
+        CapturedFrame {
+            width,
+            height,
+            pixels
+        }
+    }
+
+    /// Read the current contents of the given surface. The caller is responsible for ensuring
+    /// that any drawing to the surface has been flushed. Returns None if skia could not read the
+    /// surface.
+    pub fn read_from_surface(surface: &mut skia_safe::Surface) -> Option<Self> {
+        let width = surface.width() as u32;
+        let height = surface.height() as u32;
+
+        let image_info = Self::image_info(width, height);
+        let row_bytes = Self::row_bytes(width);
+        let mut pixels = vec![0; row_bytes * height as usize];
+
+        if surface.read_pixels(&image_info, &mut pixels, row_bytes, (0, 0)) {
+            Some(CapturedFrame::new(width, height, pixels))
+        } else {
+            warn!("Failed to read pixels from skia surface");
+            None
+        }
+    }
+
+    /// Decode a PNG (or any other format skia can decode). Returns None if the data could not be
+    /// decoded.
+    pub fn decode(encoded: &[u8]) -> Option<Self> {
+        let image = skia_safe::Image::from_encoded(skia_safe::Data::new_copy(encoded), None)?;
+
+        let width = image.width() as u32;
+        let height = image.height() as u32;
+
+        let image_info = Self::image_info(width, height);
+        let row_bytes = Self::row_bytes(width);
+        let mut pixels = vec![0; row_bytes * height as usize];
+
+        if image.read_pixels(&image_info, &mut pixels, row_bytes, (0, 0), skia_safe::image::CachingHint::Disallow) {
+            Some(CapturedFrame::new(width, height, pixels))
+        } else {
+            None
+        }
+    }
+
+    /// Read a PNG from disk
+    pub fn read_png<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let data = std::fs::read(path)?;
+        Self::decode(&data).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "Could not decode image")
+        })
+    }
+
+    /// Encode the pixels as a PNG. Returns None if skia failed to encode the image
+    pub fn encode_png(&self) -> Option<Vec<u8>> {
+        let image = skia_safe::Image::from_raster_data(
+            &Self::image_info(self.width, self.height),
+            skia_safe::Data::new_copy(&self.pixels),
+            Self::row_bytes(self.width)
+        )?;
+
+        let data = image.encode_to_data(skia_safe::EncodedImageFormat::PNG)?;
+        Some(data.as_bytes().to_vec())
+    }
+
+    /// Encode the pixels as a PNG and write them to disk
+    pub fn write_png<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let data = self.encode_png().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::Other, "Could not encode image as PNG")
+        })?;
+
+        std::fs::write(path, data)
+    }
+
+    /// Returns the RGBA value of a single pixel
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let offset = (y as usize * self.width as usize + x as usize) * Self::BYTES_PER_PIXEL;
+        [
+            self.pixels[offset],
+            self.pixels[offset + 1],
+            self.pixels[offset + 2],
+            self.pixels[offset + 3]
+        ]
+    }
+
+    fn image_info(width: u32, height: u32) -> skia_safe::ImageInfo {
+        skia_safe::ImageInfo::new(
+            (width as i32, height as i32),
+            skia_safe::ColorType::RGBA8888,
+            skia_safe::AlphaType::Unpremul,
+            None
+        )
+    }
+
+    fn row_bytes(width: u32) -> usize {
+        width as usize * Self::BYTES_PER_PIXEL
+    }
+}
@@ -0,0 +1,50 @@
+use ash::vk;
+
+/// Controls how the window's alpha channel is combined with whatever is behind the window. See
+/// the vulkan documentation for VkCompositeAlphaFlagBitsKHR for details. Anything other than
+/// `Opaque` requires a transparent window (i.e. `WindowBuilder::with_transparent`)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CompositeAlpha {
+    /// Alpha is ignored, the window covers everything behind it
+    Opaque,
+
+    /// The compositor expects colors that are already multiplied by alpha. Skia draws this way, so
+    /// the frame is presented unchanged
+    PreMultiplied,
+
+    /// The compositor multiplies colors by alpha itself. The frame is un-premultiplied when it is
+    /// copied to the swapchain
+    PostMultiplied,
+
+    /// The platform decides, usually based on how the window was created. The frame is presented
+    /// unchanged. Used as a fallback if the surface supports none of the others
+    Inherit
+}
+
+impl CompositeAlpha {
+    pub fn to_vk(self) -> vk::CompositeAlphaFlagsKHR {
+        match self {
+            CompositeAlpha::Opaque => vk::CompositeAlphaFlagsKHR::OPAQUE,
+            CompositeAlpha::PreMultiplied => vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED,
+            CompositeAlpha::PostMultiplied => vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED,
+            CompositeAlpha::Inherit => vk::CompositeAlphaFlagsKHR::INHERIT
+        }
+    }
+
+    /// Returns None unless exactly one flag is set
+    pub fn from_vk(composite_alpha: vk::CompositeAlphaFlagsKHR) -> Option<CompositeAlpha> {
+        match composite_alpha {
+            vk::CompositeAlphaFlagsKHR::OPAQUE => Some(CompositeAlpha::Opaque),
+            vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED => Some(CompositeAlpha::PreMultiplied),
+            vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED => Some(CompositeAlpha::PostMultiplied),
+            vk::CompositeAlphaFlagsKHR::INHERIT => Some(CompositeAlpha::Inherit),
+            _ => None
+        }
+    }
+}
+
+impl Default for CompositeAlpha {
+    fn default() -> Self {
+        CompositeAlpha::Opaque
+    }
+}
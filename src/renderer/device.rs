@@ -3,12 +3,13 @@ use ash::vk;
 use ash::prelude::VkResult;
 use super::VkInstance;
 use super::window_support;
+use super::Error;
+use super::DeviceSelectionPolicy;
+use super::PhysicalDeviceInfo;
 
 use ash::version::DeviceV1_0;
 use ash::version::InstanceV1_0;
 
-use std::ffi::CStr;
-
 //use ash::extensions::ext as ash_ext;
 use ash::extensions::khr;
 
@@ -27,17 +28,32 @@ pub struct Queues {
 }
 
 pub struct VkDevice {
-    pub surface: ash::vk::SurfaceKHR,
+    // The surface of the window the device was selected for. None if the device was created
+    // headless, or if ownership of the surface was taken (VkRenderer takes it and destroys it along
+    // with its swapchain)
+    pub surface: Option<ash::vk::SurfaceKHR>,
     pub surface_loader: ash::extensions::khr::Surface,
     pub physical_device: ash::vk::PhysicalDevice,
+    pub physical_device_info: PhysicalDeviceInfo,
     pub logical_device: ash::Device,
     pub queue_family_indices: QueueFamilyIndices,
     pub queues: Queues,
-    pub memory_properties: vk::PhysicalDeviceMemoryProperties
+    pub memory_properties: vk::PhysicalDeviceMemoryProperties,
+
+    // Nanoseconds per timestamp query tick, None if the graphics queue doesn't support timestamps
+    pub timestamp_period: Option<f32>,
+
+    // Number of meaningful low bits in a timestamp written on the graphics queue. The rest are
+    // undefined and must be masked off
+    pub timestamp_valid_bits: u32
 }
 
 impl VkDevice {
-    pub fn new(instance: &VkInstance, window: &winit::window::Window) -> VkResult<Self> {
+    pub fn new(
+        instance: &VkInstance,
+        window: &winit::window::Window,
+        device_selection_policy: &DeviceSelectionPolicy
+    ) -> Result<Self, Error> {
         // Get the surface, needed to select the best queue family
         use raw_window_handle::HasRawWindowHandle;
         let surface = unsafe {
@@ -45,129 +61,301 @@ impl VkDevice {
                 &instance.entry,
                 &instance.instance,
                 &window.raw_window_handle()
-            )?
+            ).map_err(Error::SurfaceCreation)?
+        };
+
+        Self::create(instance, Some(surface), device_selection_policy)
+    }
+
+    /// Create a surface for another window. Fails if the device's present queue can't present to
+    /// it. The caller is responsible for destroying the surface with `surface_loader`.
+    pub fn create_surface(
+        &self,
+        instance: &VkInstance,
+        window: &winit::window::Window
+    ) -> Result<ash::vk::SurfaceKHR, Error> {
+        use raw_window_handle::HasRawWindowHandle;
+        let surface = unsafe {
+            window_support::create_surface(
+                &instance.entry,
+                &instance.instance,
+                &window.raw_window_handle()
+            ).map_err(Error::SurfaceCreation)?
+        };
+
+        let is_supported = unsafe {
+            self.surface_loader.get_physical_device_surface_support(
+                self.physical_device,
+                self.queue_family_indices.present_queue_family_index,
+                surface
+            )
         };
 
+        if !is_supported {
+            unsafe {
+                self.surface_loader.destroy_surface(surface, None);
+            }
+            return Err(Error::SurfaceNotSupported);
+        }
+
+        Ok(surface)
+    }
+
+    /// Creates a device that can't present to a window. The graphics queue is also used as the
+    /// present queue, and the swapchain extension is not enabled.
+    pub fn new_headless(
+        instance: &VkInstance,
+        device_selection_policy: &DeviceSelectionPolicy
+    ) -> Result<Self, Error> {
+        Self::create(instance, None, device_selection_policy)
+    }
+
+    /// Lists every physical device, whether it is suitable, and the score the given policy gives
+    /// it. There is no window, so support for presenting to a surface is not checked.
+    pub fn enumerate_physical_devices(
+        instance: &VkInstance,
+        device_selection_policy: &DeviceSelectionPolicy
+    ) -> Result<Vec<PhysicalDeviceInfo>, Error> {
         let surface_loader = khr::Surface::new(
             &instance.entry,
             &instance.instance);
 
-        // Pick a physical device
-        let (
-            physical_device,
-            queue_family_indices
-        ) = Self::choose_physical_device(&instance.instance, &surface_loader, &surface)?;
+        let candidates = Self::query_physical_devices(
+            &instance.instance,
+            &surface_loader,
+            None,
+            device_selection_policy
+        )?;
+
+        Ok(candidates.into_iter().map(|(_, device_info, _)| device_info).collect())
+    }
+
+    fn create(
+        instance: &VkInstance,
+        surface: Option<ash::vk::SurfaceKHR>,
+        device_selection_policy: &DeviceSelectionPolicy
+    ) -> Result<Self, Error> {
+        let surface_loader = khr::Surface::new(
+            &instance.entry,
+            &instance.instance);
+
+        let created = Self::choose_and_create_logical_device(
+            instance,
+            &surface_loader,
+            surface.as_ref(),
+            device_selection_policy
+        );
 
-        // Create a logical device
         let (
+            physical_device,
+            physical_device_info,
+            queue_family_indices,
             logical_device,
             queues
-        ) = Self::create_logical_device(
-            &instance.instance,
-            &physical_device,
-            &queue_family_indices
-        )?;
+        ) = match created {
+            Ok(created) => created,
+            Err(e) => {
+                // The surface was handed to this device, so it has to be destroyed here if no device
+                // is created to own it
+                if let Some(surface) = surface {
+                    unsafe {
+                        surface_loader.destroy_surface(surface, None);
+                    }
+                }
+
+                return Err(e);
+            }
+        };
 
         let memory_properties = unsafe {
             instance.instance.get_physical_device_memory_properties(physical_device)
         };
 
+        let timestamp_period = Self::get_timestamp_period(
+            &instance.instance,
+            &physical_device,
+            &queue_family_indices
+        );
+
+        let timestamp_valid_bits = Self::get_timestamp_valid_bits(
+            &instance.instance,
+            &physical_device,
+            &queue_family_indices
+        );
+
         Ok(VkDevice {
             surface,
             surface_loader,
             physical_device,
+            physical_device_info,
             logical_device,
             queue_family_indices,
             queues,
-            memory_properties
+            memory_properties,
+            timestamp_period,
+            timestamp_valid_bits
         })
     }
 
+    fn choose_and_create_logical_device(
+        instance: &VkInstance,
+        surface_loader: &ash::extensions::khr::Surface,
+        surface: Option<&ash::vk::SurfaceKHR>,
+        device_selection_policy: &DeviceSelectionPolicy
+    ) -> Result<(ash::vk::PhysicalDevice, PhysicalDeviceInfo, QueueFamilyIndices, ash::Device, Queues), Error> {
+        // Pick a physical device
+        let (
+            physical_device,
+            physical_device_info,
+            queue_family_indices
+        ) = Self::choose_physical_device(
+            &instance.instance,
+            surface_loader,
+            surface,
+            device_selection_policy
+        )?;
+
+        // Create a logical device
+        let (
+            logical_device,
+            queues
+        ) = Self::create_logical_device(
+            &instance.instance,
+            &physical_device,
+            &queue_family_indices,
+            surface.is_some()
+        )?;
+
+        Ok((physical_device, physical_device_info, queue_family_indices, logical_device, queues))
+    }
+
     fn choose_physical_device(
         instance: &ash::Instance,
         surface_loader: &ash::extensions::khr::Surface,
-        surface: &ash::vk::SurfaceKHR
-    ) -> VkResult<(ash::vk::PhysicalDevice, QueueFamilyIndices)> {
+        surface: Option<&ash::vk::SurfaceKHR>,
+        device_selection_policy: &DeviceSelectionPolicy
+    ) -> Result<(ash::vk::PhysicalDevice, PhysicalDeviceInfo, QueueFamilyIndices), Error> {
+        let candidates = Self::query_physical_devices(instance, surface_loader, surface, device_selection_policy)?;
+
+        if candidates.is_empty() {
+            error!("Could not find a physical device");
+            return Err(Error::NoSuitableDevice);
+        }
+
+        let mut best_candidate = None;
+        let mut best_score = None;
+        for (physical_device, device_info, queue_family_indices) in candidates {
+            if let (Some(score), Some(queue_family_indices)) = (device_info.score, queue_family_indices) {
+                if best_score.map(|best_score| score > best_score).unwrap_or(true) {
+                    best_score = Some(score);
+                    best_candidate = Some((physical_device, device_info, queue_family_indices));
+                }
+            }
+        }
+
+        match best_candidate {
+            Some(candidate) => {
+                info!("Selected device '{}' using policy {:?}", candidate.1.name, device_selection_policy);
+                Ok(candidate)
+            },
+            None => {
+                error!("Could not find suitable device using policy {:?}", device_selection_policy);
+                Err(Error::NoSuitableDevice)
+            }
+        }
+    }
+
+    fn query_physical_devices(
+        instance: &ash::Instance,
+        surface_loader: &ash::extensions::khr::Surface,
+        surface: Option<&ash::vk::SurfaceKHR>,
+        device_selection_policy: &DeviceSelectionPolicy
+    ) -> VkResult<Vec<(ash::vk::PhysicalDevice, PhysicalDeviceInfo, Option<QueueFamilyIndices>)>> {
         let physical_devices = unsafe {
             instance
                 .enumerate_physical_devices()?
         };
 
-        if physical_devices.len() <= 0 {
-            panic!("Could not find a physical device");
-        }
-
-        let mut best_physical_device = None;
-        let mut best_physical_device_score = -1;
-        let mut best_physical_device_queue_family_indices = None;
-        for physical_device in physical_devices {
-            if let Some((score, queue_family_indices)) = Self::get_score_and_queue_families_for_physical_device(instance, &physical_device, surface_loader, surface)? {
-                if score > best_physical_device_score {
-                    best_physical_device = Some(physical_device);
-                    best_physical_device_score = score;
-                    best_physical_device_queue_family_indices = Some(queue_family_indices);
-                }
+        let mut candidates = Vec::with_capacity(physical_devices.len());
+        for (index, physical_device) in physical_devices.into_iter().enumerate() {
+            let properties : ash::vk::PhysicalDeviceProperties = unsafe { instance.get_physical_device_properties(physical_device) };
+            let mut device_info = PhysicalDeviceInfo::new(index, &properties);
+
+            let queue_family_indices = Self::get_queue_families_for_suitable_device(
+                instance,
+                &physical_device,
+                &device_info,
+                surface_loader,
+                surface
+            )?;
+
+            if queue_family_indices.is_some() {
+                device_info.is_suitable = true;
+                device_info.score = device_selection_policy.score(&device_info);
+                info!("Found suitable device '{}' score = {:?}", device_info.name, device_info.score);
             }
-        }
 
-        //TODO: Return an error
-        let physical_device = best_physical_device.expect("Could not find suitable device");
-        let queue_family_indices = best_physical_device_queue_family_indices.unwrap();
+            trace!("{:#?}", properties);
+            candidates.push((physical_device, device_info, queue_family_indices));
+        }
 
-        Ok((physical_device, queue_family_indices))
+        Ok(candidates)
     }
 
-    fn get_score_and_queue_families_for_physical_device(
+    fn get_queue_families_for_suitable_device(
         instance: &ash::Instance,
         device: &ash::vk::PhysicalDevice,
+        device_info: &PhysicalDeviceInfo,
         surface_loader: &ash::extensions::khr::Surface,
-        surface: &ash::vk::SurfaceKHR
-    ) -> VkResult<Option<(i32, QueueFamilyIndices)>> {
-        let properties : ash::vk::PhysicalDeviceProperties = unsafe { instance.get_physical_device_properties(*device) };
-        let device_name = unsafe {CStr::from_ptr(properties.device_name.as_ptr()).to_str().unwrap().to_string() };
-
+        surface: Option<&ash::vk::SurfaceKHR>
+    ) -> VkResult<Option<QueueFamilyIndices>> {
         //TODO: Check that the extensions we want to use are supported
         let _extensions : Vec<ash::vk::ExtensionProperties> = unsafe { instance.enumerate_device_extension_properties(*device)? };
         let features : vk::PhysicalDeviceFeatures = unsafe { instance.get_physical_device_features(*device) };
 
         if features.sampler_anisotropy == vk::FALSE {
-            info!("Found unsuitable device '{}', does not support sampler_anisotropy", device_name);
+            info!("Found unsuitable device '{}', does not support sampler_anisotropy", device_info.name);
             return Ok(None);
         }
 
         let queue_family_indices = Self::find_queue_families(instance, device, surface_loader, surface);
-        if let Some(queue_family_indices) = queue_family_indices {
-            // Query info about the GPU
-            //let features : ash::vk::PhysicalDeviceFeatures = unsafe { instance.get_physical_device_features(*device) };
-
-            let mut score = 0;
-
-            // What kind of GPU is it?
-            score += if properties.device_type == ash::vk::PhysicalDeviceType::DISCRETE_GPU {
-                1000
-            } else if properties.device_type == ash::vk::PhysicalDeviceType::VIRTUAL_GPU {
-                500
-            } else if properties.device_type == ash::vk::PhysicalDeviceType::INTEGRATED_GPU {
-                100
-            } else {
-                0
-            };
-
-            info!("Found suitable device '{}' score = {}", device_name, score);
-            trace!("{:#?}", properties);
-            Ok(Some((score, queue_family_indices)))
+        if queue_family_indices.is_none() {
+            info!("Found unsuitable device '{}', could not find queue families", device_info.name);
+        }
+
+        Ok(queue_family_indices)
+    }
+
+    fn get_timestamp_period(
+        instance: &ash::Instance,
+        physical_device: &ash::vk::PhysicalDevice,
+        queue_family_indices: &QueueFamilyIndices
+    ) -> Option<f32> {
+        let properties = unsafe { instance.get_physical_device_properties(*physical_device) };
+        let queue_families = unsafe { instance.get_physical_device_queue_family_properties(*physical_device) };
+        let graphics_queue_family = &queue_families[queue_family_indices.graphics_queue_family_index as usize];
+
+        if graphics_queue_family.timestamp_valid_bits > 0 && properties.limits.timestamp_period > 0.0 {
+            Some(properties.limits.timestamp_period)
         } else {
-            info!("Found unsuitable device '{}', could not find queue families", device_name);
-            trace!("{:#?}", properties);
-            Ok(None)
+            None
         }
     }
 
+    fn get_timestamp_valid_bits(
+        instance: &ash::Instance,
+        physical_device: &ash::vk::PhysicalDevice,
+        queue_family_indices: &QueueFamilyIndices
+    ) -> u32 {
+        let queue_families = unsafe { instance.get_physical_device_queue_family_properties(*physical_device) };
+        queue_families[queue_family_indices.graphics_queue_family_index as usize].timestamp_valid_bits
+    }
+
     fn find_queue_families(
         instance: &ash::Instance,
         physical_device: &ash::vk::PhysicalDevice,
         surface_loader: &ash::extensions::khr::Surface,
-        surface: &ash::vk::SurfaceKHR
+        surface: Option<&ash::vk::SurfaceKHR>
     ) -> Option<QueueFamilyIndices> {
         let queue_families : Vec<ash::vk::QueueFamilyProperties> = unsafe { instance.get_physical_device_queue_family_properties(*physical_device) };
 
@@ -179,11 +367,18 @@ impl VkDevice {
                 graphics_queue_family_index = Some(queue_family_index);
             }
 
-            if unsafe {surface_loader.get_physical_device_surface_support(*physical_device, queue_family_index, *surface) } {
-                present_queue_family_index = Some(queue_family_index);
+            if let Some(surface) = surface {
+                if unsafe {surface_loader.get_physical_device_surface_support(*physical_device, queue_family_index, *surface) } {
+                    present_queue_family_index = Some(queue_family_index);
+                }
             }
         }
 
+        // Without a surface there is nothing to present to, so just use the graphics queue
+        if surface.is_none() {
+            present_queue_family_index = graphics_queue_family_index;
+        }
+
         Some(QueueFamilyIndices {
             graphics_queue_family_index: graphics_queue_family_index?,
             present_queue_family_index: present_queue_family_index?
@@ -193,13 +388,18 @@ impl VkDevice {
     fn create_logical_device(
         instance: &ash::Instance,
         physical_device: &ash::vk::PhysicalDevice,
-        queue_family_indices: &QueueFamilyIndices
+        queue_family_indices: &QueueFamilyIndices,
+        enable_swapchain: bool
     )
         -> VkResult<(ash::Device, Queues)>
     {
         //TODO: Ideally we would set up validation layers for the logical device too.
 
-        let device_extension_names_raw = [khr::Swapchain::name().as_ptr()];
+        let mut device_extension_names_raw = vec![];
+        if enable_swapchain {
+            device_extension_names_raw.push(khr::Swapchain::name().as_ptr());
+        }
+
         let features = vk::PhysicalDeviceFeatures::builder()
             .sampler_anisotropy(true);
         let priorities = [1.0];
@@ -247,7 +447,9 @@ impl Drop for VkDevice {
         info!("destroying VkDevice");
         unsafe {
             self.logical_device.destroy_device(None);
-            self.surface_loader.destroy_surface(self.surface, None);
+            if let Some(surface) = self.surface {
+                self.surface_loader.destroy_surface(surface, None);
+            }
         }
 
         info!("destroyed VkDevice");
@@ -0,0 +1,264 @@
+use std::ffi::CStr;
+use std::sync::Arc;
+
+use ash::vk;
+
+/// The kind of GPU a physical device is
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PhysicalDeviceType {
+    Discrete,
+    Integrated,
+    Virtual,
+    Cpu,
+    Other
+}
+
+impl From<vk::PhysicalDeviceType> for PhysicalDeviceType {
+    fn from(device_type: vk::PhysicalDeviceType) -> Self {
+        match device_type {
+            vk::PhysicalDeviceType::DISCRETE_GPU => PhysicalDeviceType::Discrete,
+            vk::PhysicalDeviceType::INTEGRATED_GPU => PhysicalDeviceType::Integrated,
+            vk::PhysicalDeviceType::VIRTUAL_GPU => PhysicalDeviceType::Virtual,
+            vk::PhysicalDeviceType::CPU => PhysicalDeviceType::Cpu,
+            _ => PhysicalDeviceType::Other
+        }
+    }
+}
+
+/// Describes a physical device that could be used for rendering
+#[derive(Clone, Debug)]
+pub struct PhysicalDeviceInfo {
+    /// Position of the device in the list returned by the vulkan driver
+    pub index: usize,
+    pub name: String,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub device_type: PhysicalDeviceType,
+    pub api_version: u32,
+    pub driver_version: u32,
+
+    /// False if the device is missing required features or queue families. Unsuitable devices are
+    /// never selected, regardless of policy.
+    pub is_suitable: bool,
+
+    /// The score the selection policy gave this device. The suitable device with the highest
+    /// score is selected. None if the policy rejected the device or the device is unsuitable.
+    pub score: Option<i32>
+}
+
+impl PhysicalDeviceInfo {
+    pub(super) fn new(index: usize, properties: &vk::PhysicalDeviceProperties) -> Self {
+        let name = unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }
+            .to_string_lossy()
+            .to_string();
+
+        PhysicalDeviceInfo {
+            index,
+            name,
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            device_type: properties.device_type.into(),
+            api_version: properties.api_version,
+            driver_version: properties.driver_version,
+            is_suitable: false,
+            score: None
+        }
+    }
+}
+
+/// Determines which physical device is used when there is more than one (i.e. laptops with both
+/// integrated and discrete GPUs)
+#[derive(Clone)]
+pub enum DeviceSelectionPolicy {
+    /// Prefer discrete GPUs, then virtual, then integrated. This is the default.
+    HighPerformance,
+
+    /// Prefer integrated GPUs, which generally use less power
+    LowPower,
+
+    /// Select a device whose name, vendor ID, or device ID contains the given string (case
+    /// insensitive). IDs are compared as 4-digit hex, so "10de" matches NVIDIA devices. If several
+    /// devices match, they are ranked like `HighPerformance`
+    Matching(String),
+
+    /// Select the device at the given position in the list returned by the vulkan driver. See
+    /// `RendererBuilder::enumerate_devices`
+    Index(usize),
+
+    /// Call the given function for each suitable device. Return a score to make the device a
+    /// candidate (highest wins) or None to reject it. The function is shared, so the policy (and
+    /// builders holding it) can be sent to other threads. See `DeviceSelectionPolicy::custom`
+    Custom(Arc<dyn Fn(&PhysicalDeviceInfo) -> Option<i32> + Send + Sync>)
+}
+
+impl Default for DeviceSelectionPolicy {
+    fn default() -> Self {
+        DeviceSelectionPolicy::HighPerformance
+    }
+}
+
+impl std::fmt::Debug for DeviceSelectionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DeviceSelectionPolicy::HighPerformance => write!(f, "HighPerformance"),
+            DeviceSelectionPolicy::LowPower => write!(f, "LowPower"),
+            DeviceSelectionPolicy::Matching(s) => write!(f, "Matching({:?})", s),
+            DeviceSelectionPolicy::Index(index) => write!(f, "Index({})", index),
+            DeviceSelectionPolicy::Custom(_) => write!(f, "Custom")
+        }
+    }
+}
+
+impl DeviceSelectionPolicy {
+    /// Create a `Custom` policy from a function, i.e.
+    /// `DeviceSelectionPolicy::custom(|device| if device.name.contains("Intel") { None } else { Some(0) })`
+    pub fn custom<F : 'static + Fn(&PhysicalDeviceInfo) -> Option<i32> + Send + Sync>(f: F) -> Self {
+        DeviceSelectionPolicy::Custom(Arc::new(f))
+    }
+
+    /// Returns the score for a suitable device, or None if the policy rejects it
+    pub fn score(&self, device_info: &PhysicalDeviceInfo) -> Option<i32> {
+        match self {
+            DeviceSelectionPolicy::HighPerformance => Some(Self::high_performance_score(device_info)),
+            DeviceSelectionPolicy::LowPower => {
+                Some(match device_info.device_type {
+                    PhysicalDeviceType::Integrated => 1000,
+                    PhysicalDeviceType::Discrete => 500,
+                    PhysicalDeviceType::Virtual => 100,
+                    _ => 0
+                })
+            },
+            DeviceSelectionPolicy::Matching(s) => {
+                let s = s.to_lowercase();
+                let matches = device_info.name.to_lowercase().contains(&s)
+                    || format!("{:04x}", device_info.vendor_id).contains(&s)
+                    || format!("{:04x}", device_info.device_id).contains(&s);
+
+                if matches {
+                    Some(Self::high_performance_score(device_info))
+                } else {
+                    None
+                }
+            },
+            DeviceSelectionPolicy::Index(index) => {
+                if device_info.index == *index {
+                    Some(0)
+                } else {
+                    None
+                }
+            },
+            DeviceSelectionPolicy::Custom(f) => (f)(device_info)
+        }
+    }
+
+    fn high_performance_score(device_info: &PhysicalDeviceInfo) -> i32 {
+        match device_info.device_type {
+            PhysicalDeviceType::Discrete => 1000,
+            PhysicalDeviceType::Virtual => 500,
+            PhysicalDeviceType::Integrated => 100,
+            _ => 0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(index: usize, name: &str, vendor_id: u32, device_id: u32, device_type: PhysicalDeviceType) -> PhysicalDeviceInfo {
+        PhysicalDeviceInfo {
+            index,
+            name: name.to_string(),
+            vendor_id,
+            device_id,
+            device_type,
+            api_version: 0,
+            driver_version: 0,
+            is_suitable: true,
+            score: None
+        }
+    }
+
+    fn devices() -> Vec<PhysicalDeviceInfo> {
+        vec![
+            device(0, "Intel(R) UHD Graphics 630", 0x8086, 0x3e9b, PhysicalDeviceType::Integrated),
+            device(1, "GeForce GTX 1060", 0x10de, 0x1c20, PhysicalDeviceType::Discrete),
+            device(2, "llvmpipe", 0x10005, 0x0000, PhysicalDeviceType::Cpu),
+            device(3, "Virtual GPU", 0x1af4, 0x1050, PhysicalDeviceType::Virtual)
+        ]
+    }
+
+    // Index of the device the policy scores highest, like VkDevice's selection
+    fn selected_index(policy: &DeviceSelectionPolicy) -> Option<usize> {
+        devices().iter()
+            .filter_map(|device| policy.score(device).map(|score| (score, device.index)))
+            .max_by_key(|(score, index)| (*score, std::cmp::Reverse(*index)))
+            .map(|(_, index)| index)
+    }
+
+    #[test]
+    fn high_performance_prefers_discrete() {
+        let policy = DeviceSelectionPolicy::HighPerformance;
+        let devices = devices();
+        assert!(policy.score(&devices[1]) > policy.score(&devices[3]));
+        assert!(policy.score(&devices[3]) > policy.score(&devices[0]));
+        assert!(policy.score(&devices[0]) > policy.score(&devices[2]));
+        assert_eq!(selected_index(&policy), Some(1));
+        assert_eq!(selected_index(&DeviceSelectionPolicy::default()), Some(1));
+    }
+
+    #[test]
+    fn low_power_prefers_integrated() {
+        let policy = DeviceSelectionPolicy::LowPower;
+        let devices = devices();
+        assert!(policy.score(&devices[0]) > policy.score(&devices[1]));
+        assert!(policy.score(&devices[1]) > policy.score(&devices[3]));
+        assert!(policy.score(&devices[3]) > policy.score(&devices[2]));
+        assert_eq!(selected_index(&policy), Some(0));
+    }
+
+    #[test]
+    fn matching_by_name() {
+        assert_eq!(selected_index(&DeviceSelectionPolicy::Matching("geforce".to_string())), Some(1));
+        assert_eq!(selected_index(&DeviceSelectionPolicy::Matching("Intel".to_string())), Some(0));
+        assert_eq!(selected_index(&DeviceSelectionPolicy::Matching("radeon".to_string())), None);
+
+        // Several matches are ranked like HighPerformance
+        assert_eq!(selected_index(&DeviceSelectionPolicy::Matching("g".to_string())), Some(1));
+    }
+
+    #[test]
+    fn matching_by_hex_id() {
+        // Vendor IDs
+        assert_eq!(selected_index(&DeviceSelectionPolicy::Matching("10de".to_string())), Some(1));
+        assert_eq!(selected_index(&DeviceSelectionPolicy::Matching("8086".to_string())), Some(0));
+
+        // Device IDs, case insensitive
+        assert_eq!(selected_index(&DeviceSelectionPolicy::Matching("3E9B".to_string())), Some(0));
+        assert_eq!(selected_index(&DeviceSelectionPolicy::Matching("1c20".to_string())), Some(1));
+    }
+
+    #[test]
+    fn index() {
+        assert_eq!(selected_index(&DeviceSelectionPolicy::Index(2)), Some(2));
+        assert_eq!(DeviceSelectionPolicy::Index(2).score(&devices()[1]), None);
+        assert_eq!(selected_index(&DeviceSelectionPolicy::Index(10)), None);
+    }
+
+    #[test]
+    fn custom() {
+        // Reject anything but the CPU implementation
+        let policy = DeviceSelectionPolicy::custom(|device| {
+            if device.device_type == PhysicalDeviceType::Cpu { Some(1) } else { None }
+        });
+
+        let devices = devices();
+        assert_eq!(policy.score(&devices[1]), None);
+        assert_eq!(policy.score(&devices[2]), Some(1));
+        assert_eq!(selected_index(&policy), Some(2));
+
+        let reject_all = DeviceSelectionPolicy::custom(|_| None);
+        assert_eq!(selected_index(&reject_all), None);
+        assert_eq!(format!("{:?}", reject_all), "Custom");
+    }
+}
@@ -0,0 +1,83 @@
+use ash::vk;
+
+/// Errors that can occur while creating or using a renderer
+#[derive(Debug)]
+pub enum Error {
+    /// The vulkan loader (i.e. vulkan-1.dll or libvulkan.so) could not be found
+    LoaderMissing(String),
+
+    /// No physical device has the features and queue families that are required
+    NoSuitableDevice,
+
+    /// Failed to create a vulkan surface for the window
+    SurfaceCreation(vk::Result),
+
+    /// The device that was already selected can't present to this window's surface. This can
+    /// happen when windows are on displays driven by different GPUs
+    SurfaceNotSupported,
+
+    /// Skia could not create a GPU context from the vulkan device
+    SkiaContextCreation,
+
+    /// Skia could not create a surface to draw to
+    SkiaSurfaceCreation,
+
+    /// The swapchain no longer matches the window surface and must be recreated. The renderer
+    /// handles this internally, so this is only returned from low-level functions
+    SwapchainOutOfDate,
+
+    /// A SPIR-V shader could not be loaded
+    ShaderLoading(std::io::Error),
+
+    /// Pixels could not be read back from a surface
+    PixelReadback,
+
+    /// Software rendering can't present to this window (i.e. unsupported platform or windowing
+    /// system)
+    SoftwarePresentationUnsupported(String),
+
+    /// Copying a software rendered frame to the window failed
+    SoftwarePresentation(String),
+
+    /// Any other vulkan error
+    Vulkan(vk::Result)
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::LoaderMissing(e) => write!(f, "Could not find the vulkan loader: {}", e),
+            Error::NoSuitableDevice => write!(f, "Could not find a suitable vulkan device"),
+            Error::SurfaceCreation(e) => write!(f, "Could not create a vulkan surface for the window: {}", e),
+            Error::SurfaceNotSupported => write!(f, "The vulkan device can't present to the window's surface"),
+            Error::SkiaContextCreation => write!(f, "Could not create a skia context"),
+            Error::SkiaSurfaceCreation => write!(f, "Could not create a skia surface"),
+            Error::SwapchainOutOfDate => write!(f, "The swapchain is out of date"),
+            Error::ShaderLoading(e) => write!(f, "Could not load shader: {}", e),
+            Error::PixelReadback => write!(f, "Could not read pixels from surface"),
+            Error::SoftwarePresentationUnsupported(e) => write!(f, "Software rendering is not supported: {}", e),
+            Error::SoftwarePresentation(e) => write!(f, "Failed to present software rendered frame: {}", e),
+            Error::Vulkan(e) => write!(f, "Vulkan error: {}", e)
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::SurfaceCreation(e) => Some(e),
+            Error::ShaderLoading(e) => Some(e),
+            Error::Vulkan(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl From<vk::Result> for Error {
+    fn from(result: vk::Result) -> Self {
+        match result {
+            vk::Result::ERROR_OUT_OF_DATE_KHR => Error::SwapchainOutOfDate,
+            _ => Error::Vulkan(result)
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use ash::vk;
+use ash::prelude::VkResult;
+use ash::version::DeviceV1_0;
+
+use super::VkDevice;
+
+/// How long the GPU spent on a frame. Measured with timestamp queries, so results are from a
+/// frame that was drawn a few frames ago.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct GpuTimings {
+    /// Executing the commands skia submitted when the canvas was flushed
+    pub skia: std::time::Duration,
+
+    /// Copying the skia surface to the swapchain image
+    pub blit: std::time::Duration,
+
+    /// Both of the above
+    pub total: std::time::Duration,
+}
+
+// Timestamps written per frame in flight: before skia work, between skia work and the blit, and
+// after the blit
+const TIMESTAMPS_PER_FRAME : u32 = 3;
+
+/// Owns a timestamp query pool with a set of queries for each frame in flight (see
+/// `MAX_FRAMES_IN_FLIGHT`). The queries for a frame are reset and written each time it is drawn, and
+/// read back once the frame's fence has been waited on, before the frame is drawn again.
+pub struct VkGpuTimestamps {
+    pub device: ash::Device, // This device is owned by VkDevice, not VkGpuTimestamps
+
+    pub query_pool: vk::QueryPool,
+
+    // One per frame in flight. Resets the queries for the frame and writes the first timestamp.
+    // Submitted before skia draws.
+    pub begin_command_buffers: Vec<vk::CommandBuffer>,
+
+    // One per frame in flight. Write the timestamps before and after the blit, and are submitted
+    // in the same batch as the blit command buffer (on either side of it)
+    pub blit_begin_command_buffers: Vec<vk::CommandBuffer>,
+    pub blit_end_command_buffers: Vec<vk::CommandBuffer>,
+
+    // Nanoseconds per timestamp tick
+    timestamp_period: f32,
+
+    // Mask for the bits of a timestamp that are meaningful
+    timestamp_mask: u64,
+
+    // True if the queries for the frame have been submitted and not read back yet
+    pending: Vec<bool>,
+}
+
+impl VkGpuTimestamps {
+    /// Returns None if the graphics queue does not support timestamps
+    pub fn new(
+        device: &VkDevice,
+        command_pool: &vk::CommandPool,
+        frame_count: usize
+    ) -> VkResult<Option<Self>> {
+        let timestamp_period = match device.timestamp_period {
+            Some(timestamp_period) => timestamp_period,
+            None => {
+                info!("Timestamp queries are not supported, GPU timings will not be available");
+                return Ok(None);
+            }
+        };
+
+        let timestamp_mask = if device.timestamp_valid_bits >= 64 {
+            std::u64::MAX
+        } else {
+            (1u64 << device.timestamp_valid_bits) - 1
+        };
+
+        let query_pool_create_info = vk::QueryPoolCreateInfo::builder()
+            .query_type(vk::QueryType::TIMESTAMP)
+            .query_count(frame_count as u32 * TIMESTAMPS_PER_FRAME);
+
+        let query_pool = unsafe {
+            device.logical_device.create_query_pool(&query_pool_create_info, None)?
+        };
+
+        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+            .command_buffer_count(frame_count as u32 * 3)
+            .command_pool(*command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY);
+
+        let mut command_buffers = unsafe {
+            device.logical_device.allocate_command_buffers(&command_buffer_allocate_info)?
+        };
+
+        let blit_end_command_buffers = command_buffers.split_off(frame_count * 2);
+        let blit_begin_command_buffers = command_buffers.split_off(frame_count);
+        let begin_command_buffers = command_buffers;
+
+        for frame_index in 0..frame_count {
+            let first_query = frame_index as u32 * TIMESTAMPS_PER_FRAME;
+
+            Self::record_command_buffer(&device.logical_device, begin_command_buffers[frame_index], |command_buffer| unsafe {
+                device.logical_device.cmd_reset_query_pool(command_buffer, query_pool, first_query, TIMESTAMPS_PER_FRAME);
+                device.logical_device.cmd_write_timestamp(
+                    command_buffer,
+                    vk::PipelineStageFlags::TOP_OF_PIPE,
+                    query_pool,
+                    first_query
+                );
+            })?;
+
+            // Written once skia's work (submitted earlier on the same queue) completes
+            Self::record_command_buffer(&device.logical_device, blit_begin_command_buffers[frame_index], |command_buffer| unsafe {
+                device.logical_device.cmd_write_timestamp(
+                    command_buffer,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    query_pool,
+                    first_query + 1
+                );
+            })?;
+
+            Self::record_command_buffer(&device.logical_device, blit_end_command_buffers[frame_index], |command_buffer| unsafe {
+                device.logical_device.cmd_write_timestamp(
+                    command_buffer,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    query_pool,
+                    first_query + 2
+                );
+            })?;
+        }
+
+        Ok(Some(VkGpuTimestamps {
+            device: device.logical_device.clone(),
+            query_pool,
+            begin_command_buffers,
+            blit_begin_command_buffers,
+            blit_end_command_buffers,
+            timestamp_period,
+            timestamp_mask,
+            pending: vec![false; frame_count]
+        }))
+    }
+
+    fn record_command_buffer<F: FnOnce(vk::CommandBuffer)>(
+        logical_device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        f: F
+    ) -> VkResult<()> {
+        let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder();
+
+        unsafe {
+            logical_device.begin_command_buffer(command_buffer, &command_buffer_begin_info)?;
+            f(command_buffer);
+            logical_device.end_command_buffer(command_buffer)
+        }
+    }
+
+    /// Call after the command buffers that write the timestamps for the frame have been submitted
+    pub fn mark_submitted(&mut self, frame_index: usize) {
+        self.pending[frame_index] = true;
+    }
+
+    /// True if the frame's command buffers may be submitted, i.e. the results of the last
+    /// submission have been read back. The command buffers are not SIMULTANEOUS_USE, so they must
+    /// not be submitted again while a previous submission might still be executing.
+    pub fn can_submit(&self, frame_index: usize) -> bool {
+        !self.pending[frame_index]
+    }
+
+    /// Read back the timings from the last time the frame was drawn. Call after waiting for the
+    /// frame's fence. Returns None if the frame has not been drawn or the results are not
+    /// available yet. In the latter case the queries stay pending and are read again the next time
+    /// the frame comes around, and `can_submit` returns false until then.
+    pub fn read_results(&mut self, frame_index: usize) -> Option<GpuTimings> {
+        if !self.pending[frame_index] {
+            return None;
+        }
+
+        let mut timestamps = [0u64; TIMESTAMPS_PER_FRAME as usize];
+        let result = unsafe {
+            self.device.get_query_pool_results(
+                self.query_pool,
+                frame_index as u32 * TIMESTAMPS_PER_FRAME,
+                TIMESTAMPS_PER_FRAME,
+                &mut timestamps,
+                vk::QueryResultFlags::TYPE_64
+            )
+        };
+
+        match result {
+            Ok(()) => {},
+            Err(vk::Result::NOT_READY) => return None,
+            Err(e) => {
+                // The queries are reset by the next submission, so there is nothing to wait for
+                warn!("Error reading GPU timestamps: {:?}", e);
+                self.pending[frame_index] = false;
+                return None;
+            }
+        }
+
+        self.pending[frame_index] = false;
+
+        let to_duration = |start: u64, end: u64| {
+            let ticks = end.wrapping_sub(start) & self.timestamp_mask;
+            std::time::Duration::from_nanos((ticks as f64 * self.timestamp_period as f64) as u64)
+        };
+
+        let skia = to_duration(timestamps[0], timestamps[1]);
+        let blit = to_duration(timestamps[1], timestamps[2]);
+
+        Some(GpuTimings {
+            skia,
+            blit,
+            total: skia + blit
+        })
+    }
+}
+
+impl Drop for VkGpuTimestamps {
+    fn drop(&mut self) {
+        info!("destroying VkGpuTimestamps");
+
+        // The command buffers are freed with the command pool they were allocated from
+        unsafe {
+            self.device.destroy_query_pool(self.query_pool, None);
+        }
+
+        info!("destroyed VkGpuTimestamps");
+    }
+}
@@ -0,0 +1,102 @@
+use std::ffi::CString;
+
+use ash::version::DeviceV1_0;
+
+use std::mem::ManuallyDrop;
+use ash::vk;
+
+use super::VkInstance;
+use super::VkDevice;
+use super::VkSkiaContext;
+use super::skia_support::VkSkiaSurface;
+use super::CapturedFrame;
+use super::Error;
+use super::DeviceSelectionPolicy;
+
+/// Draws with skia into an offscreen surface of a fixed size, without needing a window. The
+/// pixels of each frame are read back and returned to the caller. This is useful for CI (i.e.
+/// no display, possibly a software vulkan driver) or generating thumbnails.
+pub struct HeadlessRenderer {
+    instance: ManuallyDrop<VkInstance>,
+    device: ManuallyDrop<VkDevice>,
+
+    skia_context: ManuallyDrop<VkSkiaContext>,
+    skia_surface: ManuallyDrop<VkSkiaSurface>,
+
+    extents: vk::Extent2D
+}
+
+impl HeadlessRenderer {
+    pub fn new(
+        app_name: &CString,
+        width: u32,
+        height: u32,
+        use_vulkan_debug_layer: bool,
+        device_selection_policy: &DeviceSelectionPolicy
+    ) -> Result<HeadlessRenderer, Error> {
+        // Plain locals, so that a failure part way through drops whatever was already created
+        let instance = VkInstance::new_headless(app_name, use_vulkan_debug_layer)?;
+        let device = VkDevice::new_headless(&instance, device_selection_policy)?;
+        let mut skia_context = VkSkiaContext::new(&instance, &device)?;
+
+        let extents = vk::Extent2D {
+            width,
+            height
+        };
+
+        info!("Create headless skia surface with extent: {:?}", extents);
+        let skia_surface = VkSkiaSurface::new(&device, &mut skia_context, &extents)?;
+
+        Ok(HeadlessRenderer {
+            instance: ManuallyDrop::new(instance),
+            device: ManuallyDrop::new(device),
+            skia_context: ManuallyDrop::new(skia_context),
+            skia_surface: ManuallyDrop::new(skia_surface),
+            extents
+        })
+    }
+
+    /// Size of the surface in pixels
+    pub fn extents(&self) -> (u32, u32) {
+        (self.extents.width, self.extents.height)
+    }
+
+    /// Call the given function to draw, then read back the results
+    pub fn draw<F : FnOnce(&mut skia_safe::Canvas)>(
+        &mut self,
+        f: F
+    ) -> Result<CapturedFrame, Error> {
+        {
+            let mut canvas = self.skia_surface.surface.canvas();
+            canvas.reset_matrix();
+
+            f(&mut canvas);
+
+            canvas.flush();
+        }
+
+        self.capture_frame()
+    }
+
+    /// Read back the current contents of the surface without drawing anything
+    pub fn capture_frame(&mut self) -> Result<CapturedFrame, Error> {
+        CapturedFrame::read_from_surface(&mut self.skia_surface.surface)
+            .ok_or(Error::PixelReadback)
+    }
+}
+
+impl Drop for HeadlessRenderer {
+    fn drop(&mut self) {
+        info!("destroying HeadlessRenderer");
+
+        unsafe {
+            self.device.logical_device.device_wait_idle().unwrap();
+            ManuallyDrop::drop(&mut self.skia_surface);
+            ManuallyDrop::drop(&mut self.skia_context);
+            ManuallyDrop::drop(&mut self.device);
+            ManuallyDrop::drop(&mut self.instance);
+        }
+
+        info!("destroyed HeadlessRenderer");
+    }
+}
@@ -43,23 +43,38 @@ impl VkImage {
             logical_device.get_image_memory_requirements(image)
         };
 
-        //TODO: Better error handling here
-        let image_memory_index = super::util::find_memorytype_index(
+        let image_memory_index = match super::util::find_memorytype_index(
             &image_memory_req,
             device_memory_properties,
             required_property_flags,
-        ).expect("Unable to find suitable memorytype for the vertex buffer.");
+        ) {
+            Some(image_memory_index) => image_memory_index,
+            None => {
+                warn!("Unable to find a suitable memory type for an image");
+                unsafe { logical_device.destroy_image(image, None) };
+                return Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY);
+            }
+        };
 
         let image_allocate_info = vk::MemoryAllocateInfo::builder()
             .allocation_size(image_memory_req.size)
             .memory_type_index(image_memory_index);
 
-        let image_memory = unsafe {
-            logical_device.allocate_memory(&image_allocate_info, None)?
+        // Destroy the image (and memory) if a later step fails, so nothing leaks
+        let image_memory = match unsafe { logical_device.allocate_memory(&image_allocate_info, None) } {
+            Ok(image_memory) => image_memory,
+            Err(e) => {
+                unsafe { logical_device.destroy_image(image, None) };
+                return Err(e);
+            }
         };
 
-        unsafe {
-            logical_device.bind_image_memory(image, image_memory, 0)?;
+        if let Err(e) = unsafe { logical_device.bind_image_memory(image, image_memory, 0) } {
+            unsafe {
+                logical_device.destroy_image(image, None);
+                logical_device.free_memory(image_memory, None);
+            }
+            return Err(e);
         }
 
         Ok(VkImage {
@@ -7,6 +7,7 @@ use ash::prelude::VkResult;
 use super::debug_reporter;
 use super::VkDebugReporter;
 use super::window_support;
+use super::Error;
 
 /// Create one of these at startup. It never gets lost/destroyed.
 pub struct VkInstance {
@@ -16,12 +17,28 @@ pub struct VkInstance {
 }
 
 impl VkInstance {
-    /// Creates a vulkan instance.
-    pub fn new(app_name: &CString, use_vulkan_debug_layer: bool) -> VkResult<VkInstance> {
+    /// Creates a vulkan instance with the extensions required to present to a window.
+    pub fn new(app_name: &CString, use_vulkan_debug_layer: bool) -> Result<VkInstance, Error> {
+        Self::create(app_name, use_vulkan_debug_layer, window_support::extension_names())
+    }
+
+    /// Creates a vulkan instance that does not enable VK_KHR_surface or any platform surface
+    /// extensions. Useful for rendering offscreen where there is no display.
+    pub fn new_headless(app_name: &CString, use_vulkan_debug_layer: bool) -> Result<VkInstance, Error> {
+        Self::create(app_name, use_vulkan_debug_layer, window_support::headless_extension_names())
+    }
+
+    fn create(
+        app_name: &CString,
+        use_vulkan_debug_layer: bool,
+        extension_names_raw: Vec<*const i8>
+    ) -> Result<VkInstance, Error> {
         // This loads the dll/so if needed
         info!("Find vulkan entry point");
-        //TODO: Return this error
-        let entry = ash::Entry::new().expect("Could not find Vulkan entry point");
+        let entry = ash::Entry::new().map_err(|e| {
+            error!("Could not find Vulkan entry point: {}", e);
+            Error::LoaderMissing(e.to_string())
+        })?;
 
         // Get the available layers/extensions
         let layers = entry.enumerate_instance_layer_properties()?;
@@ -54,9 +71,6 @@ impl VkInstance {
             .map(|raw_name| raw_name.as_ptr())
             .collect();
 
-        // Determine what extensions to use
-        let extension_names_raw = window_support::extension_names();
-
         // Create the instance
         let create_info = vk::InstanceCreateInfo::builder()
             .application_info(&appinfo)
@@ -65,10 +79,15 @@ impl VkInstance {
 
         info!("Create vulkan instance");
         let instance: ash::Instance = unsafe {
-            //TODO: Return this error
             entry
                 .create_instance(&create_info, None)
-                .expect("Instance creation error")
+                .map_err(|e| {
+                    error!("Instance creation error: {:?}", e);
+                    match e {
+                        ash::InstanceError::LoadError(e) => Error::LoaderMissing(e.join(", ")),
+                        ash::InstanceError::VkError(e) => Error::Vulkan(e)
+                    }
+                })?
         };
 
         // Setup the debug callback for the validation layer
@@ -3,21 +3,39 @@ use alignment::Align;
 
 pub mod util;
 
+mod error;
+pub use error::Error;
+
 mod window_support;
 
 mod instance;
 pub use instance::VkInstance;
 
+mod device_selection;
+pub use device_selection::DeviceSelectionPolicy;
+pub use device_selection::PhysicalDeviceInfo;
+pub use device_selection::PhysicalDeviceType;
+
 mod device;
 pub use device::VkDevice;
 pub use device::QueueFamilyIndices; // TODO: Should this be re-exported like this? Name is very general.
 pub use device::Queues; // TODO: Should this be re-exported like this? Name is very general.
 
+mod present_mode;
+pub use present_mode::PresentMode;
+
+mod composite_alpha;
+pub use composite_alpha::CompositeAlpha;
+
 mod swapchain;
 pub use swapchain::VkSwapchain;
 pub use swapchain::SwapchainInfo;
 pub use swapchain::MAX_FRAMES_IN_FLIGHT;
 
+mod gpu_timings;
+pub use gpu_timings::GpuTimings;
+pub use gpu_timings::VkGpuTimestamps;
+
 mod skia_pipeline;
 pub use skia_pipeline::VkPipeline;
 
@@ -32,9 +50,27 @@ mod image;
 pub use self::image::VkImage;
 */
 
+mod shared_context;
+pub use shared_context::VkSharedContext;
+
 mod debug_reporter;
 pub use debug_reporter::VkDebugReporter;
 
+mod vulkan_renderer;
+pub use vulkan_renderer::VkRenderer;
+
+mod software_window_support;
+
+mod software_renderer;
+pub use software_renderer::SoftwareRenderer;
+
 mod renderer;
 pub use renderer::RendererBuilder;
-pub use renderer::Renderer;
\ No newline at end of file
+pub use renderer::Renderer;
+pub use renderer::RendererBackend;
+
+mod captured_frame;
+pub use captured_frame::CapturedFrame;
+
+mod headless_renderer;
+pub use headless_renderer::HeadlessRenderer;
\ No newline at end of file
@@ -0,0 +1,47 @@
+use ash::vk;
+
+/// Controls how frames are queued for display. See the vulkan documentation for VkPresentModeKHR
+/// for details.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PresentMode {
+    /// Frames are shown as soon as they are presented. Uncapped framerate, may tear
+    Immediate,
+
+    /// Vsync without blocking, the newest frame replaces any frame that is waiting to be shown.
+    /// Uncapped framerate, does not tear
+    Mailbox,
+
+    /// Vsync, presenting blocks when frames are produced faster than the display refresh rate.
+    /// Always supported
+    Fifo,
+
+    /// Like Fifo, but a late frame is shown immediately instead of waiting for the next vertical
+    /// blank. May tear when the framerate drops below the refresh rate
+    FifoRelaxed
+}
+
+impl PresentMode {
+    pub fn to_vk(self) -> vk::PresentModeKHR {
+        match self {
+            PresentMode::Immediate => vk::PresentModeKHR::IMMEDIATE,
+            PresentMode::Mailbox => vk::PresentModeKHR::MAILBOX,
+            PresentMode::Fifo => vk::PresentModeKHR::FIFO,
+            PresentMode::FifoRelaxed => vk::PresentModeKHR::FIFO_RELAXED
+        }
+    }
+
+    pub fn from_vk(present_mode: vk::PresentModeKHR) -> Option<PresentMode> {
+        match present_mode {
+            vk::PresentModeKHR::IMMEDIATE => Some(PresentMode::Immediate),
+            vk::PresentModeKHR::MAILBOX => Some(PresentMode::Mailbox),
+            vk::PresentModeKHR::FIFO => Some(PresentMode::Fifo),
+            vk::PresentModeKHR::FIFO_RELAXED => Some(PresentMode::FifoRelaxed),
+            _ => None
+        }
+    }
+
+    /// The present modes to try, in order, if none are specified. Prefers low latency vsync.
+    pub fn default_priority() -> Vec<PresentMode> {
+        vec![PresentMode::Mailbox, PresentMode::Fifo]
+    }
+}
@@ -1,30 +1,49 @@
-
 use std::ffi::CString;
 
-use ash::version::DeviceV1_0;
-use ash::prelude::VkResult;
-
-use std::mem::ManuallyDrop;
-use ash::vk;
-
+use super::Error;
+use super::DeviceSelectionPolicy;
+use super::PhysicalDeviceInfo;
 use super::VkInstance;
 use super::VkDevice;
-use super::VkSkiaContext;
-use super::VkSwapchain;
-use super::VkPipeline;
-use super::MAX_FRAMES_IN_FLIGHT;
-
+use super::PresentMode;
+use super::CompositeAlpha;
+use super::GpuTimings;
+use super::HeadlessRenderer;
+use super::CapturedFrame;
+use super::VkRenderer;
+use super::SoftwareRenderer;
+
+/// Selects how the renderer draws to the window
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RendererBackend {
+    /// Draw with vulkan. Fails if vulkan can't be initialized
+    Vulkan,
+
+    /// Draw with skia's CPU rasterizer and copy the pixels to the window. Does not require a GPU
+    Software,
+
+    /// Try vulkan first, and fall back to software rendering if vulkan initialization fails
+    VulkanWithSoftwareFallback
+}
 
 pub struct RendererBuilder {
     app_name: CString,
-    use_vulkan_debug_layer: bool
+    use_vulkan_debug_layer: bool,
+    backend: RendererBackend,
+    device_selection_policy: DeviceSelectionPolicy,
+    present_mode_priority: Vec<PresentMode>,
+    composite_alpha: CompositeAlpha
 }
 
 impl RendererBuilder {
     pub fn new() -> Self {
         RendererBuilder {
             app_name: CString::new("Skulpin").unwrap(),
-            use_vulkan_debug_layer: false
+            use_vulkan_debug_layer: false,
+            backend: RendererBackend::Vulkan,
+            device_selection_policy: DeviceSelectionPolicy::default(),
+            present_mode_priority: PresentMode::default_priority(),
+            composite_alpha: CompositeAlpha::Opaque
         }
     }
 
@@ -38,190 +57,231 @@ impl RendererBuilder {
         self
     }
 
-    pub fn build(&self, window: &winit::window::Window) -> VkResult<Renderer> {
-        Renderer::new(&self.app_name, window, self.use_vulkan_debug_layer)
+    pub fn backend(mut self, backend: RendererBackend) -> RendererBuilder {
+        self.backend = backend;
+        self
     }
-}
 
-pub struct Renderer {
-    instance: ManuallyDrop<VkInstance>,
-    device: ManuallyDrop<VkDevice>,
+    /// Determines which GPU is used when there is more than one
+    pub fn device_selection_policy(mut self, device_selection_policy: DeviceSelectionPolicy) -> RendererBuilder {
+        self.device_selection_policy = device_selection_policy;
+        self
+    }
+
+    /// The present modes to try, in order of preference. FIFO is used if none are supported. Use
+    /// `[PresentMode::Fifo]` for vsync, or `[PresentMode::Immediate, PresentMode::Mailbox]` to run
+    /// uncapped. Has no effect on software rendering.
+    pub fn present_mode_priority(mut self, present_mode_priority: Vec<PresentMode>) -> RendererBuilder {
+        self.present_mode_priority = present_mode_priority;
+        self
+    }
 
-    skia_context: ManuallyDrop<VkSkiaContext>,
+    /// How the window's alpha channel is composited with the desktop. The window must be created
+    /// transparent for anything other than `CompositeAlpha::Opaque` (the default) to have an effect.
+    /// Falls back to a supported mode if the surface doesn't support it. Has no effect on software
+    /// rendering.
+    pub fn composite_alpha(mut self, composite_alpha: CompositeAlpha) -> RendererBuilder {
+        self.composite_alpha = composite_alpha;
+        self
+    }
 
-    swapchain: ManuallyDrop<VkSwapchain>,
-    pipeline: ManuallyDrop<VkPipeline>,
+    /// List the GPUs that could be used, along with the score the current device selection policy
+    /// gives them. This creates a temporary vulkan instance. Support for presenting to a window is
+    /// not checked, so a device listed as suitable may still be rejected by `build`.
+    pub fn enumerate_devices(&self) -> Result<Vec<PhysicalDeviceInfo>, Error> {
+        let instance = VkInstance::new_headless(&self.app_name, self.use_vulkan_debug_layer)?;
+        VkDevice::enumerate_physical_devices(&instance, &self.device_selection_policy)
+    }
 
-    // Increase until > MAX_FRAMES_IN_FLIGHT, then set to 0, or -1 if no frame drawn yet
-    sync_frame_index: usize,
+    pub fn build(&self, window: &winit::window::Window) -> Result<Renderer, Error> {
+        match self.backend {
+            RendererBackend::Vulkan => {
+                Renderer::new(
+                    &self.app_name,
+                    window,
+                    self.use_vulkan_debug_layer,
+                    &self.device_selection_policy,
+                    self.present_mode_priority.clone(),
+                    self.composite_alpha
+                )
+            },
+            RendererBackend::Software => {
+                Renderer::new_software(window)
+            },
+            RendererBackend::VulkanWithSoftwareFallback => {
+                let renderer = Renderer::new(
+                    &self.app_name,
+                    window,
+                    self.use_vulkan_debug_layer,
+                    &self.device_selection_policy,
+                    self.present_mode_priority.clone(),
+                    self.composite_alpha
+                );
+
+                match renderer {
+                    Ok(renderer) => Ok(renderer),
+                    Err(e) => {
+                        // A failed attempt has already destroyed the surface, device and instance it
+                        // created, so nothing vulkan is left attached to the window
+                        warn!("Failed to initialize vulkan renderer ({}), falling back to software rendering", e);
+                        Renderer::new_software(window)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Create a renderer that draws offscreen to a surface of the given size in pixels. No window
+    /// or VK_KHR_surface support is required
+    pub fn build_headless(&self, width: u32, height: u32) -> Result<HeadlessRenderer, Error> {
+        HeadlessRenderer::new(&self.app_name, width, height, self.use_vulkan_debug_layer, &self.device_selection_policy)
+    }
+}
+
+enum RendererImpl {
+    Vulkan(VkRenderer),
+    Software(SoftwareRenderer)
+}
+
+/// Draws to a window with either vulkan or software rendering, depending on how it was built
+pub struct Renderer {
+    renderer_impl: RendererImpl
 }
 
 impl Renderer {
+    /// Create a renderer that draws with vulkan
     pub fn new(
         app_name: &CString,
         window: &winit::window::Window,
-        use_vulkan_debug_layer: bool
-    ) -> VkResult<Renderer> {
-        let instance = ManuallyDrop::new(VkInstance::new(app_name, use_vulkan_debug_layer)?);
-        let device = ManuallyDrop::new(VkDevice::new(&instance, window)?);
-        let mut skia_context = ManuallyDrop::new(VkSkiaContext::new(&instance, &device));
-        let swapchain = ManuallyDrop::new(VkSwapchain::new(&instance, &device, window)?);
-        let pipeline = ManuallyDrop::new(VkPipeline::new(&device, &swapchain, &mut skia_context)?);
-        let sync_frame_index = 0;
-
+        use_vulkan_debug_layer: bool,
+        device_selection_policy: &DeviceSelectionPolicy,
+        present_mode_priority: Vec<PresentMode>,
+        composite_alpha: CompositeAlpha
+    ) -> Result<Renderer, Error> {
+        let renderer = VkRenderer::new(
+            app_name,
+            window,
+            use_vulkan_debug_layer,
+            device_selection_policy,
+            present_mode_priority,
+            composite_alpha
+        )?;
         Ok(Renderer {
-            instance,
-            device,
-            skia_context,
-            swapchain,
-            pipeline,
-            sync_frame_index
+            renderer_impl: RendererImpl::Vulkan(renderer)
         })
     }
 
-    pub fn draw<F : FnOnce(&mut skia_safe::Canvas)>(
-        &mut self,
-        window: &winit::window::Window,
-        f: F
-    ) -> VkResult<()> {
-        let result = self.do_draw(window, f);
-        if let Err(e) = result {
-            match e {
-                ash::vk::Result::ERROR_OUT_OF_DATE_KHR => {
-
-                    //TODO: Clean the do_draw stuff up
-                    //TODO: How does it work to render from another thread?
-                    unsafe {
-                        self.device.logical_device.device_wait_idle()?;
-                        ManuallyDrop::drop(&mut self.pipeline);
-                        ManuallyDrop::drop(&mut self.swapchain);
-                    }
-
-                    self.swapchain = ManuallyDrop::new(VkSwapchain::new(&self.instance, &self.device, window)?);
-                    self.pipeline = ManuallyDrop::new(VkPipeline::new(&self.device, &self.swapchain, &mut self.skia_context)?);
-                    Ok(())
-                },
-                ash::vk::Result::SUCCESS => {
-                    Ok(())
-                },
-                ash::vk::Result::SUBOPTIMAL_KHR => {
-                    Ok(())
-                },
-                _ => {
-                    warn!("Unexpected rendering error");
-                    Err(e)
-                }
-            }
-        } else {
-            Ok(())
-        }
+    /// Create a renderer that draws with skia's CPU rasterizer
+    pub fn new_software(
+        window: &winit::window::Window
+    ) -> Result<Renderer, Error> {
+        let renderer = SoftwareRenderer::new(window)?;
+        Ok(Renderer {
+            renderer_impl: RendererImpl::Software(renderer)
+        })
     }
 
-    fn do_draw<F : FnOnce(&mut skia_safe::Canvas)>(
-        &mut self,
+    /// Create a renderer for another window. With vulkan, the new renderer shares this renderer's
+    /// instance, device and skia context, and only creates a surface, swapchain and pipeline for
+    /// the window. Either renderer may be dropped first. The composite alpha is per window, since
+    /// only transparent windows should use anything other than `CompositeAlpha::Opaque`
+    pub fn create_renderer_for_window(
+        &self,
         window: &winit::window::Window,
-        f: F
-    )
-        -> VkResult<()>
-    {
-        let frame_fence = self.swapchain.in_flight_fences[self.sync_frame_index];
-
-        //TODO: Dont lock up forever (don't use std::u64::MAX)
-        //TODO: Can part of this run in a separate thread from the window pump?
-        //TODO: Explore an option that ensures we receive the same skia canvas back every draw call.
-        // This may require a copy from a surface that is not use in the swapchain into one that is
-
-        // Wait if two frame are already in flight
-        unsafe {
-            self.device.logical_device.wait_for_fences(&[frame_fence], true, std::u64::MAX)?;
-            self.device.logical_device.reset_fences(&[frame_fence])?;
-        }
-
-        let (present_index, _is_suboptimal) = unsafe {
-            self.swapchain
-                .swapchain_loader
-                .acquire_next_image(
-                    self.swapchain.swapchain,
-                    std::u64::MAX,
-                    self.swapchain.image_available_semaphores[self.sync_frame_index],
-                    vk::Fence::null(),
-                )?
+        composite_alpha: CompositeAlpha
+    ) -> Result<Renderer, Error> {
+        let renderer_impl = match &self.renderer_impl {
+            RendererImpl::Vulkan(renderer) => RendererImpl::Vulkan(VkRenderer::new_shared(
+                renderer.shared_context().clone(),
+                window,
+                renderer.present_mode_priority().to_vec(),
+                composite_alpha
+            )?),
+            RendererImpl::Software(_) => RendererImpl::Software(SoftwareRenderer::new(window)?)
         };
 
-        {
-            let surface = self.pipeline.skia_surface(present_index as usize);
-            let mut canvas = surface.surface.canvas();
-
-            // To handle hi-dpi displays, we need to compare the logical size of the window with the
-            // actual canvas size. Critically, the canvas size won't necessarily be the size of the
-            // window in physical pixels.
-            let window_size = window.inner_size();
-            let scale = (
-                (self.swapchain.swapchain_info.extents.width as f64 / window_size.width) as f32,
-                (self.swapchain.swapchain_info.extents.height as f64 / window_size.height) as f32
-            );
-
-            canvas.reset_matrix();
-            canvas.scale(scale);
+        Ok(Renderer {
+            renderer_impl
+        })
+    }
 
-            f(&mut canvas);
+    /// Returns the backend in use, either `Vulkan` or `Software`
+    pub fn backend(&self) -> RendererBackend {
+        match self.renderer_impl {
+            RendererImpl::Vulkan(_) => RendererBackend::Vulkan,
+            RendererImpl::Software(_) => RendererBackend::Software
+        }
+    }
 
-            canvas.flush();
+    /// How long the GPU spent drawing a recent frame, split into skia's work and the blit to the
+    /// swapchain. Results lag a few frames behind. Returns None for software rendering, if the
+    /// device doesn't support timestamp queries, or if no results are available yet.
+    pub fn gpu_timings(&self) -> Option<GpuTimings> {
+        match &self.renderer_impl {
+            RendererImpl::Vulkan(renderer) => renderer.gpu_timings(),
+            RendererImpl::Software(_) => None
         }
+    }
 
-        let wait_semaphores = [self.swapchain.image_available_semaphores[self.sync_frame_index]];
-        let signal_semaphores = [self.swapchain.render_finished_semaphores[self.sync_frame_index]];
-
-        let wait_dst_stage_mask = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-        let command_buffers = [self.pipeline.command_buffers[present_index as usize]];
-
-        //add fence to queue submit
-        let submit_info = [
-            vk::SubmitInfo::builder()
-                .wait_semaphores(&wait_semaphores)
-                .signal_semaphores(&signal_semaphores)
-                .wait_dst_stage_mask(&wait_dst_stage_mask)
-                .command_buffers(&command_buffers)
-                .build()
-        ];
-
-        unsafe {
-            self.device.logical_device
-                .queue_submit(self.device.queues.graphics_queue, &submit_info, frame_fence)?;
+    /// The present mode currently in use. Returns None for software rendering, which always
+    /// presents immediately
+    pub fn present_mode(&self) -> Option<PresentMode> {
+        match &self.renderer_impl {
+            RendererImpl::Vulkan(renderer) => renderer.present_mode(),
+            RendererImpl::Software(_) => None
         }
+    }
 
-        let wait_semaphors = [self.swapchain.render_finished_semaphores[self.sync_frame_index]];
-        let swapchains = [self.swapchain.swapchain];
-        let image_indices = [present_index];
-        let present_info = vk::PresentInfoKHR::builder()
-            .wait_semaphores(&wait_semaphors) // &base.rendering_complete_semaphore)
-            .swapchains(&swapchains)
-            .image_indices(&image_indices);
-
-        unsafe {
-            self.swapchain.swapchain_loader
-                .queue_present(self.device.queues.present_queue, &present_info)?;
+    /// The composite alpha the swapchain is currently using, which may differ from the requested
+    /// one if the surface doesn't support it. Returns None for software rendering
+    pub fn composite_alpha(&self) -> Option<CompositeAlpha> {
+        match &self.renderer_impl {
+            RendererImpl::Vulkan(renderer) => renderer.composite_alpha(),
+            RendererImpl::Software(_) => None
         }
+    }
 
-        self.sync_frame_index = (self.sync_frame_index + 1) % MAX_FRAMES_IN_FLIGHT;
+    /// Change the preferred present modes at runtime (i.e. to toggle vsync). The swapchain is
+    /// recreated. Has no effect on software rendering.
+    pub fn set_present_mode_priority(
+        &mut self,
+        window: &winit::window::Window,
+        present_mode_priority: Vec<PresentMode>
+    ) -> Result<(), Error> {
+        match &mut self.renderer_impl {
+            RendererImpl::Vulkan(renderer) => renderer.set_present_mode_priority(window, present_mode_priority),
+            RendererImpl::Software(_) => Ok(())
+        }
+    }
 
-        Ok(())
+    /// Returns false while nothing can be drawn because the window's surface has zero size (i.e. it
+    /// is minimized). Some platforms don't report this as a resize to zero. Always true for
+    /// software rendering
+    pub fn poll_surface_drawable(&mut self, window: &winit::window::Window) -> Result<bool, Error> {
+        match &mut self.renderer_impl {
+            RendererImpl::Vulkan(renderer) => renderer.poll_surface_drawable(window),
+            RendererImpl::Software(_) => Ok(true)
+        }
     }
-}
 
-impl Drop for Renderer {
-    fn drop(&mut self) {
-        info!("destroying Renderer");
-
-        unsafe {
-            self.device.logical_device.device_wait_idle().unwrap();
-            ManuallyDrop::drop(&mut self.pipeline);
-            ManuallyDrop::drop(&mut self.swapchain);
-            ManuallyDrop::drop(&mut self.skia_context);
-            ManuallyDrop::drop(&mut self.device);
-            ManuallyDrop::drop(&mut self.instance);
+    pub fn draw<F : FnOnce(&mut skia_safe::Canvas)>(
+        &mut self,
+        window: &winit::window::Window,
+        f: F
+    ) -> Result<(), Error> {
+        match &mut self.renderer_impl {
+            RendererImpl::Vulkan(renderer) => renderer.draw(window, f),
+            RendererImpl::Software(renderer) => renderer.draw(window, f)
         }
+    }
 
-        info!("destroyed Renderer");
+    /// Read back the pixels of the most recently drawn frame. The returned frame is the size of
+    /// the window in physical pixels. Returns None if no frame has been drawn since the renderer
+    /// was created or the window was last resized.
+    pub fn capture_frame(&mut self) -> Result<Option<CapturedFrame>, Error> {
+        match &mut self.renderer_impl {
+            RendererImpl::Vulkan(renderer) => renderer.capture_frame(),
+            RendererImpl::Software(renderer) => renderer.capture_frame()
+        }
     }
 }
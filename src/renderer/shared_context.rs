@@ -0,0 +1,59 @@
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::mem::ManuallyDrop;
+
+use ash::version::DeviceV1_0;
+
+use super::VkInstance;
+use super::VkDevice;
+use super::VkSkiaContext;
+use super::Error;
+use super::DeviceSelectionPolicy;
+
+/// The vulkan instance, device and skia context. These are shared (with an `Rc`) by the
+/// `VkRenderer` of every window, while each window has its own surface, swapchain and pipeline.
+pub struct VkSharedContext {
+    pub instance: ManuallyDrop<VkInstance>,
+    pub device: ManuallyDrop<VkDevice>,
+
+    // Borrowed mutably while a window's pipeline creates its skia surfaces
+    pub skia_context: ManuallyDrop<RefCell<VkSkiaContext>>
+}
+
+impl VkSharedContext {
+    /// Create the instance and select a device that can present to the given window. The window's
+    /// surface is left in `device.surface`.
+    pub fn new(
+        app_name: &CString,
+        window: &winit::window::Window,
+        use_vulkan_debug_layer: bool,
+        device_selection_policy: &DeviceSelectionPolicy
+    ) -> Result<VkSharedContext, Error> {
+        // Plain locals, so that if a later step fails the earlier ones are dropped in reverse order.
+        // They are only wrapped in ManuallyDrop once everything has been created
+        let instance = VkInstance::new(app_name, use_vulkan_debug_layer)?;
+        let device = VkDevice::new(&instance, window, device_selection_policy)?;
+        let skia_context = VkSkiaContext::new(&instance, &device)?;
+
+        Ok(VkSharedContext {
+            instance: ManuallyDrop::new(instance),
+            device: ManuallyDrop::new(device),
+            skia_context: ManuallyDrop::new(RefCell::new(skia_context))
+        })
+    }
+}
+
+impl Drop for VkSharedContext {
+    fn drop(&mut self) {
+        info!("destroying VkSharedContext");
+
+        unsafe {
+            self.device.logical_device.device_wait_idle().unwrap();
+            ManuallyDrop::drop(&mut self.skia_context);
+            ManuallyDrop::drop(&mut self.device);
+            ManuallyDrop::drop(&mut self.instance);
+        }
+
+        info!("destroyed VkSharedContext");
+    }
+}
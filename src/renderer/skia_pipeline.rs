@@ -14,6 +14,9 @@ use super::SwapchainInfo;
 use super::QueueFamilyIndices;
 use crate::renderer::VkBuffer;
 use crate::renderer::skia_support::{VkSkiaContext, VkSkiaSurface};
+use crate::renderer::Error;
+use crate::renderer::VkGpuTimestamps;
+use super::MAX_FRAMES_IN_FLIGHT;
 
 #[derive(Clone, Debug, Copy)]
 struct Vertex {
@@ -73,11 +76,14 @@ pub struct VkPipeline {
     pub skia_surfaces: Vec<VkSkiaSurface>,
     pub descriptor_pool: vk::DescriptorPool,
     pub descriptor_sets: Vec<vk::DescriptorSet>,
-    pub image_sampler: vk::Sampler
+    pub image_sampler: vk::Sampler,
+
+    // None if the device doesn't support timestamp queries
+    pub gpu_timestamps: Option<VkGpuTimestamps>
 }
 
 impl VkPipeline {
-    pub fn new(device: &VkDevice, swapchain: &VkSwapchain, skia_context: &mut VkSkiaContext) -> VkResult<Self> {
+    pub fn new(device: &VkDevice, swapchain: &VkSwapchain, skia_context: &mut VkSkiaContext) -> Result<Self, Error> {
         let mut pipeline_resources = None;
 
         let descriptor_set_layout = Self::create_descriptor_set_layout(&device.logical_device)?;
@@ -97,7 +103,7 @@ impl VkPipeline {
             })
         })?;
 
-        //TODO: Return error if not set
+        // create_pipeline always calls the callback if it succeeds
         let pipeline_resources = pipeline_resources.unwrap();
         let pipeline_layout = pipeline_resources.pipeline_layout;
         let renderpass = pipeline_resources.renderpass;
@@ -108,7 +114,7 @@ impl VkPipeline {
             &swapchain.swapchain_image_views,
             &swapchain.swapchain_info,
             &pipeline_resources.renderpass
-        );
+        )?;
 
         let command_pool = Self::create_command_pool(
             &device.logical_device,
@@ -137,10 +143,9 @@ impl VkPipeline {
 
         info!("Create skia surfaces with extent: {:?}", swapchain.swapchain_info.extents);
 
-        //TODO: Figure out how to return error here
-        let skia_surfaces : Vec<_> = (0..swapchain.swapchain_info.image_count).map(|_| {
-            VkSkiaSurface::new(device, skia_context, &swapchain.swapchain_info.extents).unwrap()
-        }).collect();
+        let skia_surfaces = (0..swapchain.swapchain_info.image_count).map(|_| {
+            VkSkiaSurface::new(device, skia_context, &swapchain.swapchain_info.extents)
+        }).collect::<Result<Vec<_>, Error>>()?;
 
         let image_sampler = VkSkiaSurface::create_sampler(
             &device.logical_device
@@ -160,6 +165,12 @@ impl VkPipeline {
             &skia_surfaces
         )?;
 
+        let gpu_timestamps = VkGpuTimestamps::new(
+            device,
+            &command_pool,
+            MAX_FRAMES_IN_FLIGHT
+        )?;
+
         for i in 0..swapchain.swapchain_info.image_count {
             Self::record_command_buffer(
                 &device.logical_device,
@@ -190,7 +201,8 @@ impl VkPipeline {
             skia_surfaces,
             descriptor_pool,
             descriptor_sets,
-            image_sampler
+            image_sampler,
+            gpu_timestamps
         })
     }
 
@@ -216,11 +228,11 @@ impl VkPipeline {
         }
     }
 
-    fn create_fixed_function_state<F : FnMut(&FixedFunctionState) -> VkResult<()>>(
+    fn create_fixed_function_state<F : FnMut(&FixedFunctionState) -> Result<(), Error>>(
         swapchain_info: &SwapchainInfo,
         mut f: F
     )
-        -> VkResult<()>
+        -> Result<(), Error>
     {
         let vertex_input_assembly_state_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
             .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
@@ -283,17 +295,42 @@ impl VkPipeline {
             .rasterization_samples(vk::SampleCountFlags::TYPE_1);
 
         // Applies to the current framebuffer
+        let color_blend_attachment_state = match swapchain_info.composite_alpha {
+            // The frame is already premultiplied, blend it as such so that it is copied unchanged
+            // onto the transparent clear color
+            vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED => {
+                vk::PipelineColorBlendAttachmentState::builder()
+                    .color_write_mask(vk::ColorComponentFlags::all())
+                    .blend_enable(true)
+                    .src_color_blend_factor(vk::BlendFactor::ONE)
+                    .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+                    .color_blend_op(vk::BlendOp::ADD)
+                    .src_alpha_blend_factor(vk::BlendFactor::ONE)
+                    .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+                    .alpha_blend_op(vk::BlendOp::ADD)
+            },
+            // The fragment shader un-premultiplies, so write its output directly. Blending would
+            // multiply by alpha a second time
+            vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED => {
+                vk::PipelineColorBlendAttachmentState::builder()
+                    .color_write_mask(vk::ColorComponentFlags::all())
+                    .blend_enable(false)
+            },
+            _ => {
+                vk::PipelineColorBlendAttachmentState::builder()
+                    .color_write_mask(vk::ColorComponentFlags::all())
+                    .blend_enable(true)
+                    .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+                    .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+                    .color_blend_op(vk::BlendOp::ADD)
+                    .src_alpha_blend_factor(vk::BlendFactor::ONE)
+                    .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
+                    .alpha_blend_op(vk::BlendOp::ADD)
+            }
+        };
+
         let color_blend_attachment_states = [
-            vk::PipelineColorBlendAttachmentState::builder()
-                .color_write_mask(vk::ColorComponentFlags::all())
-                .blend_enable(true)
-                .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
-                .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
-                .color_blend_op(vk::BlendOp::ADD)
-                .src_alpha_blend_factor(vk::BlendFactor::ONE)
-                .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
-                .alpha_blend_op(vk::BlendOp::ADD)
-                .build()
+            color_blend_attachment_state.build()
         ];
 
         // Applies globally
@@ -317,11 +354,11 @@ impl VkPipeline {
         f(&fixed_function_state)
     }
 
-    fn create_renderpass_create_info<F : FnMut(&vk::RenderPassCreateInfo) -> VkResult<()>>(
+    fn create_renderpass_create_info<F : FnMut(&vk::RenderPassCreateInfo) -> Result<(), Error>>(
         swapchain_info: &SwapchainInfo,
         mut f: F
     )
-        -> VkResult<()>
+        -> Result<(), Error>
     {
         let renderpass_attachments = [
             vk::AttachmentDescription::builder()
@@ -369,13 +406,13 @@ impl VkPipeline {
 
     fn create_pipeline<F : FnMut(PipelineResources)>(
         logical_device: &ash::Device,
-        _swapchain_info: &SwapchainInfo,
+        swapchain_info: &SwapchainInfo,
         fixed_function_state: &FixedFunctionState,
         renderpass_create_info: &vk::RenderPassCreateInfo,
         descriptor_set_layout: vk::DescriptorSetLayout,
         mut f: F
     )
-        -> VkResult<()>
+        -> Result<(), Error>
     {
         //
         // Load Shaders
@@ -384,9 +421,16 @@ impl VkPipeline {
             logical_device,
             &include_bytes!("../../shaders/skia.vert.spv")[..])?;
 
+        // Skia draws premultiplied colors. Undo that if the compositor will multiply by alpha itself
+        let fragment_shader_code = if swapchain_info.composite_alpha == vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED {
+            &include_bytes!("../../shaders/skia_unpremultiply.frag.spv")[..]
+        } else {
+            &include_bytes!("../../shaders/skia.frag.spv")[..]
+        };
+
         let fragment_shader_module = Self::load_shader_module(
             logical_device,
-            &include_bytes!("../../shaders/skia.frag.spv")[..])?;
+            fragment_shader_code)?;
 
         let shader_entry_name = CString::new("main").unwrap();
         let shader_stage_create_infos = [
@@ -464,16 +508,15 @@ impl VkPipeline {
         Ok(())
     }
 
-    fn load_shader_module(logical_device: &ash::Device, data: &[u8]) -> VkResult<vk::ShaderModule> {
+    fn load_shader_module(logical_device: &ash::Device, data: &[u8]) -> Result<vk::ShaderModule, Error> {
         let mut spv_file = std::io::Cursor::new(data);
-        //TODO: Pass this error up
-        let code = super::util::read_spv(&mut spv_file).expect("Failed to read vertex shader spv file");
+        let code = super::util::read_spv(&mut spv_file).map_err(Error::ShaderLoading)?;
         let shader_info = vk::ShaderModuleCreateInfo::builder().code(&code);
 
-        unsafe {
+        Ok(unsafe {
             logical_device
-                .create_shader_module(&shader_info, None)
-        }
+                .create_shader_module(&shader_info, None)?
+        })
     }
 
     fn create_framebuffers(
@@ -482,7 +525,7 @@ impl VkPipeline {
         swapchain_info: &SwapchainInfo,
         renderpass: &vk::RenderPass
     )
-        -> Vec<vk::Framebuffer>
+        -> VkResult<Vec<vk::Framebuffer>>
     {
         swapchain_image_views
             .iter()
@@ -496,10 +539,8 @@ impl VkPipeline {
                     .layers(1);
 
                 unsafe {
-                    //TODO: Pass this error up
                     logical_device
                         .create_framebuffer(&frame_buffer_create_info, None)
-                        .unwrap()
                 }
             })
             .collect()
@@ -658,10 +699,16 @@ impl VkPipeline {
     {
         let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder();
 
+        // Clear to transparent if the compositor uses the alpha channel
+        let clear_alpha = match swapchain_info.composite_alpha {
+            vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED | vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED => 0.0,
+            _ => 1.0
+        };
+
         let clear_values = [
             vk::ClearValue {
                 color: vk::ClearColorValue {
-                    float32: [0.0, 0.0, 0.0, 1.0],
+                    float32: [0.0, 0.0, 0.0, clear_alpha],
                 },
             },
         ];
@@ -680,7 +727,7 @@ impl VkPipeline {
             logical_device
                 .begin_command_buffer(*command_buffer, &command_buffer_begin_info)?;
 
-            let image = VkSkiaSurface::get_image_from_skia_texture(&skia_surface.texture);
+            let image = skia_surface.image;
 
             //TODO: Pull into helper function
             let image_memory_barrier = ash::vk::ImageMemoryBarrier::builder()
@@ -789,6 +836,9 @@ impl Drop for VkPipeline {
             ManuallyDrop::drop(&mut self.vertex_buffer);
             ManuallyDrop::drop(&mut self.index_buffer);
 
+            // Destroys the query pool. The command buffers are freed with the command pool
+            self.gpu_timestamps = None;
+
             self.device.destroy_command_pool(self.command_pool, None);
 
             for frame_buffer in &self.frame_buffers {
@@ -4,6 +4,7 @@ use ash::prelude::VkResult;
 
 use super::VkInstance;
 use super::VkDevice;
+use super::Error;
 
 use std::ffi::c_void;
 
@@ -16,7 +17,7 @@ pub struct VkSkiaContext {
 }
 
 impl VkSkiaContext {
-    pub fn new(instance: &VkInstance, device: &VkDevice) -> Self {
+    pub fn new(instance: &VkInstance, device: &VkDevice) -> Result<Self, Error> {
         use vk::Handle;
 
         let get_proc = |of| unsafe {
@@ -42,11 +43,12 @@ impl VkSkiaContext {
             )
         };
 
-        let context = skia_safe::gpu::Context::new_vulkan(&backend_context).unwrap();
+        let context = skia_safe::gpu::Context::new_vulkan(&backend_context)
+            .ok_or(Error::SkiaContextCreation)?;
 
-        VkSkiaContext {
+        Ok(VkSkiaContext {
             context
-        }
+        })
     }
 
     pub unsafe fn get_proc(
@@ -74,18 +76,22 @@ pub struct VkSkiaSurface {
 
     pub surface: skia_safe::Surface,
     pub texture: skia_safe::gpu::BackendTexture,
+
+    // The vulkan image backing the texture
+    pub image: vk::Image,
     pub image_view: vk::ImageView,
 }
 
 impl VkSkiaSurface {
 
-    pub fn get_image_from_skia_texture(texture: &skia_safe::gpu::BackendTexture) -> vk::Image {
-        unsafe {
-            std::mem::transmute(texture.vulkan_image_info().unwrap().image)
-        }
+    /// Returns None if the texture is not backed by a vulkan image
+    pub fn get_image_from_skia_texture(texture: &skia_safe::gpu::BackendTexture) -> Option<vk::Image> {
+        texture.vulkan_image_info().map(|image_info| unsafe {
+            std::mem::transmute(image_info.image)
+        })
     }
 
-    pub fn new(device: &VkDevice, context: &mut VkSkiaContext, extent: &vk::Extent2D) -> VkResult<Self> {
+    pub fn new(device: &VkDevice, context: &mut VkSkiaContext, extent: &vk::Extent2D) -> Result<Self, Error> {
 
         let image_info = skia_safe::ImageInfo::new_n32_premul((extent.width as i32, extent.height as i32), None);
 
@@ -97,10 +103,12 @@ impl VkSkiaSurface {
             skia_safe::gpu::SurfaceOrigin::TopLeft,
             None,
             false,
-        ).unwrap();
+        ).ok_or(Error::SkiaSurfaceCreation)?;
 
-        let texture = surface.get_backend_texture(skia_safe::surface::BackendHandleAccess::FlushRead).as_ref().unwrap().clone();
-        let image = Self::get_image_from_skia_texture(&texture);
+        let texture = surface.get_backend_texture(skia_safe::surface::BackendHandleAccess::FlushRead)
+            .ok_or(Error::SkiaSurfaceCreation)?;
+        let image = Self::get_image_from_skia_texture(&texture)
+            .ok_or(Error::SkiaSurfaceCreation)?;
 
         let skia_tex_image_view_info = vk::ImageViewCreateInfo {
             view_type: vk::ImageViewType::TYPE_2D,
@@ -131,6 +139,7 @@ impl VkSkiaSurface {
             device: device.logical_device.clone(),
             surface,
             texture,
+            image,
             image_view,
         })
     }
@@ -0,0 +1,110 @@
+use super::CapturedFrame;
+use super::Error;
+use super::software_window_support::SoftwarePresenter;
+
+/// Draws to a window using skia's CPU rasterizer. This is used when vulkan is not available (i.e.
+/// VMs or remote desktops without a GPU). Each frame is drawn to a raster surface, read into a
+/// pixel buffer, and copied to the window.
+pub struct SoftwareRenderer {
+    presenter: SoftwarePresenter,
+
+    // Recreated when the window size changes
+    surface: Option<skia_safe::Surface>,
+
+    // BGRA pixels that are copied to the window
+    pixels: Vec<u8>,
+
+    // True if the surface holds a frame that can be captured
+    has_drawn_frame: bool
+}
+
+impl SoftwareRenderer {
+    pub fn new(window: &winit::window::Window) -> Result<SoftwareRenderer, Error> {
+        let presenter = SoftwarePresenter::new(window)?;
+
+        Ok(SoftwareRenderer {
+            presenter,
+            surface: None,
+            pixels: vec![],
+            has_drawn_frame: false
+        })
+    }
+
+    pub fn draw<F : FnOnce(&mut skia_safe::Canvas)>(
+        &mut self,
+        window: &winit::window::Window,
+        f: F
+    ) -> Result<(), Error> {
+        let hidpi_factor = window.hidpi_factor();
+        let physical_size = window.inner_size().to_physical(hidpi_factor);
+        let width = physical_size.width.round() as u32;
+        let height = physical_size.height.round() as u32;
+
+        // Nothing to draw to (i.e. the window is minimized)
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        let needs_new_surface = match &self.surface {
+            Some(surface) => surface.width() as u32 != width || surface.height() as u32 != height,
+            None => true
+        };
+
+        if needs_new_surface {
+            info!("Create software surface with extent: {} {}", width, height);
+            let image_info = Self::image_info(width, height);
+            self.surface = Some(skia_safe::Surface::new_raster(&image_info, None, None)
+                .ok_or(Error::SkiaSurfaceCreation)?);
+            self.pixels = vec![0; width as usize * height as usize * 4];
+            self.has_drawn_frame = false;
+        }
+
+        let surface = self.surface.as_mut().unwrap();
+
+        {
+            let mut canvas = surface.canvas();
+
+            // Match the vulkan renderer, drawing is done in logical coordinates
+            canvas.reset_matrix();
+            canvas.scale((hidpi_factor as f32, hidpi_factor as f32));
+
+            f(&mut canvas);
+
+            canvas.flush();
+        }
+
+        let row_bytes = width as usize * 4;
+        if !surface.read_pixels(&Self::image_info(width, height), &mut self.pixels, row_bytes, (0, 0)) {
+            return Err(Error::PixelReadback);
+        }
+
+        self.has_drawn_frame = true;
+        self.presenter.present(width, height, &self.pixels)
+    }
+
+    /// Read back the pixels of the most recently drawn frame
+    pub fn capture_frame(&mut self) -> Result<Option<CapturedFrame>, Error> {
+        if !self.has_drawn_frame {
+            return Ok(None);
+        }
+
+        match &mut self.surface {
+            Some(surface) => {
+                CapturedFrame::read_from_surface(surface)
+                    .map(Some)
+                    .ok_or(Error::PixelReadback)
+            },
+            None => Ok(None)
+        }
+    }
+
+    fn image_info(width: u32, height: u32) -> skia_safe::ImageInfo {
+        // This matches the pixel layout expected when copying to the window on all platforms
+        skia_safe::ImageInfo::new(
+            (width as i32, height as i32),
+            skia_safe::ColorType::BGRA8888,
+            skia_safe::AlphaType::Premul,
+            None
+        )
+    }
+}
@@ -0,0 +1,181 @@
+use super::Error;
+
+//
+// Code for copying a CPU pixel buffer to a window. Pixels are always 32-bit BGRA, top row first.
+//
+
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+pub struct SoftwarePresenter {
+    xlib: x11_dl::xlib::Xlib,
+    display: *mut x11_dl::xlib::Display,
+    window: x11_dl::xlib::Window,
+    visual: *mut x11_dl::xlib::Visual,
+    depth: i32,
+    gc: x11_dl::xlib::GC
+}
+
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+impl SoftwarePresenter {
+    pub fn new(window: &winit::window::Window) -> Result<Self, Error> {
+        use raw_window_handle::HasRawWindowHandle;
+
+        let (display, x11_window) = match window.raw_window_handle() {
+            raw_window_handle::RawWindowHandle::Xlib(window_handle) => {
+                (window_handle.display as *mut x11_dl::xlib::Display, window_handle.window)
+            },
+            _ => {
+                return Err(Error::SoftwarePresentationUnsupported(
+                    "Software rendering is only supported with X11 on this platform".to_string()
+                ));
+            }
+        };
+
+        let xlib = match x11_dl::xlib::Xlib::open() {
+            Ok(xlib) => xlib,
+            Err(e) => {
+                return Err(Error::SoftwarePresentationUnsupported(
+                    format!("Could not load xlib: {:?}", e)
+                ));
+            }
+        };
+
+        unsafe {
+            let mut attributes : x11_dl::xlib::XWindowAttributes = std::mem::zeroed();
+            (xlib.XGetWindowAttributes)(display, x11_window, &mut attributes);
+
+            let gc = (xlib.XCreateGC)(display, x11_window, 0, std::ptr::null_mut());
+
+            Ok(SoftwarePresenter {
+                xlib,
+                display,
+                window: x11_window,
+                visual: attributes.visual,
+                depth: attributes.depth,
+                gc
+            })
+        }
+    }
+
+    pub fn present(&mut self, width: u32, height: u32, pixels: &[u8]) -> Result<(), Error> {
+        unsafe {
+            let image = (self.xlib.XCreateImage)(
+                self.display,
+                self.visual,
+                self.depth as u32,
+                x11_dl::xlib::ZPixmap,
+                0,
+                pixels.as_ptr() as *mut std::os::raw::c_char,
+                width,
+                height,
+                32,
+                (width * 4) as i32
+            );
+
+            if image.is_null() {
+                return Err(Error::SoftwarePresentation("XCreateImage failed".to_string()));
+            }
+
+            (self.xlib.XPutImage)(self.display, self.window, self.gc, image, 0, 0, 0, 0, width, height);
+
+            // The pixel data is owned by the caller, don't let XDestroyImage free it
+            (*image).data = std::ptr::null_mut();
+            (self.xlib.XDestroyImage)(image);
+
+            (self.xlib.XFlush)(self.display);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+impl Drop for SoftwarePresenter {
+    fn drop(&mut self) {
+        unsafe {
+            (self.xlib.XFreeGC)(self.display, self.gc);
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub struct SoftwarePresenter {
+    hwnd: winapi::shared::windef::HWND
+}
+
+#[cfg(target_os = "windows")]
+impl SoftwarePresenter {
+    pub fn new(window: &winit::window::Window) -> Result<Self, Error> {
+        use raw_window_handle::HasRawWindowHandle;
+
+        match window.raw_window_handle() {
+            raw_window_handle::RawWindowHandle::Windows(window_handle) => {
+                Ok(SoftwarePresenter {
+                    hwnd: window_handle.hwnd as winapi::shared::windef::HWND
+                })
+            },
+            _ => Err(Error::SoftwarePresentationUnsupported(
+                "Expected a win32 window handle".to_string()
+            ))
+        }
+    }
+
+    pub fn present(&mut self, width: u32, height: u32, pixels: &[u8]) -> Result<(), Error> {
+        use winapi::um::wingdi;
+        use winapi::um::winuser;
+
+        unsafe {
+            let mut bitmap_info : wingdi::BITMAPINFO = std::mem::zeroed();
+            bitmap_info.bmiHeader.biSize = std::mem::size_of::<wingdi::BITMAPINFOHEADER>() as u32;
+            bitmap_info.bmiHeader.biWidth = width as i32;
+            // Negative height indicates the rows are top-down
+            bitmap_info.bmiHeader.biHeight = -(height as i32);
+            bitmap_info.bmiHeader.biPlanes = 1;
+            bitmap_info.bmiHeader.biBitCount = 32;
+            bitmap_info.bmiHeader.biCompression = wingdi::BI_RGB;
+
+            let hdc = winuser::GetDC(self.hwnd);
+            let result = wingdi::SetDIBitsToDevice(
+                hdc,
+                0,
+                0,
+                width,
+                height,
+                0,
+                0,
+                0,
+                height,
+                pixels.as_ptr() as *const std::os::raw::c_void,
+                &bitmap_info,
+                wingdi::DIB_RGB_COLORS
+            );
+            winuser::ReleaseDC(self.hwnd, hdc);
+
+            if result == 0 {
+                return Err(Error::SoftwarePresentation("SetDIBitsToDevice failed".to_string()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub struct SoftwarePresenter {
+
+}
+
+#[cfg(target_os = "macos")]
+impl SoftwarePresenter {
+    pub fn new(_window: &winit::window::Window) -> Result<Self, Error> {
+        //TODO: Implement presenting via a CALayer
+        Err(Error::SoftwarePresentationUnsupported(
+            "Software rendering is not yet supported on macOS".to_string()
+        ))
+    }
+
+    pub fn present(&mut self, _width: u32, _height: u32, _pixels: &[u8]) -> Result<(), Error> {
+        Err(Error::SoftwarePresentationUnsupported(
+            "Software rendering is not yet supported on macOS".to_string()
+        ))
+    }
+}
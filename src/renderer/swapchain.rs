@@ -8,12 +8,15 @@ use ash::version::DeviceV1_0;
 use super::VkInstance;
 use super::VkDevice;
 use super::QueueFamilyIndices;
+use super::PresentMode;
+use super::CompositeAlpha;
 
 pub const MAX_FRAMES_IN_FLIGHT : usize = 2;
 
 pub struct SwapchainInfo {
     pub surface_format: vk::SurfaceFormatKHR,
     pub present_mode: vk::PresentModeKHR,
+    pub composite_alpha: vk::CompositeAlphaFlagsKHR,
     pub extents: vk::Extent2D,
     pub image_count: usize
 }
@@ -34,10 +37,16 @@ pub struct VkSwapchain {
 }
 
 impl VkSwapchain {
+    /// Create a swapchain for the surface. If the surface already has a swapchain, pass it as
+    /// `old_swapchain` (otherwise null). It is retired, but must still be destroyed by the caller
     pub fn new(
         instance: &VkInstance,
         device: &VkDevice,
-        window: &winit::window::Window
+        surface: &vk::SurfaceKHR,
+        old_swapchain: vk::SwapchainKHR,
+        window: &winit::window::Window,
+        present_mode_priority: &[PresentMode],
+        composite_alpha: CompositeAlpha
     )
         -> VkResult<VkSwapchain>
     {
@@ -46,9 +55,12 @@ impl VkSwapchain {
             &device.physical_device,
             &device.logical_device,
             &device.surface_loader,
-            &device.surface,
+            surface,
+            old_swapchain,
             &device.queue_family_indices,
-            window
+            window,
+            present_mode_priority,
+            composite_alpha
         )?;
 
         let swapchain_images = unsafe {
@@ -58,32 +70,29 @@ impl VkSwapchain {
         let swapchain_image_views = Self::create_image_views(
             &device.logical_device,
             &swapchain_info,
-            &swapchain_images);
+            &swapchain_images)?;
 
-        let image_available_semaphores : Vec<_> = (0..MAX_FRAMES_IN_FLIGHT).map(|_| {
+        let image_available_semaphores = (0..MAX_FRAMES_IN_FLIGHT).map(|_| {
             let semaphore_create_info = vk::SemaphoreCreateInfo::builder();
             unsafe {
-                //TODO: Return this
-                device.logical_device.create_semaphore(&semaphore_create_info, None).unwrap()
+                device.logical_device.create_semaphore(&semaphore_create_info, None)
             }
-        }).collect();
+        }).collect::<VkResult<Vec<_>>>()?;
 
-        let render_finished_semaphores : Vec<_> = (0..MAX_FRAMES_IN_FLIGHT).map(|_| {
+        let render_finished_semaphores = (0..MAX_FRAMES_IN_FLIGHT).map(|_| {
             let semaphore_create_info = vk::SemaphoreCreateInfo::builder();
             unsafe {
-                //TODO: Return this
-                device.logical_device.create_semaphore(&semaphore_create_info, None).unwrap()
+                device.logical_device.create_semaphore(&semaphore_create_info, None)
             }
-        }).collect();
+        }).collect::<VkResult<Vec<_>>>()?;
 
-        let in_flight_fences : Vec<_> = (0..MAX_FRAMES_IN_FLIGHT).map(|_| {
+        let in_flight_fences = (0..MAX_FRAMES_IN_FLIGHT).map(|_| {
             let fence_create_info = vk::FenceCreateInfo::builder()
                 .flags(vk::FenceCreateFlags::SIGNALED);
             unsafe {
-                //TODO: Return this
-                device.logical_device.create_fence(&fence_create_info, None).unwrap()
+                device.logical_device.create_fence(&fence_create_info, None)
             }
-        }).collect();
+        }).collect::<VkResult<Vec<_>>>()?;
 
         Ok(VkSwapchain {
             device: device.logical_device.clone(),
@@ -104,8 +113,11 @@ impl VkSwapchain {
         logical_device: &ash::Device,
         surface_loader: &ash::extensions::khr::Surface,
         surface: &ash::vk::SurfaceKHR,
+        old_swapchain: vk::SwapchainKHR,
         queue_family_indices: &QueueFamilyIndices,
-        window: &winit::window::Window
+        window: &winit::window::Window,
+        present_mode_priority: &[PresentMode],
+        composite_alpha: CompositeAlpha
     )
         -> VkResult<(SwapchainInfo, khr::Swapchain, vk::SwapchainKHR)>
     {
@@ -117,11 +129,13 @@ impl VkSwapchain {
             )?;
 
         let surface_format = Self::choose_format(&available_formats);
-        let present_mode = Self::choose_present_mode(&available_present_modes);
+        let present_mode = Self::choose_present_mode(&available_present_modes, present_mode_priority);
         let extents = Self::choose_extents(&surface_capabilities, window);
+        let composite_alpha = Self::choose_composite_alpha(&surface_capabilities, composite_alpha);
 
         info!("Surface format: {:?}", surface_format);
         info!("Present mode: {:?}", present_mode);
+        info!("Composite alpha: {:?}", composite_alpha);
         info!("Extents: {:?}", extents);
 
         // "simply sticking to this minimum means that we may sometimes have to wait on the driver
@@ -136,9 +150,9 @@ impl VkSwapchain {
 
         let swapchain_loader = khr::Swapchain::new(instance, logical_device);
 
-        //TODO: old_swapchain should be specified here if appropriate
         let mut swapchain_create_info = vk::SwapchainCreateInfoKHR::builder()
             .surface(*surface)
+            .old_swapchain(old_swapchain)
             .min_image_count(image_count)
             .image_format(surface_format.format)
             .image_color_space(surface_format.color_space)
@@ -147,7 +161,7 @@ impl VkSwapchain {
             .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
             .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
             .pre_transform(surface_capabilities.current_transform)
-            .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+            .composite_alpha(composite_alpha)
             .present_mode(present_mode)
             .clipped(true);
 
@@ -173,6 +187,7 @@ impl VkSwapchain {
         let swapchain_info = SwapchainInfo {
             surface_format,
             present_mode,
+            composite_alpha,
             extents,
             image_count: image_count as usize
         };
@@ -221,18 +236,46 @@ impl VkSwapchain {
         }
     }
 
-    fn choose_present_mode(available_present_modes: &Vec<vk::PresentModeKHR>) -> vk::PresentModeKHR {
-        let mut best_present_mode = None;
+    fn choose_composite_alpha(
+        surface_capabilities: &vk::SurfaceCapabilitiesKHR,
+        composite_alpha: CompositeAlpha
+    ) -> vk::CompositeAlphaFlagsKHR {
+        info!("Supported composite alpha: {:?}", surface_capabilities.supported_composite_alpha);
 
-        info!("Available present modes: {:?}", available_present_modes);
-        for available_present_mode in available_present_modes {
-            if *available_present_mode == ash::vk::PresentModeKHR::MAILBOX {
-                best_present_mode = Some(available_present_mode)
-            }
+        if surface_capabilities.supported_composite_alpha.contains(composite_alpha.to_vk()) {
+            return composite_alpha.to_vk();
         }
 
+        // Per spec at least one of these is supported. INHERIT leaves it to the platform (i.e. how
+        // the window was created)
+        let fallback = [
+            vk::CompositeAlphaFlagsKHR::OPAQUE,
+            vk::CompositeAlphaFlagsKHR::INHERIT,
+            vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED,
+            vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED
+        ].iter()
+            .cloned()
+            .find(|x| surface_capabilities.supported_composite_alpha.contains(*x))
+            .unwrap_or(vk::CompositeAlphaFlagsKHR::OPAQUE);
+
+        warn!("Composite alpha {:?} is not supported, using {:?}", composite_alpha, fallback);
+        fallback
+    }
+
+    fn choose_present_mode(
+        available_present_modes: &Vec<vk::PresentModeKHR>,
+        present_mode_priority: &[PresentMode]
+    ) -> vk::PresentModeKHR {
+        info!("Available present modes: {:?}", available_present_modes);
+        info!("Preferred present modes: {:?}", present_mode_priority);
+
+        let best_present_mode = present_mode_priority
+            .iter()
+            .map(|present_mode| present_mode.to_vk())
+            .find(|present_mode| available_present_modes.contains(present_mode));
+
         match best_present_mode {
-            Some(present_mode) => *present_mode,
+            Some(present_mode) => present_mode,
             None => ash::vk::PresentModeKHR::FIFO // Per spec, FIFO always exists
         }
     }
@@ -263,7 +306,7 @@ impl VkSwapchain {
         swapchain_info: &SwapchainInfo,
         swapchain_images: &Vec<vk::Image>
     )
-        -> Vec<vk::ImageView>
+        -> VkResult<Vec<vk::ImageView>>
     {
         swapchain_images
             .iter()
@@ -287,8 +330,7 @@ impl VkSwapchain {
                     });
 
                 unsafe {
-                    //TODO: Return this
-                    logical_device.create_image_view(&create_view_info, None).unwrap()
+                    logical_device.create_image_view(&create_view_info, None)
                 }
             })
             .collect()
@@ -0,0 +1,476 @@
+use std::ffi::CString;
+use std::rc::Rc;
+
+use ash::version::DeviceV1_0;
+use ash::prelude::VkResult;
+
+use std::mem::ManuallyDrop;
+use ash::vk;
+
+use super::VkSharedContext;
+use super::VkSwapchain;
+use super::VkPipeline;
+use super::CapturedFrame;
+use super::Error;
+use super::DeviceSelectionPolicy;
+use super::PresentMode;
+use super::CompositeAlpha;
+use super::GpuTimings;
+use super::MAX_FRAMES_IN_FLIGHT;
+use crate::profiler::ScopeTimer;
+
+/// Draws to a window using vulkan. Skia draws to an offscreen surface, which is then blitted to the
+/// swapchain
+pub struct VkRenderer {
+    // Instance, device and skia context, possibly shared with renderers for other windows
+    shared_context: ManuallyDrop<Rc<VkSharedContext>>,
+
+    // Owned by this renderer, destroyed after the swapchain
+    surface: vk::SurfaceKHR,
+
+    swapchain: ManuallyDrop<VkSwapchain>,
+    pipeline: ManuallyDrop<VkPipeline>,
+
+    // Increase until > MAX_FRAMES_IN_FLIGHT, then set to 0, or -1 if no frame drawn yet
+    sync_frame_index: usize,
+
+    // Index of the skia surface that was drawn most recently, None if there is nothing to capture
+    previous_present_index: Option<usize>,
+
+    // The first supported present mode in this list is used when creating the swapchain
+    present_mode_priority: Vec<PresentMode>,
+
+    // Requested when creating the swapchain, the surface may not support it
+    composite_alpha: CompositeAlpha,
+
+    // Set when the swapchain is out of date but can't be recreated yet because the surface has
+    // zero size (i.e. the window is minimized)
+    swapchain_needs_recreate: bool,
+
+    // The most recent GPU timings that were read back
+    gpu_timings: Option<GpuTimings>
+}
+
+impl VkRenderer {
+    pub fn new(
+        app_name: &CString,
+        window: &winit::window::Window,
+        use_vulkan_debug_layer: bool,
+        device_selection_policy: &DeviceSelectionPolicy,
+        present_mode_priority: Vec<PresentMode>,
+        composite_alpha: CompositeAlpha
+    ) -> Result<VkRenderer, Error> {
+        let mut shared_context = VkSharedContext::new(app_name, window, use_vulkan_debug_layer, device_selection_policy)?;
+
+        // The device was selected using this window's surface, and this renderer will own it
+        let surface = shared_context.device.surface.take().unwrap();
+
+        Self::create(Rc::new(shared_context), surface, window, present_mode_priority, composite_alpha)
+    }
+
+    /// Create a renderer for another window that uses the same instance, device and skia context
+    /// as an existing renderer
+    pub fn new_shared(
+        shared_context: Rc<VkSharedContext>,
+        window: &winit::window::Window,
+        present_mode_priority: Vec<PresentMode>,
+        composite_alpha: CompositeAlpha
+    ) -> Result<VkRenderer, Error> {
+        let surface = shared_context.device.create_surface(&shared_context.instance, window)?;
+        Self::create(shared_context, surface, window, present_mode_priority, composite_alpha)
+    }
+
+    fn create(
+        shared_context: Rc<VkSharedContext>,
+        surface: vk::SurfaceKHR,
+        window: &winit::window::Window,
+        present_mode_priority: Vec<PresentMode>,
+        composite_alpha: CompositeAlpha
+    ) -> Result<VkRenderer, Error> {
+        let (swapchain, pipeline) = match Self::create_swapchain_and_pipeline(
+            &shared_context,
+            &surface,
+            window,
+            &present_mode_priority,
+            composite_alpha
+        ) {
+            Ok(result) => result,
+            Err(e) => {
+                // This renderer owns the surface. It must be destroyed before the shared context is
+                // dropped, which may destroy the instance
+                unsafe {
+                    shared_context.device.surface_loader.destroy_surface(surface, None);
+                }
+
+                return Err(e);
+            }
+        };
+
+        let sync_frame_index = 0;
+        let previous_present_index = None;
+
+        Ok(VkRenderer {
+            shared_context: ManuallyDrop::new(shared_context),
+            surface,
+            swapchain: ManuallyDrop::new(swapchain),
+            pipeline: ManuallyDrop::new(pipeline),
+            sync_frame_index,
+            previous_present_index,
+            present_mode_priority,
+            composite_alpha,
+            swapchain_needs_recreate: false,
+            gpu_timings: None
+        })
+    }
+
+    fn create_swapchain_and_pipeline(
+        shared_context: &VkSharedContext,
+        surface: &vk::SurfaceKHR,
+        window: &winit::window::Window,
+        present_mode_priority: &[PresentMode],
+        composite_alpha: CompositeAlpha
+    ) -> Result<(VkSwapchain, VkPipeline), Error> {
+        let swapchain = VkSwapchain::new(
+            &shared_context.instance,
+            &shared_context.device,
+            surface,
+            vk::SwapchainKHR::null(),
+            window,
+            present_mode_priority,
+            composite_alpha
+        )?;
+
+        let pipeline = VkPipeline::new(
+            &shared_context.device,
+            &swapchain,
+            &mut shared_context.skia_context.borrow_mut()
+        )?;
+
+        Ok((swapchain, pipeline))
+    }
+
+    /// The instance, device and skia context used by this renderer. Pass it to `new_shared` to draw
+    /// to another window with the same device.
+    pub fn shared_context(&self) -> &Rc<VkSharedContext> {
+        &self.shared_context
+    }
+
+    /// How long the GPU spent on a recent frame. None if timestamp queries aren't supported or no
+    /// results have been read back yet.
+    pub fn gpu_timings(&self) -> Option<GpuTimings> {
+        self.gpu_timings
+    }
+
+    /// The present mode the swapchain is currently using
+    pub fn present_mode(&self) -> Option<PresentMode> {
+        PresentMode::from_vk(self.swapchain.swapchain_info.present_mode)
+    }
+
+    pub fn present_mode_priority(&self) -> &[PresentMode] {
+        &self.present_mode_priority
+    }
+
+    /// The composite alpha the swapchain is currently using. This may differ from the one that was
+    /// requested if the surface doesn't support it
+    pub fn composite_alpha(&self) -> Option<CompositeAlpha> {
+        CompositeAlpha::from_vk(self.swapchain.swapchain_info.composite_alpha)
+    }
+
+    pub fn requested_composite_alpha(&self) -> CompositeAlpha {
+        self.composite_alpha
+    }
+
+    /// Returns false while the window's surface has zero size, which some platforms report without
+    /// resizing the window to zero. Once the surface has a size again the swapchain is rebuilt and
+    /// this returns true
+    pub fn poll_surface_drawable(&mut self, window: &winit::window::Window) -> Result<bool, Error> {
+        if self.swapchain_needs_recreate {
+            self.recreate_swapchain(window)?;
+        }
+
+        Ok(!self.swapchain_needs_recreate)
+    }
+
+    /// Change the preferred present modes. The swapchain is recreated immediately so the change
+    /// takes effect on the next frame.
+    pub fn set_present_mode_priority(
+        &mut self,
+        window: &winit::window::Window,
+        present_mode_priority: Vec<PresentMode>
+    ) -> Result<(), Error> {
+        self.present_mode_priority = present_mode_priority;
+        self.recreate_swapchain(window)
+    }
+
+    fn recreate_swapchain(&mut self, window: &winit::window::Window) -> Result<(), Error> {
+        //TODO: How does it work to render from another thread?
+        let shared_context = &self.shared_context;
+
+        // A swapchain can't have zero extent. Keep the old one until the window has a size again
+        let surface_capabilities = unsafe {
+            shared_context.device.surface_loader
+                .get_physical_device_surface_capabilities(shared_context.device.physical_device, self.surface)?
+        };
+
+        if surface_capabilities.current_extent.width == 0 || surface_capabilities.current_extent.height == 0 {
+            debug!("Surface has zero size, not recreating the swapchain");
+            self.swapchain_needs_recreate = true;
+            return Ok(());
+        }
+
+        self.swapchain_needs_recreate = false;
+
+        unsafe {
+            shared_context.device.logical_device.device_wait_idle()?;
+        }
+
+        // Build the replacements before destroying anything, so that on failure this renderer still
+        // holds a valid (if out of date) swapchain and pipeline. The old swapchain is retired by
+        // passing it to the new one
+        let swapchain = VkSwapchain::new(
+            &shared_context.instance,
+            &shared_context.device,
+            &self.surface,
+            self.swapchain.swapchain,
+            window,
+            &self.present_mode_priority,
+            self.composite_alpha
+        )?;
+
+        let pipeline = VkPipeline::new(
+            &shared_context.device,
+            &swapchain,
+            &mut shared_context.skia_context.borrow_mut()
+        )?;
+
+        self.previous_present_index = None;
+        let old_pipeline = std::mem::replace(&mut *self.pipeline, pipeline);
+        let old_swapchain = std::mem::replace(&mut *self.swapchain, swapchain);
+
+        // The pipeline uses the swapchain's images, so it goes first
+        std::mem::drop(old_pipeline);
+        std::mem::drop(old_swapchain);
+        Ok(())
+    }
+
+    /// Read back the pixels of the most recently drawn frame. The returned frame is the size of
+    /// the swapchain in physical pixels. Returns None if no frame has been drawn since the renderer
+    /// was created or the swapchain was last rebuilt.
+    pub fn capture_frame(&mut self) -> Result<Option<CapturedFrame>, Error> {
+        let present_index = match self.previous_present_index {
+            Some(present_index) => present_index,
+            None => return Ok(None)
+        };
+
+        // Make sure the GPU is done with the frame before reading from it
+        unsafe {
+            self.shared_context.device.logical_device.device_wait_idle()?;
+        }
+
+        let surface = self.pipeline.skia_surface(present_index);
+
+        CapturedFrame::read_from_surface(&mut surface.surface)
+            .map(Some)
+            .ok_or(Error::PixelReadback)
+    }
+
+    /// Draw a frame. Does nothing while the window has zero size (i.e. is minimized)
+    pub fn draw<F : FnOnce(&mut skia_safe::Canvas)>(
+        &mut self,
+        window: &winit::window::Window,
+        f: F
+    ) -> Result<(), Error> {
+        let physical_size = window.inner_size().to_physical(window.hidpi_factor());
+        if physical_size.width < 1.0 || physical_size.height < 1.0 {
+            return Ok(());
+        }
+
+        if self.swapchain_needs_recreate {
+            self.recreate_swapchain(window)?;
+
+            // The surface may still have zero size even though the window doesn't
+            if self.swapchain_needs_recreate {
+                return Ok(());
+            }
+        }
+
+        let result = self.do_draw(window, f);
+        if let Err(e) = result {
+            match e {
+                ash::vk::Result::ERROR_OUT_OF_DATE_KHR => {
+                    //TODO: Clean the do_draw stuff up
+                    self.recreate_swapchain(window)
+                },
+                ash::vk::Result::SUCCESS => {
+                    Ok(())
+                },
+                ash::vk::Result::SUBOPTIMAL_KHR => {
+                    Ok(())
+                },
+                _ => {
+                    warn!("Unexpected rendering error: {:?}", e);
+                    Err(e.into())
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    fn do_draw<F : FnOnce(&mut skia_safe::Canvas)>(
+        &mut self,
+        window: &winit::window::Window,
+        f: F
+    )
+        -> VkResult<()>
+    {
+        let frame_fence = self.swapchain.in_flight_fences[self.sync_frame_index];
+
+        //TODO: Dont lock up forever (don't use std::u64::MAX)
+        //TODO: Can part of this run in a separate thread from the window pump?
+        //TODO: Explore an option that ensures we receive the same skia canvas back every draw call.
+        // This may require a copy from a surface that is not use in the swapchain into one that is
+
+        // Wait if two frame are already in flight
+        {
+            let _scope_timer = ScopeTimer::new("wait for frame fence");
+            unsafe {
+                self.shared_context.device.logical_device.wait_for_fences(&[frame_fence], true, std::u64::MAX)?;
+                self.shared_context.device.logical_device.reset_fences(&[frame_fence])?;
+            }
+        }
+
+        // Everything submitted the last time this frame was drawn has finished, so its timings can
+        // be read. If they can be, the frame's timestamp command buffers are free to submit again
+        let write_timestamps = match &mut self.pipeline.gpu_timestamps {
+            Some(gpu_timestamps) => {
+                if let Some(gpu_timings) = gpu_timestamps.read_results(self.sync_frame_index) {
+                    self.gpu_timings = Some(gpu_timings);
+                }
+
+                gpu_timestamps.can_submit(self.sync_frame_index)
+            },
+            None => false
+        };
+
+        let (present_index, _is_suboptimal) = unsafe {
+            let _scope_timer = ScopeTimer::new("acquire next image");
+            self.swapchain
+                .swapchain_loader
+                .acquire_next_image(
+                    self.swapchain.swapchain,
+                    std::u64::MAX,
+                    self.swapchain.image_available_semaphores[self.sync_frame_index],
+                    vk::Fence::null(),
+                )?
+        };
+
+        // Submit the commands that reset the queries and write the timestamp before skia's work
+        if let (true, Some(gpu_timestamps)) = (write_timestamps, &self.pipeline.gpu_timestamps) {
+            let command_buffers = [gpu_timestamps.begin_command_buffers[self.sync_frame_index]];
+            let submit_info = [
+                vk::SubmitInfo::builder()
+                    .command_buffers(&command_buffers)
+                    .build()
+            ];
+
+            unsafe {
+                self.shared_context.device.logical_device
+                    .queue_submit(self.shared_context.device.queues.graphics_queue, &submit_info, vk::Fence::null())?;
+            }
+        }
+
+        {
+            let surface = self.pipeline.skia_surface(present_index as usize);
+            let mut canvas = surface.surface.canvas();
+
+            // To handle hi-dpi displays, we need to compare the logical size of the window with the
+            // actual canvas size. Critically, the canvas size won't necessarily be the size of the
+            // window in physical pixels.
+            let window_size = window.inner_size();
+            let scale = (
+                (self.swapchain.swapchain_info.extents.width as f64 / window_size.width) as f32,
+                (self.swapchain.swapchain_info.extents.height as f64 / window_size.height) as f32
+            );
+
+            canvas.reset_matrix();
+            canvas.scale(scale);
+
+            f(&mut canvas);
+
+            let _scope_timer = ScopeTimer::new("skia flush");
+            canvas.flush();
+        }
+
+        let wait_semaphores = [self.swapchain.image_available_semaphores[self.sync_frame_index]];
+        let signal_semaphores = [self.swapchain.render_finished_semaphores[self.sync_frame_index]];
+
+        let wait_dst_stage_mask = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+        let blit_command_buffer = self.pipeline.command_buffers[present_index as usize];
+        let command_buffers = match (write_timestamps, &self.pipeline.gpu_timestamps) {
+            (true, Some(gpu_timestamps)) => vec![
+                gpu_timestamps.blit_begin_command_buffers[self.sync_frame_index],
+                blit_command_buffer,
+                gpu_timestamps.blit_end_command_buffers[self.sync_frame_index]
+            ],
+            _ => vec![blit_command_buffer]
+        };
+
+        //add fence to queue submit
+        let submit_info = [
+            vk::SubmitInfo::builder()
+                .wait_semaphores(&wait_semaphores)
+                .signal_semaphores(&signal_semaphores)
+                .wait_dst_stage_mask(&wait_dst_stage_mask)
+                .command_buffers(&command_buffers)
+                .build()
+        ];
+
+        unsafe {
+            let _scope_timer = ScopeTimer::new("queue submit");
+            self.shared_context.device.logical_device
+                .queue_submit(self.shared_context.device.queues.graphics_queue, &submit_info, frame_fence)?;
+        }
+
+        if let (true, Some(gpu_timestamps)) = (write_timestamps, &mut self.pipeline.gpu_timestamps) {
+            gpu_timestamps.mark_submitted(self.sync_frame_index);
+        }
+
+        let wait_semaphors = [self.swapchain.render_finished_semaphores[self.sync_frame_index]];
+        let swapchains = [self.swapchain.swapchain];
+        let image_indices = [present_index];
+        let present_info = vk::PresentInfoKHR::builder()
+            .wait_semaphores(&wait_semaphors) // &base.rendering_complete_semaphore)
+            .swapchains(&swapchains)
+            .image_indices(&image_indices);
+
+        unsafe {
+            let _scope_timer = ScopeTimer::new("queue present");
+            self.swapchain.swapchain_loader
+                .queue_present(self.shared_context.device.queues.present_queue, &present_info)?;
+        }
+
+        self.sync_frame_index = (self.sync_frame_index + 1) % MAX_FRAMES_IN_FLIGHT;
+        self.previous_present_index = Some(present_index as usize);
+
+        Ok(())
+    }
+}
+
+impl Drop for VkRenderer {
+    fn drop(&mut self) {
+        info!("destroying VkRenderer");
+
+        unsafe {
+            self.shared_context.device.logical_device.device_wait_idle().unwrap();
+            ManuallyDrop::drop(&mut self.pipeline);
+            ManuallyDrop::drop(&mut self.swapchain);
+            self.shared_context.device.surface_loader.destroy_surface(self.surface, None);
+
+            // Destroys the device and instance if no other renderer is using them
+            ManuallyDrop::drop(&mut self.shared_context);
+        }
+
+        info!("destroyed VkRenderer");
+    }
+}
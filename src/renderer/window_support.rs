@@ -148,3 +148,10 @@ pub fn extension_names() -> Vec<*const i8> {
         DebugReport::name().as_ptr(),
     ]
 }
+
+/// Extensions we want to use when there is no window to present to
+pub fn headless_extension_names() -> Vec<*const i8> {
+    vec![
+        DebugReport::name().as_ptr(),
+    ]
+}